@@ -0,0 +1,1510 @@
+use crate::{
+    config::{Config, ConnectionProfile, Rotation},
+    crash::RecentLogs,
+    data::{AppData, SavedDevice, SortOrder},
+    input::{Action, Binding, Direction, Keymap},
+    power_supply::HandheldBattery,
+    signals::{ActionSignals, QuitSignals, SignalAction},
+    tone::TestTone,
+    ui::{
+        screens::{
+            AboutScreen, AudioTestScreen, AutoConnectScreen, DeviceSettingsScreen,
+            HiddenDevicesScreen, LogsScreen, MainScreen, NetworkScreen, PinEntryScreen,
+            ProfilesScreen, RemapScreen, Screen, SettingsRow,
+        },
+        texts,
+    },
+};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{
+    AppState, AudioProfile, BluetoothConnectState, BluetoothScanStatus, BluezVersion, Command,
+    DeviceInfo, Error, ManagerHandle, TetherState,
+};
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+use std::{fs, path::PathBuf};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        watch,
+    },
+    time::{interval, sleep, sleep_until, Interval, MissedTickBehavior},
+};
+use tracing::{info, warn};
+
+// the least time the quitting message stays on the screen
+const QUIT_DELAY: Duration = Duration::from_millis(50);
+
+// the volume changes by this many percent per press
+const VOLUME_STEP: u8 = 10;
+
+// left and right scroll the log screen by this many lines
+const LOGS_PAGE: usize = 10;
+
+// the longest PIN of Bluetooth
+const MAX_PIN_LEN: usize = 16;
+
+// how often the scan countdown is redrawn, often enough to not skip a second
+const COUNTDOWN_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Everything the main loop reacts to.
+enum AppEvent {
+    Input(Action),
+    /// The `BluetoothManager` published a new `AppState`.
+    StateChanged,
+    /// The config file was changed on disk.
+    ConfigChanged,
+    Signal(&'static str),
+    SignalAction(SignalAction),
+    /// Time to redraw the scan countdown.
+    Tick,
+    /// The device list was left alone long enough to look for new devices.
+    IdleRescan,
+    /// The battery of the handheld changed.
+    BatteryChanged,
+}
+
+/// Progress of the remap wizard, which asks for a key or button for each of
+/// `Keymap::REMAP_ACTIONS` in turn.
+struct Remap {
+    keymap: Keymap,
+    step: usize,
+    // already given to an earlier action
+    taken: Vec<Binding>,
+    // the last press was taken already
+    rejected: bool,
+}
+
+/// The test tones, opened from the settings of a device.
+struct AudioTest {
+    addr: Address,
+    // index into `TestTone::ALL`
+    selected_index: usize,
+    playing: Option<TestTone>,
+}
+
+/// The PIN typed for a legacy device which refused the default ones.
+struct PinEntry {
+    addr: Address,
+    digits: String,
+    // index of the key under the cursor
+    cursor: usize,
+}
+
+pub struct App {
+    rotation: Rotation,
+    // the cursor starts on it after a scan
+    preferred_device: Option<Address>,
+    // the device connected at start, shown instead of the list until any input
+    auto_connect: Option<Address>,
+
+    is_running: bool,
+
+    // the latest state published by the `BluetoothManager`
+    state: AppState,
+    // when the running scan started, for its countdown
+    scan_started: Option<Instant>,
+    countdown: Interval,
+    // when the device list is rescanned in the background, while it's shown
+    // with nothing connected
+    idle_rescan_at: Option<Instant>,
+    // the window is in the background, nothing is scanned then
+    backgrounded: bool,
+
+    selected_bluetooth_device_index: usize,
+    // how many of the devices are listed, the others are behind the last row
+    listed: usize,
+    // the last row was picked, every device is listed until the next scan
+    list_expanded: bool,
+
+    // shown on top of the main screen until the next input
+    notice: Option<String>,
+    // a device which doesn't look like an audio device, connected when A is
+    // pressed again right away
+    confirm_connect: Option<Address>,
+
+    // the device whose settings are shown instead of the list, and the row
+    // under the cursor
+    settings: Option<(Address, usize)>,
+    // the selected row while the hidden devices are shown instead of the list
+    hidden_list: Option<usize>,
+    // the addresses hidden by the config, changed here before the config file
+    // is reloaded
+    hidden_devices: Vec<String>,
+    // like `hidden_devices`
+    hide_unnamed_devices: bool,
+    // the selected row while the connection profiles are shown instead of the
+    // list
+    profiles: Option<usize>,
+    // the selected row while the tethering phones are shown instead of the
+    // list
+    network: Option<usize>,
+    // shown over the settings it's opened from
+    audio_test: Option<AudioTest>,
+    // shown instead of everything else while remapping
+    remap: Option<Remap>,
+    // shown instead of the list until the PIN is entered or cancelled
+    pin_entry: Option<PinEntry>,
+    // how far the log screen is scrolled up from the newest line, shown on
+    // top of everything but the remap wizard
+    logs: Option<usize>,
+    recent_logs: RecentLogs,
+    // the battery of the handheld, a pairing fails when it dies
+    handheld_battery: Option<HandheldBattery>,
+    battery_rx: watch::Receiver<Option<HandheldBattery>>,
+    // the versions are shown instead of the list
+    about: bool,
+    kernel_version: Option<String>,
+
+    data: AppData,
+    data_path: PathBuf,
+    config_path: PathBuf,
+
+    manager: ManagerHandle,
+    action_rx: mpsc::UnboundedReceiver<Action>,
+    screen_tx: watch::Sender<Screen>,
+    config_rx: watch::Receiver<Config>,
+    signals: QuitSignals,
+    action_signals: ActionSignals,
+}
+
+impl App {
+    pub fn new(
+        manager: ManagerHandle, action_rx: mpsc::UnboundedReceiver<Action>,
+        screen_tx: watch::Sender<Screen>, mut config_rx: watch::Receiver<Config>,
+        signals: QuitSignals, action_signals: ActionSignals, config_path: PathBuf,
+        battery_rx: watch::Receiver<Option<HandheldBattery>>,
+    ) -> Self {
+        let config = config_rx.borrow_and_update().clone();
+        let data_path = AppData::path();
+        let data = AppData::load(&data_path);
+        Self {
+            rotation: config.rotation,
+            preferred_device: config.preferred_device(),
+            auto_connect: config.manager_options(&data).auto_connect,
+            is_running: true,
+            state: AppState::default(),
+            scan_started: None,
+            countdown: countdown_interval(),
+            idle_rescan_at: None,
+            backgrounded: false,
+            selected_bluetooth_device_index: 0,
+            listed: 0,
+            list_expanded: false,
+            notice: None,
+            confirm_connect: None,
+            settings: None,
+            hidden_list: None,
+            hidden_devices: config.hidden_devices.clone(),
+            hide_unnamed_devices: config.hide_unnamed_devices,
+            profiles: None,
+            network: None,
+            audio_test: None,
+            remap: None,
+            pin_entry: None,
+            logs: None,
+            recent_logs: RecentLogs::default(),
+            handheld_battery: None,
+            battery_rx,
+            about: false,
+            kernel_version: fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .map(|release| release.trim().to_string()),
+            data,
+            data_path,
+            config_path,
+            manager,
+            action_rx,
+            screen_tx,
+            config_rx,
+            signals,
+            action_signals,
+        }
+    }
+
+    pub fn show_notice(&mut self, notice: String) {
+        self.notice = Some(notice);
+    }
+
+    /// The log lines shown by the log screen.
+    pub fn set_recent_logs(&mut self, recent_logs: RecentLogs) {
+        self.recent_logs = recent_logs;
+    }
+
+    /// Warn once each time the battery of the handheld gets low.
+    fn update_battery(&mut self, battery: Option<HandheldBattery>) {
+        let is_low =
+            |battery: Option<HandheldBattery>| battery.is_some_and(|battery| battery.is_low());
+        if is_low(battery) && !is_low(self.handheld_battery) {
+            warn!(?battery, "handheld battery is low");
+            let texts = texts::texts(self.config_rx.borrow().language);
+            self.notice = Some(texts.low_battery.to_string());
+        }
+        self.handheld_battery = battery;
+    }
+
+    /// Tell why a connect press does nothing while another connect runs.
+    fn notify_busy(&mut self) {
+        let texts = texts::texts(self.config_rx.borrow().language);
+        self.notice = Some(texts.connect_busy.to_string());
+    }
+
+    /// Hand the command to the manager, telling when it can't take more so
+    /// the press isn't lost silently.
+    fn send(&mut self, command: Command) {
+        match self.manager.command_tx.try_send(command) {
+            Ok(()) => {}
+            Err(TrySendError::Full(command)) => {
+                warn!(?command, "bluetooth manager is busy, drop command");
+                let texts = texts::texts(self.config_rx.borrow().language);
+                self.notice = Some(texts.manager_busy.to_string());
+            }
+            Err(TrySendError::Closed(command)) => {
+                warn!(?command, "bluetooth manager is gone, drop command");
+            }
+        }
+    }
+
+    /// Ask for new keys and buttons, starting from the current keymap.
+    pub fn start_remap(&mut self) {
+        info!("start remapping");
+        self.remap = Some(Remap {
+            keymap: self.config_rx.borrow().keymap.clone(),
+            step: 0,
+            taken: Vec::new(),
+            rejected: false,
+        });
+    }
+
+    pub async fn run(mut self) -> anyhow::Result<()> {
+        let battery = *self.battery_rx.borrow_and_update();
+        self.update_battery(battery);
+        self.screen_tx.send_replace(self.screen());
+
+        while let Some(event) = self.next_event().await {
+            // a focus change isn't a press, it doesn't start the idle wait over
+            let input = !matches!(event, AppEvent::Input(Action::Background(_)))
+                && matches!(event, AppEvent::Input(_));
+            match event {
+                AppEvent::Input(Action::Background(backgrounded)) => {
+                    self.set_backgrounded(backgrounded)
+                }
+                AppEvent::Input(action) => self.handle_action(action),
+                AppEvent::StateChanged => {
+                    let state = self.manager.state_rx.borrow_and_update().clone();
+                    self.update_state(state);
+                }
+                AppEvent::ConfigChanged => {
+                    let config = self.config_rx.borrow_and_update().clone();
+                    self.apply_config(&config);
+                }
+                AppEvent::Signal(name) => {
+                    info!(signal = name, "quit by signal");
+                    self.is_running = false;
+                }
+                AppEvent::SignalAction(action) => {
+                    info!(?action, "handle signal action");
+                    self.handle_signal_action(action);
+                }
+                AppEvent::Tick => {}
+                AppEvent::BatteryChanged => {
+                    let battery = *self.battery_rx.borrow_and_update();
+                    self.update_battery(battery);
+                }
+                AppEvent::IdleRescan => {
+                    info!("rescan the idle device list");
+                    self.idle_rescan_at = None;
+                    // nobody waits for it, a busy manager just skips it
+                    let _ = self.manager.command_tx.try_send(Command::BackgroundScan);
+                }
+            }
+
+            if !self.is_running {
+                break;
+            }
+
+            self.schedule_idle_rescan(input);
+
+            self.screen_tx.send_replace(self.screen());
+        }
+
+        self.save_ui_prefs();
+
+        // 退出前先停止蓝牙操作
+        self.screen_tx.send_replace(Screen::Quitting);
+        sleep(QUIT_DELAY).await;
+        self.manager.shutdown().await;
+
+        Ok(())
+    }
+
+    /// Wait for the next input, state change or signal, `None` once the SDL
+    /// thread or the manager has gone away.
+    async fn next_event(&mut self) -> Option<AppEvent> {
+        tokio::select! {
+            action = self.action_rx.recv() => action.map(AppEvent::Input),
+            changed = self.manager.state_rx.changed() => changed.ok().map(|()| AppEvent::StateChanged),
+            changed = self.config_rx.changed() => changed.ok().map(|()| AppEvent::ConfigChanged),
+            Ok(()) = self.battery_rx.changed() => Some(AppEvent::BatteryChanged),
+            name = self.signals.recv() => Some(AppEvent::Signal(name)),
+            action = self.action_signals.recv() => Some(AppEvent::SignalAction(action)),
+            _ = self.countdown.tick(), if self.scan_started.is_some() => Some(AppEvent::Tick),
+            () = sleep_until(self.idle_rescan_at.unwrap_or_else(Instant::now).into()),
+                if self.idle_rescan_at.is_some() => Some(AppEvent::IdleRescan),
+        }
+    }
+
+    /// Stop scanning while the window is in the background, the discovery
+    /// costs battery and nobody looks at the list.
+    fn set_backgrounded(&mut self, backgrounded: bool) {
+        if backgrounded == self.backgrounded {
+            return;
+        }
+        info!(backgrounded, "window focus changed");
+        self.backgrounded = backgrounded;
+        // also stops an idle rescan, which any command does
+        if backgrounded {
+            self.send(Command::StopScan);
+        }
+    }
+
+    /// Start waiting for the idle rescan once the finished list is shown with
+    /// nothing connected, input starts the wait over.
+    fn schedule_idle_rescan(&mut self, input: bool) {
+        let on_list = !self.backgrounded
+            && self.remap.is_none()
+            && self.logs.is_none()
+            && !self.about
+            && self.pin_entry.is_none()
+            && self.settings.is_none()
+            && self.hidden_list.is_none()
+            && self.profiles.is_none()
+            && self.network.is_none()
+            && self.auto_connect.is_none();
+        let idle = on_list
+            && self.state.powered
+            && self.state.scan_status == BluetoothScanStatus::Finished
+            && self.state.connected_device().is_none()
+            && !self.state.connect_state.is_in_progress();
+
+        let idle_rescan = self.config_rx.borrow().idle_rescan();
+        match idle_rescan {
+            Some(after) if idle => {
+                if input || self.idle_rescan_at.is_none() {
+                    self.idle_rescan_at = Some(Instant::now() + after);
+                }
+            }
+            _ => self.idle_rescan_at = None,
+        }
+    }
+
+    /// Rotation needs a restart, theme and language are applied by the SDL
+    /// thread itself.
+    fn apply_config(&mut self, config: &Config) {
+        self.preferred_device = config.preferred_device();
+        self.hidden_devices = config.hidden_devices.clone();
+        self.hide_unnamed_devices = config.hide_unnamed_devices;
+        let mut devices = std::mem::take(&mut self.state.devices);
+        self.listed = self.arrange_devices(&mut devices);
+        if self.selected_bluetooth_device_index >= self.list_rows(&devices) {
+            self.selected_bluetooth_device_index = 0;
+        }
+        self.state.devices = devices;
+        if let Some(index) = &mut self.profiles {
+            *index = (*index).min(config.profiles.len().saturating_sub(1));
+        }
+        self.send(Command::SetOptions(config.manager_options(&self.data)));
+    }
+
+    fn update_state(&mut self, mut state: AppState) {
+        if state.scan_status == BluetoothScanStatus::Scanning
+            && self.state.scan_status != BluetoothScanStatus::Scanning
+        {
+            self.list_expanded = false;
+        }
+        let listed = self.arrange_devices(&mut state.devices);
+
+        // a background scan adds devices to the shown list, keep the cursor
+        // on its device
+        if state.scan_status == BluetoothScanStatus::Finished
+            && self.state.scan_status == BluetoothScanStatus::Finished
+        {
+            if let Some(addr) = self.selected_device().map(|info| info.addr) {
+                if let Some(index) = state.devices.iter().position(|info| info.addr == addr) {
+                    self.selected_bluetooth_device_index = index;
+                }
+            }
+        }
+        self.listed = listed;
+
+        if state.powered != self.state.powered {
+            self.selected_bluetooth_device_index = 0;
+        }
+        if state.scan_status != BluetoothScanStatus::Scanning {
+            self.scan_started = None;
+        } else if self.state.scan_status != BluetoothScanStatus::Scanning {
+            self.scan_started = Some(Instant::now());
+        }
+        if self.selected_bluetooth_device_index >= self.list_rows(&state.devices) {
+            self.selected_bluetooth_device_index = 0;
+        }
+        if state.scan_status == BluetoothScanStatus::Finished
+            && self.state.scan_status != BluetoothScanStatus::Finished
+        {
+            let last_selected = self
+                .data
+                .ui
+                .last_selected
+                .as_deref()
+                .and_then(|addr| addr.parse().ok());
+            let start_device = self.preferred_device.or(last_selected);
+            if let Some(index) = state
+                .devices
+                .iter()
+                .position(|info| Some(info.addr) == start_device)
+            {
+                self.selected_bluetooth_device_index = index;
+            }
+        }
+        if let Some(addr) = state.last_connected {
+            if state.last_connected != self.state.last_connected {
+                self.remember_last_device(addr, &state);
+            }
+        }
+        if let BluetoothConnectState::Failed {
+            error: Error::PinRequired(addr),
+            ..
+        } = state.connect_state
+        {
+            if state.connect_state != self.state.connect_state {
+                self.pin_entry = Some(PinEntry {
+                    addr,
+                    digits: String::new(),
+                    cursor: 0,
+                });
+            }
+        }
+        if state.audio_switched_away != self.state.audio_switched_away
+            && state.connected_device().is_some()
+        {
+            let texts = texts::texts(self.config_rx.borrow().language);
+            self.notice = Some(if state.audio_switched_away {
+                texts.audio_switched_away.to_string()
+            } else {
+                texts.audio_returned.to_string()
+            });
+        }
+        if state.bluez_busy != self.state.bluez_busy {
+            let texts = texts::texts(self.config_rx.borrow().language);
+            self.notice = state.bluez_busy.then(|| texts.bluez_busy.to_string());
+        }
+        if let Some(version) = state.bluez_version {
+            if version.is_outdated() && state.bluez_version != self.state.bluez_version {
+                let texts = texts::texts(self.config_rx.borrow().language);
+                self.notice = Some((texts.bluez_outdated)(
+                    &version.to_string(),
+                    &BluezVersion::MINIMUM.to_string(),
+                ));
+            }
+        }
+        if let Some((addr, pin)) = &state.paired_pin {
+            if state.paired_pin != self.state.paired_pin && self.data.set_pin(*addr, pin.clone()) {
+                if let Err(err) = self.data.save(&self.data_path) {
+                    warn!(?err, "save pin failed");
+                }
+            }
+        }
+        self.state = state;
+        if let Some(index) = &mut self.network {
+            *index = (*index).min(self.tethering_phones().len().saturating_sub(1));
+        }
+    }
+
+    fn remember_last_device(&mut self, addr: Address, state: &AppState) {
+        // a device reconnected without a scan isn't in the list, keep its name
+        let name = match state.devices.iter().find(|info| info.addr == addr) {
+            Some(info) => info.name.clone(),
+            None => self
+                .data
+                .last_device
+                .as_ref()
+                .filter(|device| device.addr() == Some(addr))
+                .map(|device| device.name.clone())
+                .unwrap_or_default(),
+        };
+
+        self.data.last_device = Some(SavedDevice {
+            address: addr.to_string(),
+            name,
+        });
+        if let Err(err) = self.data.save(&self.data_path) {
+            warn!(?err, "save last device failed");
+        }
+    }
+
+    /// Move the favorites to the top of the list, in the order they were added.
+    fn pin_favorites(&self, devices: &mut [DeviceInfo]) {
+        devices.sort_by_key(|info| self.data.favorite_index(info.addr).unwrap_or(usize::MAX));
+    }
+
+    /// Pin the favorites and, when there are more devices than listed, put
+    /// the connected, paired and strongest ones before the others. The listed
+    /// ones are in the sort order picked.
+    /// Returns how many are listed.
+    fn arrange_devices(&self, devices: &mut [DeviceInfo]) -> usize {
+        let limit = self.config_rx.borrow().max_listed_devices;
+        let limited = !self.list_expanded && limit > 0 && devices.len() > limit;
+        if limited {
+            devices.sort_by_key(|info| {
+                (
+                    !info.connected,
+                    !info.paired,
+                    Reverse(info.rssi.unwrap_or(i16::MIN)),
+                )
+            });
+        }
+        let listed = if limited { limit } else { devices.len() };
+        self.sort_devices(&mut devices[..listed]);
+        // stable, the ranking stays among the favorites and the others
+        self.pin_favorites(devices);
+        listed
+    }
+
+    fn sort_devices(&self, devices: &mut [DeviceInfo]) {
+        match self.data.ui.sort {
+            SortOrder::Found => {}
+            SortOrder::Name => devices.sort_by_cached_key(|info| {
+                self.data
+                    .nickname(info.addr)
+                    .unwrap_or(&info.name)
+                    .to_lowercase()
+            }),
+            SortOrder::Signal => devices.sort_by_key(|info| Reverse(info.rssi.unwrap_or(i16::MIN))),
+        }
+    }
+
+    /// The rows of the list, the devices listed and the row of the others.
+    fn list_rows(&self, devices: &[DeviceInfo]) -> usize {
+        self.listed + usize::from(self.listed < devices.len())
+    }
+
+    /// Sort the list by the next order, the cursor stays on its device.
+    fn cycle_sort(&mut self) {
+        let sort = self.data.ui.sort.next();
+        info!(?sort, "sort devices");
+        self.data.ui.sort = sort;
+        if let Err(err) = self.data.save(&self.data_path) {
+            warn!(?err, "save sort order failed");
+        }
+
+        let selected = self.selected_device().map(|info| info.addr);
+        let mut devices = std::mem::take(&mut self.state.devices);
+        self.listed = self.arrange_devices(&mut devices);
+        if let Some(index) = devices.iter().position(|info| Some(info.addr) == selected) {
+            self.selected_bluetooth_device_index = index;
+        }
+        self.state.devices = devices;
+
+        let texts = texts::texts(self.config_rx.borrow().language);
+        let index = SortOrder::ALL
+            .iter()
+            .position(|order| *order == sort)
+            .unwrap_or(0);
+        self.notice = Some(format!("{}{}", texts.sort_title, texts.sort_names[index]));
+    }
+
+    fn toggle_favorite(&mut self) {
+        let Some(device) = self.selected_device().cloned() else {
+            return;
+        };
+
+        match self.data.favorite_index(device.addr) {
+            Some(index) => {
+                self.data.favorites.remove(index);
+            }
+            None => self.data.favorites.push(SavedDevice {
+                address: device.addr.to_string(),
+                name: device.name,
+            }),
+        }
+        if let Err(err) = self.data.save(&self.data_path) {
+            warn!(?err, "save favorites failed");
+        }
+
+        // keep the cursor on the device while it moves
+        let mut devices = std::mem::take(&mut self.state.devices);
+        self.listed = self.arrange_devices(&mut devices);
+        if let Some(index) = devices.iter().position(|info| info.addr == device.addr) {
+            self.selected_bluetooth_device_index = index;
+        }
+        self.state.devices = devices;
+    }
+
+    fn save_ui_prefs(&mut self) {
+        let Some(device) = self.selected_device() else {
+            return;
+        };
+        self.data.ui.last_selected = Some(device.addr.to_string());
+        if let Err(err) = self.data.save(&self.data_path) {
+            warn!(?err, "save ui preferences failed");
+        }
+    }
+
+    /// The remembered device, unless it's connected right now.
+    fn reconnect_target(&self) -> Option<&SavedDevice> {
+        let device = self.data.last_device.as_ref()?;
+        let connected = self
+            .state
+            .connected_device()
+            .is_some_and(|info| Some(info.addr) == device.addr());
+        (!connected).then_some(device)
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        self.notice = None;
+        let confirmed = self.confirm_connect.take();
+
+        if self.remap.is_some() {
+            self.handle_remap_action(action);
+            return;
+        }
+
+        if let Some(scroll) = self.logs {
+            self.handle_logs_action(scroll, action);
+            return;
+        }
+        if action == Action::ShowLogs {
+            self.logs = Some(0);
+            return;
+        }
+
+        // B or the about key go back, other keys do nothing there
+        if self.about {
+            if matches!(action, Action::Quit | Action::About) {
+                self.about = false;
+            }
+            return;
+        }
+
+        if let Some(entry) = self.pin_entry.take() {
+            self.handle_pin_action(entry, action);
+            return;
+        }
+
+        // any key but quit leaves the auto connect screen for the list
+        if self.auto_connect.is_some() && action != Action::Quit {
+            self.auto_connect = None;
+            return;
+        }
+
+        if let Some(audio_test) = self.audio_test.take() {
+            self.handle_audio_test_action(audio_test, action);
+            return;
+        }
+        if let Some((addr, row)) = self.settings {
+            self.handle_settings_action(addr, row, action);
+            return;
+        }
+        if let Some(index) = self.hidden_list {
+            self.handle_hidden_list_action(index, action);
+            return;
+        }
+        if let Some(index) = self.profiles {
+            self.handle_profiles_action(index, action);
+            return;
+        }
+        if let Some(index) = self.network {
+            self.handle_network_action(index, action);
+            return;
+        }
+
+        match action {
+            Action::Quit => {
+                self.is_running = false;
+            }
+
+            Action::PowerOn => {
+                if self.state.powered {
+                    return;
+                }
+                self.send(Command::PowerOn);
+            }
+
+            Action::PowerOff => {
+                if !self.state.powered {
+                    return;
+                }
+                self.send(Command::PowerOff);
+            }
+
+            Action::Move(direction) => {
+                if self.state.scan_status != BluetoothScanStatus::Finished {
+                    return;
+                }
+                let len = self.list_rows(&self.state.devices);
+                if len == 0 {
+                    return;
+                }
+                match self.rotation.map_direction(direction) {
+                    Direction::Up => {
+                        if self.selected_bluetooth_device_index == 0 {
+                            self.selected_bluetooth_device_index = len - 1;
+                        } else {
+                            self.selected_bluetooth_device_index -= 1;
+                        }
+                    }
+                    Direction::Down => {
+                        if self.selected_bluetooth_device_index == len - 1 {
+                            self.selected_bluetooth_device_index = 0;
+                        } else {
+                            self.selected_bluetooth_device_index += 1;
+                        }
+                    }
+                    Direction::Right => {
+                        if let Some(device) = self.selected_device() {
+                            let addr = device.addr;
+                            self.settings = Some((addr, 0));
+                            self.send(Command::ReadDetails(addr));
+                        }
+                    }
+                    Direction::Left => self.hidden_list = Some(0),
+                }
+            }
+
+            Action::Rescan => self.rescan(),
+
+            Action::Connect => {
+                if self.state.connect_state.is_in_progress() {
+                    self.notify_busy();
+                    return;
+                }
+                if self.selected_bluetooth_device_index == self.listed
+                    && self.listed < self.state.devices.len()
+                    && self.state.scan_status == BluetoothScanStatus::Finished
+                {
+                    // the last row lists the other devices
+                    info!("list every device");
+                    self.list_expanded = true;
+                    self.listed = self.state.devices.len();
+                    return;
+                }
+                let Some(device) = self.selected_device() else {
+                    return;
+                };
+                let addr = device.addr;
+                // 防止误连接手机等非音频设备
+                if device.is_audio() == Some(false) && confirmed != Some(addr) {
+                    info!(%addr, "not an audio device, ask to confirm");
+                    let texts = texts::texts(self.config_rx.borrow().language);
+                    self.notice = Some(texts.not_audio_confirm.to_string());
+                    self.confirm_connect = Some(addr);
+                    return;
+                }
+                self.send(Command::Connect(addr));
+            }
+
+            Action::Disconnect => {
+                if self.state.connect_state.is_in_progress() {
+                    self.send(Command::CancelConnect);
+                    return;
+                }
+                if self.state.scan_status != BluetoothScanStatus::Finished {
+                    return;
+                }
+                self.send(Command::Disconnect);
+            }
+
+            Action::ReconnectLast => {
+                if self.state.connect_state.is_in_progress() {
+                    self.notify_busy();
+                    return;
+                }
+                if let Some(addr) = self.reconnect_target().and_then(SavedDevice::addr) {
+                    self.send(Command::Connect(addr));
+                }
+            }
+
+            Action::ToggleFavorite => self.toggle_favorite(),
+
+            Action::ConnectFavorite(index) => {
+                if self.state.connect_state.is_in_progress() {
+                    self.notify_busy();
+                    return;
+                }
+                if let Some(addr) = self.data.favorites.get(index).and_then(SavedDevice::addr) {
+                    self.send(Command::Connect(addr));
+                }
+            }
+
+            Action::Remap => self.start_remap(),
+
+            Action::Profiles => self.profiles = Some(0),
+
+            Action::Network => self.network = Some(0),
+
+            Action::About => self.about = true,
+
+            Action::ToggleTheme => {
+                let theme = self.config_rx.borrow().theme.toggled();
+                info!(?theme, "toggle theme");
+                if let Err(err) = Config::edit(&self.config_path, |config| config.theme = theme) {
+                    warn!(?err, "save theme failed");
+                }
+            }
+            Action::ToggleLanguage => {
+                let language = self.config_rx.borrow().language.toggled();
+                info!(?language, "toggle language");
+                if let Err(err) =
+                    Config::edit(&self.config_path, |config| config.language = language)
+                {
+                    warn!(?err, "save language failed");
+                }
+            }
+            Action::CycleSort => self.cycle_sort(),
+
+            Action::VolumeUp => self.send(Command::ChangeVolume(VOLUME_STEP as i8)),
+            Action::VolumeDown => self.send(Command::ChangeVolume(-(VOLUME_STEP as i8))),
+
+            // handled above, from any screen
+            Action::ShowLogs => {}
+
+            // only sent while remapping
+            Action::Bind(_) => {}
+
+            // handled by `run`, from any screen
+            Action::Background(_) => {}
+
+            Action::Forget => {
+                if let Some(device) = self.selected_device() {
+                    self.send(Command::Forget(device.addr));
+                }
+            }
+        }
+    }
+
+    /// Each press is taken for the next action, the keymap is saved once every
+    /// action has one.
+    fn handle_remap_action(&mut self, action: Action) {
+        let Some(remap) = &mut self.remap else {
+            return;
+        };
+
+        let binding = match action {
+            Action::Bind(binding) if binding != Binding::CANCEL => binding,
+            Action::Quit | Action::Bind(_) => {
+                info!("remapping cancelled");
+                self.remap = None;
+                return;
+            }
+            _ => return,
+        };
+
+        if remap.taken.contains(&binding) {
+            remap.rejected = true;
+            return;
+        }
+        remap
+            .keymap
+            .rebind(Keymap::REMAP_ACTIONS[remap.step], binding);
+        remap.taken.push(binding);
+        remap.step += 1;
+        remap.rejected = false;
+
+        if remap.step == Keymap::REMAP_ACTIONS.len() {
+            let keymap = remap.keymap.clone();
+            self.remap = None;
+            // the reloader hands the new keymap to the SDL thread
+            match Config::edit(&self.config_path, |config| config.keymap = keymap) {
+                Ok(()) => info!("save keymap"),
+                Err(err) => warn!(?err, "save keymap failed"),
+            }
+        }
+    }
+
+    /// B goes back to the list, the D-pad picks a row and changes its value.
+    fn handle_settings_action(&mut self, addr: Address, row: usize, action: Action) {
+        let rows = SettingsRow::ALL.len();
+        match action {
+            Action::Quit => self.settings = None,
+            Action::Move(direction) => match self.rotation.map_direction(direction) {
+                Direction::Up => self.settings = Some((addr, (row + rows - 1) % rows)),
+                Direction::Down => self.settings = Some((addr, (row + 1) % rows)),
+                Direction::Left => self.change_setting(addr, SettingsRow::ALL[row], false),
+                Direction::Right => self.change_setting(addr, SettingsRow::ALL[row], true),
+            },
+            Action::Connect => self.change_setting(addr, SettingsRow::ALL[row], true),
+            _ => {}
+        }
+    }
+
+    /// B goes back to the settings, which stops the tone, A plays the selected
+    /// tone or stops it.
+    fn handle_audio_test_action(&mut self, mut audio_test: AudioTest, action: Action) {
+        let len = TestTone::ALL.len();
+        let index = audio_test.selected_index;
+        match action {
+            Action::Quit => return,
+            Action::Move(direction) => match self.rotation.map_direction(direction) {
+                Direction::Up => audio_test.selected_index = (index + len - 1) % len,
+                Direction::Down => audio_test.selected_index = (index + 1) % len,
+                Direction::Left | Direction::Right => {}
+            },
+            Action::Connect => {
+                let tone = TestTone::ALL[index];
+                audio_test.playing = (audio_test.playing != Some(tone)).then_some(tone);
+            }
+            _ => {}
+        }
+        self.audio_test = Some(audio_test);
+    }
+
+    /// Up and down scroll a line, left and right a page, B or the log key go
+    /// back.
+    fn handle_logs_action(&mut self, scroll: usize, action: Action) {
+        let max_scroll = self.recent_logs.lines().len().saturating_sub(1);
+        let scroll = match action {
+            Action::Quit | Action::ShowLogs => {
+                self.logs = None;
+                return;
+            }
+            Action::Move(direction) => match self.rotation.map_direction(direction) {
+                Direction::Up => scroll + 1,
+                Direction::Down => scroll.saturating_sub(1),
+                Direction::Left => scroll + LOGS_PAGE,
+                Direction::Right => scroll.saturating_sub(LOGS_PAGE),
+            },
+            _ => return,
+        };
+        self.logs = Some(scroll.min(max_scroll));
+    }
+
+    /// B goes back to the list, A on the first row toggles the unnamed
+    /// devices and on the others unhides the device.
+    fn handle_hidden_list_action(&mut self, index: usize, action: Action) {
+        let len = self.hidden_devices.len() + 1;
+        match action {
+            Action::Quit => self.hidden_list = None,
+            Action::Move(direction) => match self.rotation.map_direction(direction) {
+                Direction::Up => self.hidden_list = Some((index + len - 1) % len),
+                Direction::Down => self.hidden_list = Some((index + 1) % len),
+                Direction::Left | Direction::Right => {}
+            },
+            Action::Connect if index == 0 => self.toggle_unnamed_devices(),
+            Action::Connect if index < len => {
+                let address = self.hidden_devices.remove(index - 1);
+                self.hidden_list = Some(index.min(self.hidden_devices.len()));
+                self.save_hidden_devices();
+                info!(address, "unhide device");
+            }
+            _ => {}
+        }
+    }
+
+    /// Show or hide the devices without a name, the list is scanned again to
+    /// apply it.
+    fn toggle_unnamed_devices(&mut self) {
+        self.hide_unnamed_devices = !self.hide_unnamed_devices;
+        let hide = self.hide_unnamed_devices;
+        info!(hide, "toggle unnamed devices");
+        if let Err(err) = Config::edit(&self.config_path, |config| {
+            config.hide_unnamed_devices = hide;
+        }) {
+            warn!(?err, "save unnamed devices toggle failed");
+        }
+
+        // the scan can't wait for the reloader to hand over the options
+        let mut config = self.config_rx.borrow().clone();
+        config.hide_unnamed_devices = hide;
+        self.send(Command::SetOptions(config.manager_options(&self.data)));
+        if self.state.powered {
+            self.send(Command::Scan);
+        }
+    }
+
+    /// Left and right move over the keys, A types the key under the cursor and
+    /// B cancels. The number keys of a keyboard type their digit.
+    fn handle_pin_action(&mut self, mut entry: PinEntry, action: Action) {
+        let key_count = PinEntryScreen::KEY_COUNT;
+        let key = match action {
+            Action::Quit => return,
+            Action::Move(direction) => {
+                match self.rotation.map_direction(direction) {
+                    Direction::Left => entry.cursor = (entry.cursor + key_count - 1) % key_count,
+                    Direction::Right => entry.cursor = (entry.cursor + 1) % key_count,
+                    Direction::Up | Direction::Down => {}
+                }
+                None
+            }
+            Action::Connect => Some(entry.cursor),
+            // the hotkeys of the favorites are the keys 1 to 9
+            Action::ConnectFavorite(index) => Some(index),
+            _ => None,
+        };
+
+        match key {
+            Some(PinEntryScreen::DELETE) => {
+                entry.digits.pop();
+            }
+            Some(PinEntryScreen::DONE) => {
+                if !entry.digits.is_empty() {
+                    self.submit_pin(entry);
+                    return;
+                }
+            }
+            Some(digit) if entry.digits.len() < MAX_PIN_LEN => {
+                entry.digits.push(PinEntryScreen::DIGITS[digit]);
+            }
+            _ => {}
+        }
+        self.pin_entry = Some(entry);
+    }
+
+    /// Remember the PIN as the one of the device and pair again with it.
+    fn submit_pin(&mut self, entry: PinEntry) {
+        info!(addr = %entry.addr, "pair with the entered pin");
+        if self.data.set_pin(entry.addr, entry.digits) {
+            if let Err(err) = self.data.save(&self.data_path) {
+                warn!(?err, "save pin failed");
+            }
+        }
+        let options = self.config_rx.borrow().manager_options(&self.data);
+        self.send(Command::SetOptions(options));
+        self.send(Command::Connect(entry.addr));
+    }
+
+    /// B goes back to the list, A applies the selected profile.
+    fn handle_profiles_action(&mut self, index: usize, action: Action) {
+        let profiles = self.config_rx.borrow().profiles.clone();
+        let len = profiles.len();
+        match action {
+            Action::Quit | Action::Profiles => self.profiles = None,
+            Action::Move(direction) if len > 0 => match self.rotation.map_direction(direction) {
+                Direction::Up => self.profiles = Some((index + len - 1) % len),
+                Direction::Down => self.profiles = Some((index + 1) % len),
+                Direction::Left | Direction::Right => {}
+            },
+            Action::Connect if index < len => {
+                if self.state.connect_state.is_in_progress() {
+                    self.notify_busy();
+                    return;
+                }
+                self.profiles = None;
+                self.apply_profile(&profiles[index]);
+            }
+            _ => {}
+        }
+    }
+
+    /// B goes back to the list, A tethers the selected phone, or stops when
+    /// it's tethered already.
+    fn handle_network_action(&mut self, index: usize, action: Action) {
+        let phones = self.tethering_phones();
+        let len = phones.len();
+        match action {
+            Action::Quit | Action::Network => self.network = None,
+            Action::Move(direction) if len > 0 => match self.rotation.map_direction(direction) {
+                Direction::Up => self.network = Some((index + len - 1) % len),
+                Direction::Down => self.network = Some((index + 1) % len),
+                Direction::Left | Direction::Right => {}
+            },
+            Action::Connect if index < len => {
+                let addr = phones[index].0;
+                match &self.state.tether {
+                    TetherState::Connecting(_) => self.notify_busy(),
+                    TetherState::Connected { addr: tethered, .. } if *tethered == addr => {
+                        self.send(Command::Untether)
+                    }
+                    _ => self.send(Command::Tether(addr)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The paired phones offering tethering, with their names.
+    fn tethering_phones(&self) -> Vec<(Address, String)> {
+        self.state
+            .devices
+            .iter()
+            .filter(|info| info.paired && info.offers_tethering())
+            .map(|info| (info.addr, self.device_name(info.addr)))
+            .collect()
+    }
+
+    /// Save the settings of the profile as the settings of its device, then
+    /// connect it with them.
+    fn apply_profile(&mut self, profile: &ConnectionProfile) {
+        let Some(addr) = profile.addr() else {
+            warn!(
+                profile = profile.name,
+                address = profile.address,
+                "invalid profile address"
+            );
+            return;
+        };
+        info!(profile = profile.name, %addr, "apply connection profile");
+
+        let mut settings = self.data.settings(addr);
+        settings.profile = profile.profile;
+        settings.volume = profile.volume;
+        self.data.set_settings(addr, settings);
+        self.data.active_profile = Some(profile.name.clone());
+        if let Err(err) = self.data.save(&self.data_path) {
+            warn!(?err, "save connection profile failed");
+        }
+
+        // the manager handles the commands in order, the connect gets the
+        // new settings
+        let options = self.config_rx.borrow().manager_options(&self.data);
+        self.send(Command::SetOptions(options));
+        self.send(Command::Connect(addr));
+    }
+
+    /// Hide the device from the next scans on, and from the list right away.
+    fn hide_device(&mut self, addr: Address) {
+        self.settings = None;
+        if !self.hidden_devices.contains(&addr.to_string()) {
+            self.hidden_devices.push(addr.to_string());
+            self.save_hidden_devices();
+            info!(%addr, "hide device");
+        }
+        let mut devices = std::mem::take(&mut self.state.devices);
+        devices.retain(|info| info.connected || info.addr != addr);
+        self.listed = self.arrange_devices(&mut devices);
+        if self.selected_bluetooth_device_index >= self.list_rows(&devices) {
+            self.selected_bluetooth_device_index = 0;
+        }
+        self.state.devices = devices;
+    }
+
+    fn save_hidden_devices(&self) {
+        let hidden_devices = self.hidden_devices.clone();
+        if let Err(err) = Config::edit(&self.config_path, |config| {
+            config.hidden_devices = hidden_devices;
+        }) {
+            warn!(?err, "save hidden devices failed");
+        }
+    }
+
+    /// Step the value of a row, save it and hand it to the manager for the
+    /// next connect.
+    fn change_setting(&mut self, addr: Address, row: SettingsRow, forward: bool) {
+        let mut settings = self.data.settings(addr);
+        match row {
+            SettingsRow::Test => {
+                if forward {
+                    self.audio_test = Some(AudioTest {
+                        addr,
+                        selected_index: 0,
+                        playing: None,
+                    });
+                }
+                return;
+            }
+            SettingsRow::Hide => {
+                if forward {
+                    self.hide_device(addr);
+                }
+                return;
+            }
+            SettingsRow::AutoConnect => settings.auto_connect = !settings.auto_connect,
+            SettingsRow::Trust => settings.trust = !settings.trust,
+            SettingsRow::Profile => {
+                settings.profile = match settings.profile {
+                    AudioProfile::A2dp => AudioProfile::Hfp,
+                    AudioProfile::Hfp => AudioProfile::A2dp,
+                }
+            }
+            SettingsRow::Volume => {
+                // 不调整 → 10% → … → 100% → 不调整
+                settings.volume = match (settings.volume, forward) {
+                    (None, true) => Some(VOLUME_STEP),
+                    (None, false) => Some(100),
+                    (Some(volume), true) if volume < 100 => Some((volume + VOLUME_STEP).min(100)),
+                    (Some(volume), false) if volume > VOLUME_STEP => Some(volume - VOLUME_STEP),
+                    (Some(_), _) => None,
+                }
+            }
+        }
+
+        self.data.set_settings(addr, settings);
+        if let Err(err) = self.data.save(&self.data_path) {
+            warn!(?err, "save device settings failed");
+        }
+        let options = self.config_rx.borrow().manager_options(&self.data);
+        self.send(Command::SetOptions(options));
+    }
+
+    fn rescan(&mut self) {
+        if !self.state.powered || self.state.scan_status == BluetoothScanStatus::Scanning {
+            return;
+        }
+        self.send(Command::Scan);
+    }
+
+    fn handle_signal_action(&mut self, action: SignalAction) {
+        match action {
+            // straight to the manager, as a key would go to the open screen
+            SignalAction::Rescan => self.rescan(),
+            SignalAction::Reconnect => {
+                if self.state.connect_state.is_in_progress() {
+                    return;
+                }
+                let last_device = self.data.last_device.as_ref().and_then(SavedDevice::addr);
+                if let Some(addr) = self
+                    .state
+                    .last_connected
+                    .or(last_device)
+                    .or(self.preferred_device)
+                {
+                    self.send(Command::EnsureConnected(addr));
+                }
+            }
+        }
+    }
+
+    /// The name of a device, which may not be in the list yet.
+    fn device_name(&self, addr: Address) -> String {
+        if let Some(nickname) = self.data.nickname(addr) {
+            return nickname.to_string();
+        }
+
+        let saved = self
+            .data
+            .last_device
+            .iter()
+            .chain(&self.data.favorites)
+            .find(|device| device.addr() == Some(addr));
+        match (
+            self.state.devices.iter().find(|info| info.addr == addr),
+            saved,
+        ) {
+            (Some(info), _) if !info.name.is_empty() => info.name.clone(),
+            (_, Some(device)) => device.display_name().to_string(),
+            _ => addr.to_string(),
+        }
+    }
+
+    /// The device under the cursor, once the scan has finished.
+    fn selected_device(&self) -> Option<&DeviceInfo> {
+        if self.state.scan_status != BluetoothScanStatus::Finished
+            || self.selected_bluetooth_device_index >= self.listed
+        {
+            return None;
+        }
+        self.state.devices.get(self.selected_bluetooth_device_index)
+    }
+
+    fn screen(&self) -> Screen {
+        if let Some(remap) = &self.remap {
+            return Screen::Remap(RemapScreen {
+                step: remap.step,
+                rejected: remap.rejected,
+            });
+        }
+
+        if let Some(scroll) = self.logs {
+            return Screen::Logs(LogsScreen {
+                lines: self.recent_logs.lines(),
+                scroll,
+            });
+        }
+
+        if self.about {
+            return Screen::About(AboutScreen {
+                app_version: env!("CARGO_PKG_VERSION"),
+                bluez_version: self.state.bluez_version,
+                kernel_version: self.kernel_version.clone(),
+            });
+        }
+
+        if let Some(entry) = &self.pin_entry {
+            return Screen::PinEntry(PinEntryScreen {
+                name: self.device_name(entry.addr),
+                digits: entry.digits.clone(),
+                cursor: entry.cursor,
+            });
+        }
+
+        if let Some(audio_test) = &self.audio_test {
+            return Screen::AudioTest(AudioTestScreen {
+                name: self.device_name(audio_test.addr),
+                connected: self
+                    .state
+                    .connected_device()
+                    .is_some_and(|info| info.addr == audio_test.addr),
+                playing: audio_test.playing,
+                channel: None,
+                selected_index: audio_test.selected_index,
+            });
+        }
+
+        if let Some((addr, row)) = self.settings {
+            return Screen::DeviceSettings(DeviceSettingsScreen {
+                name: self.device_name(addr),
+                address: addr,
+                settings: self.data.settings(addr),
+                details: self
+                    .state
+                    .details
+                    .clone()
+                    .filter(|details| details.addr == addr),
+                link_history: match self.state.connected_device() {
+                    Some(info) if info.addr == addr => self.state.link_history.clone(),
+                    _ => Vec::new(),
+                },
+                selected_row: row,
+            });
+        }
+
+        if let Some(index) = self.hidden_list {
+            return Screen::HiddenDevices(HiddenDevicesScreen {
+                unnamed_hidden: self.hide_unnamed_devices,
+                devices: self
+                    .hidden_devices
+                    .iter()
+                    .map(|address| match address.parse() {
+                        Ok(addr) => match self.device_name(addr) {
+                            name if name == *address => name,
+                            name => format!("{} ({})", name, address),
+                        },
+                        Err(_) => address.clone(),
+                    })
+                    .collect(),
+                selected_index: index,
+            });
+        }
+
+        if let Some(index) = self.profiles {
+            return Screen::Profiles(ProfilesScreen {
+                profiles: self
+                    .config_rx
+                    .borrow()
+                    .profiles
+                    .iter()
+                    .map(|profile| match profile.addr() {
+                        Some(addr) => format!("{} ({})", profile.name, self.device_name(addr)),
+                        None => profile.name.clone(),
+                    })
+                    .collect(),
+                selected_index: index,
+            });
+        }
+
+        if let Some(index) = self.network {
+            return Screen::Network(NetworkScreen {
+                phones: self.tethering_phones(),
+                tether: self.state.tether.clone(),
+                selected_index: index,
+            });
+        }
+
+        if let Some(addr) = self.auto_connect {
+            return Screen::AutoConnect(AutoConnectScreen {
+                name: self.device_name(addr),
+                connect_state: self.state.connect_state.clone(),
+            });
+        }
+
+        let mut state = self.state.clone();
+        state.devices.truncate(self.listed);
+        for info in &mut state.devices {
+            if let Some(nickname) = self.data.nickname(info.addr) {
+                info.name = nickname.to_string();
+            }
+        }
+
+        Screen::Main(MainScreen {
+            state,
+            selected_device_index: self.selected_bluetooth_device_index,
+            more_devices: self.state.devices.len() - self.listed,
+            no_controller: false,
+            handheld_battery: self.handheld_battery,
+            notice: self.notice.clone(),
+            reconnect_last: self
+                .reconnect_target()
+                .and_then(SavedDevice::addr)
+                .map(|addr| self.device_name(addr)),
+            favorites: self
+                .data
+                .favorites
+                .iter()
+                .filter_map(SavedDevice::addr)
+                .collect(),
+            show_addresses: self.config_rx.borrow().show_addresses,
+            scan_seconds_left: self.scan_started.map(|started| {
+                let left = self
+                    .config_rx
+                    .borrow()
+                    .scan_duration()
+                    .saturating_sub(started.elapsed());
+                // rounded up, 4.2s left shows as 5s
+                left.as_secs() + u64::from(left.subsec_nanos() > 0)
+            }),
+        })
+    }
+}
+
+fn countdown_interval() -> Interval {
+    let mut countdown = interval(COUNTDOWN_INTERVAL);
+    countdown.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    countdown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bluetooth_audio_connector_tg5040::{BluetoothManager, ManagerOptions, MockBackend};
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn rescan_signal_on_a_modal_screen() {
+        let addr = Address::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x01]);
+        let backend = Arc::new(MockBackend::new(vec![DeviceInfo {
+            addr,
+            name: "Headset".to_string(),
+            paired: true,
+            ..DeviceInfo::default()
+        }]));
+        let options = ManagerOptions {
+            scan_at_start: false,
+            ..ManagerOptions::default()
+        };
+        let manager = BluetoothManager::spawn(backend, options);
+        let mut state_rx = manager.state_rx.clone();
+        let (_action_tx, action_rx) = mpsc::unbounded_channel();
+        let (screen_tx, _screen_rx) = watch::channel(Screen::default());
+        let (_config_tx, config_rx) = watch::channel(Config::default());
+        let (_battery_tx, battery_rx) = watch::channel(None);
+        let mut app = App::new(
+            manager,
+            action_rx,
+            screen_tx,
+            config_rx,
+            QuitSignals::new().unwrap(),
+            ActionSignals::new().unwrap(),
+            PathBuf::from("config.toml"),
+            battery_rx,
+        );
+        app.state = state_rx
+            .wait_for(|state| state.powered)
+            .await
+            .unwrap()
+            .clone();
+        // the auto connect screen under the about screen
+        app.auto_connect = Some(addr);
+        app.about = true;
+
+        // SAFETY: a plain syscall, the handler of tokio takes the signal
+        assert_eq!(unsafe { libc::raise(libc::SIGUSR1) }, 0);
+        let action = app.action_signals.recv().await;
+        app.handle_signal_action(action);
+
+        state_rx
+            .wait_for(|state| state.scan_status == BluetoothScanStatus::Scanning)
+            .await
+            .unwrap();
+        assert!(app.about);
+        assert_eq!(app.auto_connect, Some(addr));
+    }
+}
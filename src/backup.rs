@@ -0,0 +1,74 @@
+use crate::{
+    config::Config,
+    data::{AppData, SavedDevice},
+    files,
+};
+use anyhow::Context;
+use bluetooth_audio_connector_tg5040::DeviceSettings;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::Path};
+use tracing::info;
+
+/// The device metadata of the app in one JSON file, for restoring it after
+/// reflashing the SD card.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backup {
+    pub preferred_device: Option<String>,
+    pub last_device: Option<SavedDevice>,
+    pub favorites: Vec<SavedDevice>,
+    pub nicknames: BTreeMap<String, String>,
+    pub device_settings: BTreeMap<String, DeviceSettings>,
+}
+
+pub fn export(path: &Path, config: &Config) -> anyhow::Result<()> {
+    let data = AppData::load(&AppData::path());
+    let backup = Backup {
+        preferred_device: config.preferred_device.clone(),
+        last_device: data.last_device,
+        favorites: data.favorites,
+        nicknames: data.nicknames,
+        device_settings: data.device_settings,
+    };
+
+    files::write_atomic(path, serde_json::to_string_pretty(&backup)?)
+        .with_context(|| format!("write {}", path.display()))?;
+    info!(?path, "export backup");
+    Ok(())
+}
+
+/// Merge a backup into the config and data files, what the backup holds wins
+/// over what is set already.
+pub fn import(path: &Path, config_path: &Path) -> anyhow::Result<()> {
+    let content = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    let backup: Backup =
+        serde_json::from_str(&content).with_context(|| format!("parse {}", path.display()))?;
+
+    if backup.preferred_device.is_some() {
+        // the file itself, without the overrides of the command line
+        let mut config = Config::load_or_create(config_path)?;
+        config.preferred_device = backup.preferred_device;
+        config.save(config_path)?;
+    }
+
+    let data_path = AppData::path();
+    let mut data = AppData::load(&data_path);
+    if backup.last_device.is_some() {
+        data.last_device = backup.last_device;
+    }
+    for device in backup.favorites {
+        if !data
+            .favorites
+            .iter()
+            .any(|favorite| favorite.address == device.address)
+        {
+            data.favorites.push(device);
+        }
+    }
+    data.nicknames.extend(backup.nicknames);
+    data.device_settings.extend(backup.device_settings);
+    data.save(&data_path)?;
+
+    info!(?path, "import backup");
+    Ok(())
+}
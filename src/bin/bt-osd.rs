@@ -0,0 +1,142 @@
+//! Shows a small banner over the running game for a few seconds when the
+//! connector, usually run as `--daemon`, connects or loses a device. Follows
+//! the D-Bus service of the connector, so it's started next to it.
+
+use anyhow::Context;
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{dbus::ConnectorProxy, BluetoothBackend, BluezBackend};
+use sdl2::{pixels::Color, rect::Rect, rwops::RWops, sys::SDL_WindowFlags};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+use zbus::Connection;
+
+// the connector already has the Chinese font, the banner only needs a few
+// English words
+const FONT: &[u8] = include_bytes!("../../assets/DejaVuSansMono.ttf");
+
+const FONT_SIZE: u16 = 20;
+
+const BANNER_WIDTH: u32 = 480;
+const BANNER_HEIGHT: u32 = 40;
+const BANNER_TIME: Duration = Duration::from_secs(3);
+
+const FRAME_TIME: Duration = Duration::from_millis(50);
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let connection = Connection::system().await?;
+    let proxy = ConnectorProxy::new(&connection).await?;
+    // only for the names, the connector owns the connection
+    let backend = match BluezBackend::new().await {
+        Ok(backend) => Some(backend),
+        Err(err) => {
+            warn!(?err, "connect bluetoothd failed, show addresses");
+            None
+        }
+    };
+
+    let mut connected = device(backend.as_ref(), &proxy.connected_device().await?).await;
+    let mut changes = proxy.receive_connected_device_changed().await;
+    info!("follow the connected device");
+
+    while let Some(change) = changes.next().await {
+        let current = device(backend.as_ref(), &change.get().await?).await;
+        if current.as_ref().map(|(addr, _)| addr) == connected.as_ref().map(|(addr, _)| addr) {
+            continue;
+        }
+        let text = match (&current, &connected) {
+            (Some((_, name)), _) => format!("Connected: {name}"),
+            (None, Some((_, name))) => format!("Disconnected: {name}"),
+            (None, None) => continue,
+        };
+        connected = current;
+
+        info!(text, "show banner");
+        tokio::task::spawn_blocking(move || show_banner(&text))
+            .await?
+            .unwrap_or_else(|err| warn!(?err, "show banner failed"));
+    }
+    Ok(())
+}
+
+/// The address and the name of the device, the address when it has no name.
+async fn device(backend: Option<&BluezBackend>, address: &str) -> Option<(Address, String)> {
+    let addr: Address = address.parse().ok()?;
+    let name = match backend {
+        Some(backend) => match backend.properties(addr).await {
+            Ok(info) if !info.name.is_empty() => info.name,
+            Ok(_) => addr.to_string(),
+            Err(err) => {
+                debug!(?err, %addr, "get device name failed");
+                addr.to_string()
+            }
+        },
+        None => addr.to_string(),
+    };
+    Some((addr, name))
+}
+
+/// Draw the text in a borderless window at the top of the screen, kept above
+/// the game, and close it after `BANNER_TIME`.
+fn show_banner(text: &str) -> anyhow::Result<()> {
+    let sdl_context = sdl2::init().map_err(anyhow::Error::msg)?;
+    let video_subsystem = sdl_context.video().map_err(anyhow::Error::msg)?;
+    let ttf_context = sdl2::ttf::init()?;
+    let font = ttf_context
+        .load_font_from_rwops(
+            RWops::from_bytes(FONT).map_err(anyhow::Error::msg)?,
+            FONT_SIZE,
+        )
+        .map_err(anyhow::Error::msg)?;
+
+    let screen_width = video_subsystem
+        .current_display_mode(0)
+        .map(|mode| mode.w)
+        .unwrap_or(BANNER_WIDTH as i32);
+    let mut builder = video_subsystem.window("bt-osd", BANNER_WIDTH, BANNER_HEIGHT);
+    builder
+        .borderless()
+        .position((screen_width - BANNER_WIDTH as i32) / 2, 0);
+    let flags = builder.window_flags() | SDL_WindowFlags::SDL_WINDOW_ALWAYS_ON_TOP as u32;
+    let window = builder.set_window_flags(flags).build()?;
+    let mut canvas = window.into_canvas().build()?;
+
+    let surface = font
+        .render(text)
+        .blended(Color::RGB(255, 255, 255))
+        .context("render banner text")?;
+    let texture_creator = canvas.texture_creator();
+    let texture = texture_creator.create_texture_from_surface(&surface)?;
+    let query = texture.query();
+    let width = query.width.min(BANNER_WIDTH);
+    let target = Rect::new(
+        ((BANNER_WIDTH - width) / 2) as i32,
+        (BANNER_HEIGHT.saturating_sub(query.height) / 2) as i32,
+        width,
+        query.height,
+    );
+
+    canvas.set_draw_color(Color::RGB(32, 32, 32));
+    canvas.clear();
+    canvas
+        .copy(&texture, None, target)
+        .map_err(anyhow::Error::msg)?;
+    canvas.present();
+
+    // the window manager only maps a window whose events are pumped
+    let mut event_pump = sdl_context.event_pump().map_err(anyhow::Error::msg)?;
+    let deadline = Instant::now() + BANNER_TIME;
+    while Instant::now() < deadline {
+        for event in event_pump.poll_iter() {
+            debug!(?event, "banner event");
+        }
+        thread::sleep(FRAME_TIME);
+    }
+    Ok(())
+}
@@ -0,0 +1,1593 @@
+use crate::{
+    bluetooth::{
+        backend::{mgmt, BluetoothBackend},
+        battery, AudioProfile, BluetoothEvent, BluezVersion, DeviceDetails, DeviceInfo, LinkStats,
+        NowPlaying, PairingAdvertisement, GOOD_RSSI,
+    },
+    error::{Error, Result},
+};
+use async_trait::async_trait;
+use bluer::{
+    agent::{Agent, AgentHandle, ReqError, ReqResult},
+    gatt::remote::Characteristic,
+    Adapter, AdapterEvent, Address, AddressType, Device, DeviceEvent, DeviceProperty, Session,
+    SessionEvent, Uuid,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CString, OsString},
+    io::{self, Write},
+    mem,
+    net::Ipv4Addr,
+    os::unix::{ffi::OsStringExt, fs::OpenOptionsExt},
+    path::{Path, PathBuf},
+    pin::pin,
+    process::{Output, Stdio},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    process,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc, watch,
+    },
+    time::timeout,
+};
+use tokio_stream::StreamExt;
+use tracing::{debug, error, info, warn};
+use zbus::{
+    dbus_proxy,
+    fdo::{InterfacesAddedStream, InterfacesRemovedStream, ObjectManagerProxy, PropertiesProxy},
+    names::InterfaceName,
+    zvariant::{OwnedValue, Value},
+    MatchRule, MessageStream, MessageType,
+};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const MEDIA_TRANSPORT: &str = "org.bluez.MediaTransport1";
+const MEDIA_PLAYER: &str = "org.bluez.MediaPlayer1";
+
+// the device ID of the adapters, unless main.conf sets another one
+const LINUX_FOUNDATION_VENDOR: u32 = 0x1d6b;
+const BLUEZ_PRODUCT: u32 = 0x0246;
+
+// the role connected of a tethering phone, its network access point
+const NAP_ROLE: &str = "nap";
+
+// the DHCP client of busybox, which the firmware has
+const DHCP_CLIENT: &str = "udhcpc";
+
+// how long the phone has to hand out an address
+const DHCP_TIMEOUT: Duration = Duration::from_secs(20);
+
+// udhcpc only tells the lease to its script, the default one isn't on every
+// firmware, so this one prints it for `lease_address` to apply
+const DHCP_SCRIPT: &str = r#"#!/bin/sh
+[ "$1" = bound ] || [ "$1" = renew ] || exit 0
+echo "lease ip=$ip"
+echo "lease mask=$mask"
+echo "lease subnet=$subnet"
+for router in $router; do echo "lease router=$router"; done
+for dns in $dns; do echo "lease dns=$dns"; done
+"#;
+// the script runs as root, so it's written in a new directory only root can
+// enter, mkdtemp(3) fills in the X
+const DHCP_SCRIPT_DIR: &str = "bt-audio-connector-XXXXXX";
+const DHCP_SCRIPT_NAME: &str = "udhcpc.sh";
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+// `ip` only talks to the kernel, it answers at once
+const IP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// the GATT Battery Service and its level characteristic
+const BATTERY_SERVICE: Uuid = Uuid::from_u128(0x0000180f_0000_1000_8000_00805f9b34fb);
+const BATTERY_LEVEL: Uuid = Uuid::from_u128(0x00002a19_0000_1000_8000_00805f9b34fb);
+
+// the Device Information Service and the texts read from it
+const DEVICE_INFORMATION: Uuid = Uuid::from_u128(0x0000180a_0000_1000_8000_00805f9b34fb);
+const MANUFACTURER_NAME: Uuid = Uuid::from_u128(0x00002a29_0000_1000_8000_00805f9b34fb);
+const MODEL_NUMBER: Uuid = Uuid::from_u128(0x00002a24_0000_1000_8000_00805f9b34fb);
+const FIRMWARE_REVISION: Uuid = Uuid::from_u128(0x00002a26_0000_1000_8000_00805f9b34fb);
+const HARDWARE_REVISION: Uuid = Uuid::from_u128(0x00002a27_0000_1000_8000_00805f9b34fb);
+
+// the transport volume goes from 0 to 127
+const MAX_TRANSPORT_VOLUME: u16 = 127;
+
+// how long the properties of a device added again are reused by the events
+const EVENT_FETCH_WINDOW: Duration = Duration::from_secs(10);
+
+/// The real backend, talking to bluetoothd over D-Bus.
+pub struct BluezBackend {
+    // keeps the D-Bus connection of the adapter alive
+    _session: Session,
+    // for the APIs bluer doesn't cover, shared by their calls
+    connection: zbus::Connection,
+    media: Arc<MediaObjects>,
+    // `None` while the dongle is unplugged
+    adapter: Arc<RwLock<Option<Adapter>>>,
+    adapter_rx: watch::Receiver<bool>,
+    devices: Arc<DeviceCache>,
+    gatt_batteries: Arc<GattBatteries>,
+    // unregisters the agent when dropped
+    _agent: Option<AgentHandle>,
+    pin_request: Arc<Mutex<PinRequest>>,
+    // what the networks of tethered phones changed, undone when they go down
+    networks: Mutex<HashMap<Address, NetworkSetup>>,
+}
+
+/// What the agent answers while `pair` runs.
+#[derive(Default)]
+struct PinRequest {
+    pin: Option<(Address, String)>,
+    // the device asked for the PIN
+    asked: bool,
+}
+
+impl PinRequest {
+    /// The PIN of the device being paired, any other device is rejected.
+    fn answer(request: &Mutex<PinRequest>, addr: Address) -> ReqResult<String> {
+        let mut request = request.lock().unwrap();
+        match &request.pin {
+            Some((pin_addr, pin)) if *pin_addr == addr => {
+                let pin = pin.clone();
+                request.asked = true;
+                Ok(pin)
+            }
+            _ => Err(ReqError::Rejected),
+        }
+    }
+}
+
+impl BluezBackend {
+    /// Connect to bluetoothd and use its default adapter.
+    pub async fn new() -> Result<Self> {
+        let session = Session::new()
+            .await
+            .map_err(|err| Error::BluezDown(err.message))?;
+        let adapter = session
+            .default_adapter()
+            .await
+            .map_err(|_| Error::AdapterMissing)?;
+        let connection = zbus::Connection::system()
+            .await
+            .map_err(|err| Error::BluezDown(err.to_string()))?;
+        let media = MediaObjects::follow(&connection)
+            .await
+            .map_err(|err| Error::BluezDown(err.to_string()))?;
+
+        // not the default agent, BlueZ only asks it about the pairings started
+        // here
+        let pin_request = Arc::new(Mutex::new(PinRequest::default()));
+        let agent = match session.register_agent(pin_agent(pin_request.clone())).await {
+            Ok(agent) => Some(agent),
+            Err(err) => {
+                warn!(?err, "register pin agent failed, legacy devices can't pair");
+                None
+            }
+        };
+
+        let adapter = Arc::new(RwLock::new(Some(adapter)));
+        let (adapter_tx, adapter_rx) = watch::channel(true);
+        let devices = Arc::new(DeviceCache::default());
+        match session.events().await {
+            Ok(session_events) => {
+                tokio::spawn(follow_adapters(
+                    session.clone(),
+                    session_events,
+                    adapter.clone(),
+                    adapter_tx,
+                    devices.clone(),
+                ));
+            }
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "watch adapters failed, an unplugged adapter isn't noticed"
+                );
+            }
+        }
+
+        Ok(Self {
+            _session: session,
+            connection,
+            media,
+            adapter,
+            adapter_rx,
+            devices,
+            gatt_batteries: Arc::default(),
+            _agent: agent,
+            pin_request,
+            networks: Mutex::default(),
+        })
+    }
+
+    fn adapter(&self) -> Result<Adapter> {
+        self.adapter
+            .read()
+            .unwrap()
+            .clone()
+            .ok_or(Error::AdapterMissing)
+    }
+
+    fn device(&self, addr: Address) -> Result<Device> {
+        self.devices.device(&self.adapter()?, addr)
+    }
+
+    /// The D-Bus object of the device, for the APIs bluer doesn't cover.
+    fn device_path(&self, addr: Address) -> Result<String> {
+        Ok(format!(
+            "/org/bluez/{}/dev_{}",
+            self.adapter()?.name(),
+            addr.to_string().replace(':', "_")
+        ))
+    }
+
+    /// The PAN client of the device.
+    async fn network(&self, addr: Address) -> Result<NetworkProxy<'static>> {
+        NetworkProxy::builder(&self.connection)
+            .path(self.device_path(addr)?)
+            .map_err(dbus_error)?
+            .build()
+            .await
+            .map_err(dbus_error)
+    }
+
+    /// The properties of the media transport of the device.
+    async fn transport(&self, addr: Address) -> Result<PropertiesProxy<'static>> {
+        let (path, _) = self
+            .media
+            .find(&self.device_path(addr)?, MEDIA_TRANSPORT)
+            .ok_or(Error::AudioRoutingFailed)?;
+        PropertiesProxy::builder(&self.connection)
+            .destination(BLUEZ_SERVICE)
+            .and_then(|builder| builder.path(path))
+            .map_err(dbus_error)?
+            .build()
+            .await
+            .map_err(dbus_error)
+    }
+}
+
+/// Drop the adapter when it's removed, and take the next one added while there
+/// is none.
+async fn follow_adapters(
+    session: Session, session_events: impl tokio_stream::Stream<Item = SessionEvent>,
+    adapter: Arc<RwLock<Option<Adapter>>>, adapter_tx: watch::Sender<bool>,
+    devices: Arc<DeviceCache>,
+) {
+    let mut session_events = pin!(session_events);
+    while let Some(session_event) = session_events.next().await {
+        match session_event {
+            SessionEvent::AdapterRemoved(name) => {
+                let mut adapter = adapter.write().unwrap();
+                if adapter
+                    .as_ref()
+                    .is_some_and(|current| current.name() == name)
+                {
+                    warn!(name, "bluetooth adapter removed");
+                    *adapter = None;
+                    devices.clear();
+                    adapter_tx.send_replace(false);
+                }
+            }
+            SessionEvent::AdapterAdded(name) => {
+                if adapter.read().unwrap().is_some() {
+                    continue;
+                }
+                match session.adapter(&name) {
+                    Ok(added) => {
+                        info!(name, "bluetooth adapter added");
+                        *adapter.write().unwrap() = Some(added);
+                        adapter_tx.send_replace(true);
+                    }
+                    Err(err) => {
+                        error!(?err, name, "use added adapter failed");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Answers the PIN and passkey requests of legacy devices with the PIN given to
+/// `pair`.
+fn pin_agent(request: Arc<Mutex<PinRequest>>) -> Agent {
+    let pin_code_request = request.clone();
+    Agent {
+        request_pin_code: Some(Box::new(move |req| {
+            let answer = PinRequest::answer(&pin_code_request, req.device);
+            Box::pin(async move { answer })
+        })),
+        request_passkey: Some(Box::new(move |req| {
+            let answer = PinRequest::answer(&request, req.device)
+                .and_then(|pin| pin.parse().map_err(|_| ReqError::Rejected));
+            Box::pin(async move { answer })
+        })),
+        ..Default::default()
+    }
+}
+
+#[async_trait]
+impl BluetoothBackend for BluezBackend {
+    async fn is_powered(&self) -> Result<bool> {
+        Ok(self.adapter()?.is_powered().await?)
+    }
+
+    async fn set_powered(&self, powered: bool) -> Result<()> {
+        self.adapter()?.set_powered(powered).await?;
+        Ok(())
+    }
+
+    async fn bluez_version(&self) -> Result<Option<BluezVersion>> {
+        let version = self
+            .adapter()?
+            .modalias()
+            .await?
+            .filter(|modalias| {
+                modalias.vendor == LINUX_FOUNDATION_VENDOR && modalias.product == BLUEZ_PRODUCT
+            })
+            .map(|modalias| BluezVersion::from_device_id(modalias.device));
+        Ok(version)
+    }
+
+    async fn scan(&self, duration: Duration) -> Result<Vec<DeviceInfo>> {
+        let device_events = self.adapter()?.discover_devices().await?;
+        let mut device_events = pin!(device_events);
+
+        let adapter = self.adapter()?;
+        let mut properties = PropertiesCache::new(duration);
+        let mut devices: Vec<DeviceInfo> = Vec::new();
+
+        let _ = timeout(duration, async {
+            while let Some(device_event) = device_events.next().await {
+                match device_event {
+                    AdapterEvent::DeviceAdded(addr) => {
+                        if devices.iter().any(|info| info.addr == addr) {
+                            continue;
+                        }
+                        match properties.get(&adapter, addr).await {
+                            Ok(info) => devices.push(info),
+                            Err(err) => {
+                                error!(?err, "get device properties failed");
+                            }
+                        }
+                    }
+                    AdapterEvent::DeviceRemoved(addr) => {
+                        devices.retain(|info| info.addr != addr);
+                    }
+                    _ => (),
+                }
+            }
+        })
+        .await;
+
+        for info in &mut devices {
+            self.gatt_batteries.fill(info);
+        }
+        Ok(devices)
+    }
+
+    async fn known_devices(&self) -> Result<Vec<DeviceInfo>> {
+        let mut devices = Vec::new();
+        for addr in self.adapter()?.device_addresses().await? {
+            devices.push(self.properties(addr).await?);
+        }
+        Ok(devices)
+    }
+
+    async fn is_discovering(&self) -> Result<bool> {
+        Ok(self.adapter()?.is_discovering().await?)
+    }
+
+    async fn properties(&self, addr: Address) -> Result<DeviceInfo> {
+        let mut info = self.devices.info(&self.adapter()?, addr).await?;
+        self.gatt_batteries.fill(&mut info);
+        Ok(info)
+    }
+
+    async fn details(&self, addr: Address) -> Result<DeviceDetails> {
+        let device = self.device(addr)?;
+        let mut details = DeviceDetails {
+            addr,
+            ..DeviceDetails::default()
+        };
+
+        // BR/EDR headsets rarely have the service, LE ones mostly do
+        if device.is_connected().await? {
+            if let Err(err) = read_device_information(&device, &mut details).await {
+                debug!(?err, %addr, "read device information failed");
+            }
+        }
+
+        // the device ID only has numbers, from its maker's own numbering
+        if let Some(modalias) = device.modalias().await? {
+            let hex = |value: u32| format!("{:#06x}", value);
+            details
+                .manufacturer
+                .get_or_insert_with(|| hex(modalias.vendor));
+            details.model.get_or_insert_with(|| hex(modalias.product));
+            details.firmware.get_or_insert_with(|| hex(modalias.device));
+        }
+        Ok(details)
+    }
+
+    async fn pair(&self, addr: Address, pin: &str) -> Result<bool> {
+        *self.pin_request.lock().unwrap() = PinRequest {
+            pin: Some((addr, pin.to_string())),
+            asked: false,
+        };
+        let result = match self.device(addr) {
+            Ok(device) => device.pair().await.map_err(Error::from),
+            Err(err) => Err(err),
+        };
+        let asked = mem::take(&mut *self.pin_request.lock().unwrap()).asked;
+
+        match result {
+            Ok(()) => {
+                self.devices.update(addr, |info| info.paired = true);
+                Ok(asked)
+            }
+            Err(Error::PairingRejected(_)) if asked => Err(Error::WrongPin),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn cancel_pairing(&self, addr: Address) -> Result<()> {
+        self.device(addr)?.cancel_pairing().await?;
+        Ok(())
+    }
+
+    async fn connect(&self, addr: Address) -> Result<()> {
+        self.device(addr)?.connect().await?;
+        self.devices.update(addr, |info| info.connected = true);
+        Ok(())
+    }
+
+    async fn connect_profile(&self, addr: Address, profile: AudioProfile) -> Result<()> {
+        self.device(addr)?.connect_profile(&profile.uuid()).await?;
+        self.devices.update(addr, |info| info.connected = true);
+        Ok(())
+    }
+
+    async fn set_trusted(&self, addr: Address, trusted: bool) -> Result<()> {
+        self.device(addr)?.set_trusted(trusted).await?;
+        Ok(())
+    }
+
+    async fn has_audio(&self, addr: Address) -> Result<bool> {
+        Ok(self
+            .media
+            .find(&self.device_path(addr)?, MEDIA_TRANSPORT)
+            .is_some())
+    }
+
+    async fn link_stats(&self, addr: Address) -> Result<LinkStats> {
+        // bluetoothd doesn't tell the link of a connection, the kernel does
+        let adapter = self.adapter()?;
+        let index = mgmt::adapter_index(adapter.name())
+            .ok_or_else(|| Error::bluez(format!("no index in {}", adapter.name())))?;
+        let address_type = self.device(addr)?.address_type().await?;
+        let connection = mgmt_call(move || mgmt::connection_info(index, addr, address_type)).await;
+        let packets = mgmt_call(move || mgmt::adapter_packets(index)).await;
+
+        let rssi = connection.and_then(|connection| connection.rssi);
+        Ok(LinkStats {
+            rssi: match address_type {
+                AddressType::BrEdr => rssi,
+                // told in dBm, made relative to where a good signal starts
+                _ => rssi.map(|rssi| (i16::from(rssi) - GOOD_RSSI).min(0) as i8),
+            },
+            tx_power: connection.and_then(|connection| connection.tx_power),
+            packets,
+        })
+    }
+
+    async fn volume(&self, addr: Address) -> Result<u8> {
+        let transport = self.transport(addr).await?;
+        let volume = transport
+            .get(
+                InterfaceName::from_static_str_unchecked(MEDIA_TRANSPORT),
+                "Volume",
+            )
+            .await
+            .map_err(dbus_error)?;
+        let volume = u16::try_from(volume).map_err(dbus_error)?;
+        Ok((volume.min(MAX_TRANSPORT_VOLUME) * 100 / MAX_TRANSPORT_VOLUME) as u8)
+    }
+
+    async fn now_playing(&self, addr: Address) -> Result<Option<NowPlaying>> {
+        let Some((_, player)) = self.media.find(&self.device_path(addr)?, MEDIA_PLAYER) else {
+            return Ok(None);
+        };
+        let mut properties = player.properties;
+
+        // a player without a track, like before the first song
+        let Some(track) = properties.remove("Track") else {
+            return Ok(None);
+        };
+        let mut track = HashMap::<String, OwnedValue>::try_from(track).map_err(dbus_error)?;
+        let mut text = |key: &str| {
+            track
+                .remove(key)
+                .and_then(|value| String::try_from(value).ok())
+                .unwrap_or_default()
+        };
+        let (title, artist) = (text("Title"), text("Artist"));
+        let millis = |value: Option<OwnedValue>| {
+            value
+                .and_then(|value| u32::try_from(value).ok())
+                .map(|millis| Duration::from_millis(millis.into()))
+        };
+
+        // 0 or missing for streams
+        let duration = millis(track.remove("Duration")).filter(|duration| !duration.is_zero());
+        let playing = properties
+            .remove("Status")
+            .and_then(|status| String::try_from(status).ok())
+            .is_some_and(|status| status == "playing");
+        let mut position = millis(properties.remove("Position")).unwrap_or_default();
+        // bluetoothd tells the position on a seek or a new status only
+        if playing {
+            position += player.position_at.elapsed();
+        }
+        if let Some(duration) = duration {
+            position = position.min(duration);
+        }
+
+        Ok(Some(NowPlaying {
+            title,
+            artist,
+            position,
+            duration,
+            playing,
+        }))
+    }
+
+    async fn set_volume(&self, addr: Address, volume: u8) -> Result<()> {
+        let volume = u16::from(volume.min(100)) * MAX_TRANSPORT_VOLUME / 100;
+        self.transport(addr)
+            .await?
+            .set(
+                InterfaceName::from_static_str_unchecked(MEDIA_TRANSPORT),
+                "Volume",
+                &Value::from(volume),
+            )
+            .await
+            .map_err(dbus_error)?;
+        Ok(())
+    }
+
+    async fn disconnect(&self, addr: Address) -> Result<()> {
+        self.device(addr)?.disconnect().await?;
+        self.devices.update(addr, |info| info.connected = false);
+        Ok(())
+    }
+
+    async fn connect_network(&self, addr: Address) -> Result<String> {
+        let network = self.network(addr).await?;
+        let interface = network.connect(NAP_ROLE).await.map_err(dbus_error)?;
+        info!(%addr, interface, "network of the phone connected");
+
+        match lease_address(&interface).await {
+            Ok(setup) => {
+                // a phone tethered again replaces its old setup, which is
+                // undone first so the name servers from before it are kept
+                let old = self.networks.lock().unwrap().remove(&addr);
+                if let Some(old) = old {
+                    old.undo().await;
+                }
+                self.networks.lock().unwrap().insert(addr, setup);
+            }
+            Err(err) => {
+                // no use keeping a network without an address
+                if let Err(err) = network.disconnect().await {
+                    warn!(?err, %addr, "disconnect network failed");
+                }
+                return Err(err);
+            }
+        }
+        Ok(interface)
+    }
+
+    async fn disconnect_network(&self, addr: Address) -> Result<()> {
+        let setup = self.networks.lock().unwrap().remove(&addr);
+        if let Some(setup) = setup {
+            setup.undo().await;
+        }
+        self.network(addr)
+            .await?
+            .disconnect()
+            .await
+            .map_err(dbus_error)
+    }
+
+    async fn remove(&self, addr: Address) -> Result<()> {
+        self.adapter()?.remove_device(addr).await?;
+        self.devices.evict(addr);
+        Ok(())
+    }
+
+    async fn events(&self) -> Result<mpsc::Receiver<BluetoothEvent>> {
+        let adapter = self.adapter()?;
+        let adapter_events = adapter.events().await?;
+        let known = adapter.device_addresses().await?;
+        let (event_tx, event_rx) = mpsc::channel(64);
+        let gatt_batteries = self.gatt_batteries.clone();
+
+        let mut media_changes = self.media.changed_tx.subscribe();
+        let media_tx = event_tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let addr = match media_changes.recv().await {
+                    Ok(addr) => addr,
+                    // the next change reads the object again anyway
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                if media_tx
+                    .send(BluetoothEvent::MediaChanged(addr))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            let mut properties = PropertiesCache::new(EVENT_FETCH_WINDOW);
+            // one watch per device, not one per time it was added
+            let mut watched = HashSet::new();
+            for addr in known {
+                watched.insert(addr);
+                watch_device(&adapter, addr, event_tx.clone(), gatt_batteries.clone());
+            }
+
+            let mut adapter_events = pin!(adapter_events);
+            while let Some(adapter_event) = adapter_events.next().await {
+                let event = match adapter_event {
+                    AdapterEvent::DeviceAdded(addr) => match properties.get(&adapter, addr).await {
+                        Ok(info) => {
+                            if watched.insert(addr) {
+                                watch_device(
+                                    &adapter,
+                                    addr,
+                                    event_tx.clone(),
+                                    gatt_batteries.clone(),
+                                );
+                            }
+                            BluetoothEvent::DeviceFound(info)
+                        }
+                        Err(err) => {
+                            error!(?err, "get device properties failed");
+                            continue;
+                        }
+                    },
+                    AdapterEvent::DeviceRemoved(addr) => {
+                        // the watch ends with the device object
+                        watched.remove(&addr);
+                        BluetoothEvent::DeviceRemoved(addr)
+                    }
+                    _ => continue,
+                };
+                if event_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(event_rx)
+    }
+
+    fn adapter_present(&self) -> watch::Receiver<bool> {
+        self.adapter_rx.clone()
+    }
+}
+
+/// Forward the connection and battery changes of a device, until the events
+/// receiver is dropped. The battery is read from the GATT Battery Service
+/// while connected, when BlueZ has no `Battery1` for the device.
+fn watch_device(
+    adapter: &Adapter, addr: Address, event_tx: mpsc::Sender<BluetoothEvent>,
+    gatt_batteries: Arc<GattBatteries>,
+) {
+    let device = match adapter.device(addr) {
+        Ok(device) => device,
+        Err(err) => {
+            error!(?err, %addr, "get device failed");
+            return;
+        }
+    };
+
+    tokio::spawn(async move {
+        let device_events = match device.events().await {
+            Ok(device_events) => device_events,
+            Err(err) => {
+                error!(?err, %addr, "watch device failed");
+                return;
+            }
+        };
+        let mut device_events = pin!(device_events);
+
+        let follow_battery = || {
+            tokio::spawn(follow_gatt_battery(
+                device.clone(),
+                event_tx.clone(),
+                gatt_batteries.clone(),
+            ))
+        };
+        // connected before the watch started
+        let mut gatt_battery = device
+            .is_services_resolved()
+            .await
+            .unwrap_or(false)
+            .then(&follow_battery);
+
+        while let Some(DeviceEvent::PropertyChanged(prop)) = device_events.next().await {
+            let event = match prop {
+                DeviceProperty::Connected(true) => BluetoothEvent::Connected(addr),
+                DeviceProperty::Connected(false) => {
+                    if let Some(task) = gatt_battery.take() {
+                        task.abort();
+                    }
+                    gatt_batteries.remove(addr);
+                    BluetoothEvent::Disconnected(addr)
+                }
+                // the services are only there once resolved after connecting
+                DeviceProperty::ServicesResolved(true) => {
+                    if gatt_battery.is_none() {
+                        gatt_battery = Some(follow_battery());
+                    }
+                    continue;
+                }
+                DeviceProperty::BatteryPercentage(battery) => {
+                    BluetoothEvent::BatteryChanged { addr, battery }
+                }
+                _ => continue,
+            };
+            if event_tx.send(event).await.is_err() {
+                break;
+            }
+        }
+        if let Some(task) = gatt_battery {
+            task.abort();
+        }
+    });
+}
+
+/// The media transports and players of the devices, bluer doesn't cover the
+/// media API. Followed from the signals of bluetoothd, so reading them is no
+/// D-Bus call.
+struct MediaObjects {
+    objects: RwLock<HashMap<String, MediaObject>>,
+    // the device of each object added, changed or removed
+    changed_tx: broadcast::Sender<Address>,
+}
+
+/// The properties of a media transport or player.
+#[derive(Clone)]
+struct MediaObject {
+    interface: &'static str,
+    properties: HashMap<String, OwnedValue>,
+    /// When a player last told its `Position`, which moves on by itself while
+    /// playing.
+    position_at: Instant,
+}
+
+impl MediaObjects {
+    /// Load the media objects bluetoothd has and follow their changes, until
+    /// the connection closes.
+    async fn follow(connection: &zbus::Connection) -> zbus::Result<Arc<Self>> {
+        let object_manager = ObjectManagerProxy::builder(connection)
+            .destination(BLUEZ_SERVICE)?
+            .path("/")?
+            .build()
+            .await?;
+        // subscribed before the objects are read, so no change is missed
+        let added = object_manager.receive_interfaces_added().await?;
+        let removed = object_manager.receive_interfaces_removed().await?;
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(BLUEZ_SERVICE)?
+            .path_namespace("/org/bluez")?
+            .interface("org.freedesktop.DBus.Properties")?
+            .member("PropertiesChanged")?
+            .build();
+        let changed = MessageStream::for_match_rule(rule, connection, None).await?;
+
+        let (changed_tx, _) = broadcast::channel(16);
+        let media = Arc::new(Self {
+            objects: RwLock::default(),
+            changed_tx,
+        });
+        for (path, interfaces) in object_manager.get_managed_objects().await? {
+            for (interface, properties) in interfaces {
+                media.add(path.as_str(), interface.as_str(), properties);
+            }
+        }
+        tokio::spawn(media.clone().watch(added, removed, changed));
+        Ok(media)
+    }
+
+    async fn watch(
+        self: Arc<Self>, mut added: InterfacesAddedStream<'static>,
+        mut removed: InterfacesRemovedStream<'static>, mut changed: MessageStream,
+    ) {
+        loop {
+            tokio::select! {
+                Some(signal) = added.next() => {
+                    let Ok(args) = signal.args() else {
+                        continue;
+                    };
+                    for (interface, properties) in args.interfaces_and_properties() {
+                        let properties = properties
+                            .iter()
+                            .map(|(name, value)| (name.to_string(), OwnedValue::from(value.clone())))
+                            .collect();
+                        self.add(args.object_path().as_str(), interface, properties);
+                    }
+                }
+                Some(signal) = removed.next() => {
+                    let Ok(args) = signal.args() else {
+                        continue;
+                    };
+                    self.remove(args.object_path().as_str(), args.interfaces());
+                }
+                Some(message) = changed.next() => {
+                    let Ok(message) = message else {
+                        continue;
+                    };
+                    let Some(path) = message.path() else {
+                        continue;
+                    };
+                    let body = message.body::<(String, HashMap<String, OwnedValue>, Vec<String>)>();
+                    match body {
+                        Ok((interface, changed, invalidated)) => {
+                            self.change(path.as_str(), &interface, changed, &invalidated);
+                        }
+                        Err(err) => debug!(?err, %path, "read changed properties failed"),
+                    }
+                }
+                else => break,
+            }
+        }
+        debug!("media signals ended");
+    }
+
+    /// The path and properties of the object of the device with the media
+    /// `interface`, `None` without one.
+    fn find(&self, device_path: &str, interface: &str) -> Option<(String, MediaObject)> {
+        let device_path = format!("{device_path}/");
+        self.objects
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(path, object)| path.starts_with(&device_path) && object.interface == interface)
+            .map(|(path, object)| (path.clone(), object.clone()))
+    }
+
+    fn add(&self, path: &str, interface: &str, properties: HashMap<String, OwnedValue>) {
+        let interface = match interface {
+            MEDIA_TRANSPORT => MEDIA_TRANSPORT,
+            MEDIA_PLAYER => MEDIA_PLAYER,
+            _ => return,
+        };
+        let object = MediaObject {
+            interface,
+            properties,
+            position_at: Instant::now(),
+        };
+        self.objects
+            .write()
+            .unwrap()
+            .insert(path.to_string(), object);
+        self.notify(path);
+    }
+
+    fn remove(&self, path: &str, interfaces: &[&str]) {
+        let mut objects = self.objects.write().unwrap();
+        if objects
+            .get(path)
+            .is_some_and(|object| interfaces.contains(&object.interface))
+        {
+            objects.remove(path);
+            drop(objects);
+            self.notify(path);
+        }
+    }
+
+    fn change(
+        &self, path: &str, interface: &str, changed: HashMap<String, OwnedValue>,
+        invalidated: &[String],
+    ) {
+        let mut objects = self.objects.write().unwrap();
+        let Some(object) = objects
+            .get_mut(path)
+            .filter(|object| object.interface == interface)
+        else {
+            return;
+        };
+        if changed.contains_key("Position") {
+            object.position_at = Instant::now();
+        }
+        object.properties.extend(changed);
+        for name in invalidated {
+            object.properties.remove(name);
+        }
+        drop(objects);
+        self.notify(path);
+    }
+
+    fn notify(&self, path: &str) {
+        if let Some(addr) = path_address(path) {
+            // no receiver while nothing subscribed to the events
+            let _ = self.changed_tx.send(addr);
+        }
+    }
+}
+
+/// The device of a BlueZ object, like
+/// `/org/bluez/hci0/dev_00_11_22_33_44_55/fd0`.
+fn path_address(path: &str) -> Option<Address> {
+    let device = path.split('/').find_map(|part| part.strip_prefix("dev_"))?;
+    device.replace('_', ":").parse().ok()
+}
+
+/// The battery levels read from the GATT Battery Service, for the devices
+/// BlueZ has no `Battery1` for.
+#[derive(Default)]
+struct GattBatteries(Mutex<HashMap<Address, u8>>);
+
+impl GattBatteries {
+    /// Fill in the level of a device when BlueZ tells none.
+    fn fill(&self, info: &mut DeviceInfo) {
+        if info.battery.is_none() {
+            info.battery = self.0.lock().unwrap().get(&info.addr).copied();
+        }
+    }
+
+    fn set(&self, addr: Address, battery: u8) {
+        self.0.lock().unwrap().insert(addr, battery);
+    }
+
+    fn remove(&self, addr: Address) {
+        self.0.lock().unwrap().remove(&addr);
+    }
+}
+
+/// Read the battery level of a connected device from its GATT Battery
+/// Service, and follow it while the device notifies changes. BlueZ claims the
+/// service itself for `Battery1` when it can, then there's nothing to read.
+async fn follow_gatt_battery(
+    device: Device, event_tx: mpsc::Sender<BluetoothEvent>, gatt_batteries: Arc<GattBatteries>,
+) {
+    let addr = device.address();
+    if !matches!(device.battery_percentage().await, Ok(None)) {
+        return;
+    }
+    let level = match battery_level(&device).await {
+        Ok(Some(level)) => level,
+        Ok(None) => {
+            debug!(%addr, "no battery service");
+            return;
+        }
+        Err(err) => {
+            warn!(?err, %addr, "find battery service failed");
+            return;
+        }
+    };
+
+    let report = |value: Vec<u8>| {
+        let event_tx = event_tx.clone();
+        let gatt_batteries = gatt_batteries.clone();
+        async move {
+            // an empty value changes nothing
+            let Some(&battery) = value.first() else {
+                return true;
+            };
+            let battery = battery.min(100);
+            gatt_batteries.set(addr, battery);
+            event_tx
+                .send(BluetoothEvent::BatteryChanged { addr, battery })
+                .await
+                .is_ok()
+        }
+    };
+
+    match level.read().await {
+        Ok(value) => {
+            info!(%addr, ?value, "battery read from gatt");
+            if !report(value).await {
+                return;
+            }
+        }
+        Err(err) => {
+            warn!(?err, %addr, "read gatt battery failed");
+            return;
+        }
+    }
+
+    // the notification is optional, the level read stays then
+    let notifications = match level.notify().await {
+        Ok(notifications) => notifications,
+        Err(err) => {
+            debug!(?err, %addr, "gatt battery doesn't notify");
+            return;
+        }
+    };
+    let mut notifications = pin!(notifications);
+    while let Some(value) = notifications.next().await {
+        if !report(value).await {
+            break;
+        }
+    }
+}
+
+/// Fill in the texts of the Device Information Service of the device.
+async fn read_device_information(
+    device: &Device, details: &mut DeviceDetails,
+) -> bluer::Result<()> {
+    for service in device.services().await? {
+        if service.uuid().await? != DEVICE_INFORMATION {
+            continue;
+        }
+        for characteristic in service.characteristics().await? {
+            let field = match characteristic.uuid().await? {
+                MANUFACTURER_NAME => &mut details.manufacturer,
+                MODEL_NUMBER => &mut details.model,
+                FIRMWARE_REVISION => &mut details.firmware,
+                HARDWARE_REVISION => &mut details.hardware,
+                _ => continue,
+            };
+            // some devices pad the strings with zeros
+            let value = characteristic.read().await?;
+            let value = String::from_utf8_lossy(&value)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string();
+            *field = (!value.is_empty()).then_some(value);
+        }
+    }
+    Ok(())
+}
+
+/// The battery level characteristic of the Battery Service of the device.
+async fn battery_level(device: &Device) -> bluer::Result<Option<Characteristic>> {
+    for service in device.services().await? {
+        if service.uuid().await? != BATTERY_SERVICE {
+            continue;
+        }
+        for characteristic in service.characteristics().await? {
+            if characteristic.uuid().await? == BATTERY_LEVEL {
+                return Ok(Some(characteristic));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// The properties of the added devices, a device added and removed over and
+/// over in a busy place is only fetched once per window.
+struct PropertiesCache {
+    window: Duration,
+    fetched: HashMap<Address, (Instant, DeviceInfo)>,
+}
+
+impl PropertiesCache {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            fetched: HashMap::new(),
+        }
+    }
+
+    async fn get(&mut self, adapter: &Adapter, addr: Address) -> Result<DeviceInfo> {
+        if let Some((fetched_at, info)) = self.fetched.get(&addr) {
+            if fetched_at.elapsed() < self.window {
+                return Ok(info.clone());
+            }
+        }
+        let info = device_info(adapter, addr).await?;
+        self.fetched.insert(addr, (Instant::now(), info.clone()));
+        Ok(info)
+    }
+}
+
+/// The handles of the devices and their last known properties, kept current by
+/// the changes BlueZ sends, so the steps of a connect don't read every
+/// property again over D-Bus.
+#[derive(Default)]
+struct DeviceCache {
+    devices: Mutex<HashMap<Address, CachedDevice>>,
+    // tells the follow task of an evicted device from the one of its new entry
+    next_id: AtomicU64,
+}
+
+struct CachedDevice {
+    id: u64,
+    device: Device,
+    info: DeviceInfo,
+}
+
+impl DeviceCache {
+    fn device(&self, adapter: &Adapter, addr: Address) -> Result<Device> {
+        if let Some(cached) = self.devices.lock().unwrap().get(&addr) {
+            return Ok(cached.device.clone());
+        }
+        Ok(adapter.device(addr)?)
+    }
+
+    /// The properties of the device, read once and then followed.
+    async fn info(self: &Arc<Self>, adapter: &Adapter, addr: Address) -> Result<DeviceInfo> {
+        let cached = self
+            .devices
+            .lock()
+            .unwrap()
+            .get(&addr)
+            .map(|cached| cached.info.clone());
+        if let Some(info) = cached {
+            return Ok(info);
+        }
+
+        let device = adapter.device(addr)?;
+        // subscribed before reading, so no change is missed in between
+        let device_events = device.events().await?;
+        let info = device_info(adapter, addr).await?;
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.devices.lock().unwrap().insert(
+            addr,
+            CachedDevice {
+                id,
+                device,
+                info: info.clone(),
+            },
+        );
+        tokio::spawn(self.clone().follow(addr, id, device_events));
+        Ok(info)
+    }
+
+    /// Apply the changes of the device until it's gone or evicted.
+    async fn follow(
+        self: Arc<Self>, addr: Address, id: u64,
+        device_events: impl tokio_stream::Stream<Item = DeviceEvent>,
+    ) {
+        let mut device_events = pin!(device_events);
+        while let Some(DeviceEvent::PropertyChanged(prop)) = device_events.next().await {
+            let mut devices = self.devices.lock().unwrap();
+            match devices.get_mut(&addr) {
+                Some(cached) if cached.id == id => apply_property(&mut cached.info, prop),
+                _ => return,
+            }
+        }
+
+        let mut devices = self.devices.lock().unwrap();
+        if devices.get(&addr).is_some_and(|cached| cached.id == id) {
+            devices.remove(&addr);
+        }
+    }
+
+    /// Change the cached properties after a call of this backend, BlueZ may
+    /// send the change after the call returns.
+    fn update(&self, addr: Address, f: impl FnOnce(&mut DeviceInfo)) {
+        if let Some(cached) = self.devices.lock().unwrap().get_mut(&addr) {
+            f(&mut cached.info);
+        }
+    }
+
+    fn evict(&self, addr: Address) {
+        self.devices.lock().unwrap().remove(&addr);
+    }
+
+    /// Forget every device, when their adapter is gone.
+    fn clear(&self) {
+        self.devices.lock().unwrap().clear();
+    }
+}
+
+async fn device_info(adapter: &Adapter, addr: Address) -> Result<DeviceInfo> {
+    let device = adapter.device(addr)?;
+
+    let mut info = DeviceInfo::default();
+    info.addr = addr;
+
+    for prop in device.all_properties().await? {
+        apply_property(&mut info, prop);
+    }
+
+    Ok(info)
+}
+
+fn apply_property(info: &mut DeviceInfo, prop: DeviceProperty) {
+    match prop {
+        DeviceProperty::Name(name) => {
+            info.name = name;
+        }
+        DeviceProperty::Paired(paired) => {
+            info.paired = paired;
+        }
+        DeviceProperty::Connected(connected) => {
+            info.connected = connected;
+        }
+        DeviceProperty::Rssi(rssi) => {
+            info.rssi = Some(rssi);
+        }
+        DeviceProperty::TxPower(tx_power) => {
+            info.tx_power = Some(tx_power);
+        }
+        DeviceProperty::BatteryPercentage(battery) => {
+            info.battery = Some(battery);
+        }
+        DeviceProperty::Class(class) => {
+            info.class = Some(class);
+        }
+        DeviceProperty::Uuids(uuids) => {
+            info.uuids = uuids.into_iter().collect();
+        }
+        // a USB vendor ID is another numbering
+        DeviceProperty::Modalias(modalias) if modalias.source == "bluetooth" => {
+            info.vendor = u16::try_from(modalias.vendor).ok();
+        }
+        // the service and manufacturer data come apart, each only changes its
+        // own advertisement
+        DeviceProperty::ManufacturerData(data) => {
+            info.buds_battery = battery::buds_battery(&data);
+            update_pairing_advertisement(
+                info,
+                PairingAdvertisement::SwiftPair,
+                PairingAdvertisement::is_swift_pair(&data),
+            );
+        }
+        DeviceProperty::ServiceData(data) => update_pairing_advertisement(
+            info,
+            PairingAdvertisement::FastPair,
+            PairingAdvertisement::is_fast_pair(&data),
+        ),
+        _ => {}
+    }
+}
+
+fn update_pairing_advertisement(
+    info: &mut DeviceInfo, advertisement: PairingAdvertisement, advertised: bool,
+) {
+    if advertised {
+        info.pairing_advertisement = Some(advertisement);
+    } else if info.pairing_advertisement == Some(advertisement) {
+        info.pairing_advertisement = None;
+    }
+}
+
+/// The PAN client of a device.
+#[dbus_proxy(interface = "org.bluez.Network1", default_service = "org.bluez")]
+trait Network {
+    /// Connect the PAN role of the device, returns the interface it gets.
+    fn connect(&self, uuid: &str) -> zbus::Result<String>;
+
+    fn disconnect(&self) -> zbus::Result<()>;
+}
+
+/// What the phone handed out by DHCP.
+#[derive(Debug, PartialEq)]
+struct Lease {
+    address: Ipv4Addr,
+    prefix: u8,
+    router: Option<Ipv4Addr>,
+    dns: Vec<Ipv4Addr>,
+}
+
+impl Lease {
+    /// The lease from the `lease key=value` lines `DHCP_SCRIPT` prints.
+    /// Older udhcpc only give the netmask, newer ones its length as well.
+    fn parse(output: &str) -> Option<Self> {
+        let mut address = None;
+        let mut prefix = None;
+        let mut netmask = None;
+        let mut router = None;
+        let mut dns = Vec::new();
+        for line in output.lines() {
+            let Some((key, value)) = line
+                .strip_prefix("lease ")
+                .and_then(|line| line.split_once('='))
+            else {
+                continue;
+            };
+            match key {
+                "ip" => address = value.parse().ok(),
+                "mask" => prefix = value.parse().ok().filter(|prefix| *prefix <= 32),
+                "subnet" => netmask = value.parse::<Ipv4Addr>().ok(),
+                // the first router is the gateway, like the default script
+                "router" => router = router.or(value.parse().ok()),
+                "dns" => dns.extend(value.parse::<Ipv4Addr>().ok()),
+                _ => {}
+            }
+        }
+        let prefix = prefix
+            .or_else(|| netmask.map(|netmask| u32::from(netmask).leading_ones() as u8))
+            .unwrap_or(24);
+        Some(Self {
+            address: address?,
+            prefix,
+            router,
+            dns,
+        })
+    }
+}
+
+/// What `lease_address` changed besides the interface, which goes away with
+/// the connection by itself.
+#[derive(Debug)]
+struct NetworkSetup {
+    interface: String,
+    /// The gateway of the default route added through the phone.
+    router: Option<Ipv4Addr>,
+    resolv_conf: Option<ResolvConf>,
+}
+
+/// The name servers of the phone written over the ones before.
+#[derive(Debug)]
+struct ResolvConf {
+    written: String,
+    /// `None` when there was no file.
+    previous: Option<Vec<u8>>,
+}
+
+impl NetworkSetup {
+    /// Delete the route and put back the name servers from before, unless
+    /// something else like the DHCP client of a Wi-Fi rewrote them since.
+    async fn undo(self) {
+        if let Some(router) = self.router {
+            let router = router.to_string();
+            let interface = self.interface.as_str();
+            // the kernel already dropped it when the interface is gone
+            match ip(&["route", "del", "default", "via", &router, "dev", interface]).await {
+                Ok(()) => info!(interface, "default route removed"),
+                Err(err) => {
+                    debug!(?err, interface, "remove default route failed")
+                }
+            }
+        }
+
+        let Some(resolv_conf) = self.resolv_conf else {
+            return;
+        };
+        match std::fs::read_to_string(RESOLV_CONF) {
+            Ok(current) if current == resolv_conf.written => {}
+            _ => {
+                info!("name servers changed since tethering, keep them");
+                return;
+            }
+        }
+        let restored = match resolv_conf.previous {
+            Some(previous) => std::fs::write(RESOLV_CONF, previous),
+            None => std::fs::remove_file(RESOLV_CONF),
+        };
+        match restored {
+            Ok(()) => info!("name servers restored"),
+            Err(err) => warn!(?err, "restore name servers failed"),
+        }
+    }
+}
+
+/// Bring up the interface of a tethered phone, get an address from it by DHCP
+/// and put the address, the default route and the name servers in place like
+/// the default script of udhcpc. Returns what has to be undone when the
+/// network goes down.
+async fn lease_address(interface: &str) -> Result<NetworkSetup> {
+    ip(&["link", "set", interface, "up"]).await?;
+
+    let output = {
+        let dir = PrivateDir::create()
+            .map_err(|err| Error::NetworkSetup(format!("create script directory: {err}")))?;
+        let script = write_script(&dir.0)
+            .map_err(|err| Error::NetworkSetup(format!("write {}: {err}", dir.0.display())))?;
+        let script = script.to_string_lossy();
+        // -n gives up when the phone doesn't answer, -q quits with the address
+        run(
+            DHCP_CLIENT,
+            &["-i", interface, "-n", "-q", "-s", &script],
+            DHCP_TIMEOUT,
+        )
+        .await?
+        .ok_or(Error::NoNetworkAddress)?
+    };
+    if !output.status.success() {
+        return Err(Error::NoNetworkAddress);
+    }
+    let lease =
+        Lease::parse(&String::from_utf8_lossy(&output.stdout)).ok_or(Error::NoNetworkAddress)?;
+    info!(interface, ?lease, "network address leased");
+
+    let address = format!("{}/{}", lease.address, lease.prefix);
+    ip(&["addr", "flush", "dev", interface]).await?;
+    ip(&["addr", "add", &address, "dev", interface]).await?;
+
+    let mut setup = NetworkSetup {
+        interface: interface.to_string(),
+        router: None,
+        resolv_conf: None,
+    };
+    if let Some(router) = lease.router {
+        let gateway = router.to_string();
+        // fails with the default route of a Wi-Fi in place, which then stays
+        match ip(&["route", "add", "default", "via", &gateway, "dev", interface]).await {
+            Ok(()) => setup.router = Some(router),
+            Err(err) => warn!(?err, interface, "add default route failed"),
+        }
+    }
+    if !lease.dns.is_empty() {
+        let written = lease
+            .dns
+            .iter()
+            .map(|server| format!("nameserver {server}\n"))
+            .collect::<String>();
+        let previous = match std::fs::read(RESOLV_CONF) {
+            Ok(previous) => Some(previous),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                warn!(?err, "read name servers failed");
+                None
+            }
+        };
+        // the address works without them, only names don't resolve
+        match std::fs::write(RESOLV_CONF, &written) {
+            Ok(()) => setup.resolv_conf = Some(ResolvConf { written, previous }),
+            Err(err) => warn!(?err, "write name servers failed"),
+        }
+    }
+    Ok(setup)
+}
+
+/// A new directory only its owner can enter, removed with what's in it when
+/// dropped.
+struct PrivateDir(PathBuf);
+
+impl PrivateDir {
+    fn create() -> io::Result<Self> {
+        let template = std::env::temp_dir().join(DHCP_SCRIPT_DIR);
+        let mut template =
+            CString::new(template.into_os_string().into_vec())?.into_bytes_with_nul();
+        // SAFETY: the template is a nul terminated string, which mkdtemp
+        // fills in without changing its length
+        if unsafe { libc::mkdtemp(template.as_mut_ptr().cast()) }.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        template.pop();
+        Ok(Self(PathBuf::from(OsString::from_vec(template))))
+    }
+}
+
+impl Drop for PrivateDir {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.0) {
+            warn!(?err, path = ?self.0, "remove script directory failed");
+        }
+    }
+}
+
+/// Write the DHCP script in the directory, failing rather than following a
+/// file already there.
+fn write_script(dir: &Path) -> io::Result<PathBuf> {
+    let path = dir.join(DHCP_SCRIPT_NAME);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o700)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(&path)?
+        .write_all(DHCP_SCRIPT.as_bytes())?;
+    Ok(path)
+}
+
+/// Run `ip` with the arguments, which has to succeed.
+async fn ip(args: &[&str]) -> Result<()> {
+    let command = format!("ip {}", args.join(" "));
+    let output = run("ip", args, IP_TIMEOUT)
+        .await?
+        .ok_or_else(|| Error::NetworkSetup(format!("{command} timed out")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::NetworkSetup(format!(
+            "{command}: {}",
+            stderr.trim_end()
+        )));
+    }
+    Ok(())
+}
+
+/// The output of a program, `None` when it runs longer than the limit and was
+/// killed.
+async fn run(program: &str, args: &[&str], limit: Duration) -> Result<Option<Output>> {
+    let output = process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    match timeout(limit, output).await {
+        Ok(output) => output
+            .map(Some)
+            .map_err(|err| Error::NetworkSetup(format!("run {program}: {err}"))),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Make a call on the management socket off the runtime, `None` when it fails,
+/// as it does without the rights for it or for a device on another adapter.
+async fn mgmt_call<T: Send + 'static>(
+    call: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> Option<T> {
+    match tokio::task::spawn_blocking(call).await {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(err)) => {
+            debug!(?err, "management call failed");
+            None
+        }
+        Err(err) => {
+            debug!(?err, "management call panicked");
+            None
+        }
+    }
+}
+
+fn dbus_error(err: impl std::fmt::Display) -> Error {
+    Error::bluez(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_of_device_paths() {
+        let addr = Address::new([0x00, 0x1b, 0x66, 0xaa, 0xbb, 0xcc]);
+        assert_eq!(
+            path_address("/org/bluez/hci0/dev_00_1B_66_AA_BB_CC"),
+            Some(addr)
+        );
+        assert_eq!(
+            path_address("/org/bluez/hci0/dev_00_1B_66_AA_BB_CC/fd0"),
+            Some(addr)
+        );
+        assert_eq!(path_address("/org/bluez/hci0"), None);
+        assert_eq!(path_address("/org/bluez/hci0/dev_00_1B"), None);
+    }
+
+    #[test]
+    fn lease_with_mask_length() {
+        let output = "udhcpc: started\n\
+                      lease ip=192.168.44.150\n\
+                      lease mask=24\n\
+                      lease subnet=255.255.255.0\n\
+                      lease router=192.168.44.1\n\
+                      lease router=192.168.44.2\n\
+                      lease dns=192.168.44.1\n\
+                      lease dns=8.8.8.8\n";
+        assert_eq!(
+            Lease::parse(output),
+            Some(Lease {
+                address: Ipv4Addr::new(192, 168, 44, 150),
+                prefix: 24,
+                router: Some(Ipv4Addr::new(192, 168, 44, 1)),
+                dns: vec![Ipv4Addr::new(192, 168, 44, 1), Ipv4Addr::new(8, 8, 8, 8)],
+            })
+        );
+    }
+
+    #[test]
+    fn lease_with_netmask_only() {
+        let lease = Lease::parse("lease ip=172.20.10.2\nlease subnet=255.255.255.240\n").unwrap();
+        assert_eq!(lease.prefix, 28);
+        assert_eq!(lease.router, None);
+        assert!(lease.dns.is_empty());
+    }
+
+    #[test]
+    fn no_lease_without_address() {
+        assert_eq!(Lease::parse(""), None);
+        assert_eq!(Lease::parse("lease router=192.168.44.1\n"), None);
+        assert_eq!(Lease::parse("lease ip=not-an-address\n"), None);
+    }
+}
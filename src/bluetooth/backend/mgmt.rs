@@ -0,0 +1,326 @@
+//! The management socket of the kernel, for what bluetoothd doesn't tell over
+//! D-Bus. The calls block, so they run off the async runtime.
+
+use crate::bluetooth::PacketStats;
+use bluer::{Address, AddressType};
+use std::{
+    ffi::{c_int, c_ulong},
+    fs::File,
+    io::{self, Read, Write},
+    mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    time::Duration,
+};
+
+const BTPROTO_HCI: c_int = 1;
+const HCI_CHANNEL_CONTROL: u16 = 3;
+// the control channel isn't bound to one adapter, each command names its own
+const HCI_DEV_NONE: u16 = 0xffff;
+
+// _IOR('H', 211, int) of the kernel headers
+const HCIGETDEVINFO: c_ulong = 0x800448d3;
+
+const MGMT_OP_GET_CONN_INFO: u16 = 0x0031;
+const MGMT_EV_CMD_COMPLETE: u16 = 0x0001;
+const MGMT_EV_CMD_STATUS: u16 = 0x0002;
+const MGMT_STATUS_SUCCESS: u8 = 0x00;
+const MGMT_HEADER_SIZE: usize = 6;
+
+// what the controller tells for an RSSI or power it doesn't know
+const HCI_UNKNOWN_POWER: i8 = 127;
+
+// the kernel answers at once, unless the controller hangs
+const MGMT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What the controller tells about a connection.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct ConnectionInfo {
+    /// In dB under the range the controller wants for BR/EDR, in dBm for LE.
+    pub rssi: Option<i8>,
+    /// The transmit power of the adapter on the connection, in dBm.
+    pub tx_power: Option<i8>,
+}
+
+#[repr(C)]
+struct SockaddrHci {
+    hci_family: libc::sa_family_t,
+    hci_dev: u16,
+    hci_channel: u16,
+}
+
+// laid out like the structs of the kernel, only the counters are read
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct HciDevStats {
+    err_rx: u32,
+    err_tx: u32,
+    cmd_tx: u32,
+    evt_rx: u32,
+    acl_tx: u32,
+    acl_rx: u32,
+    sco_tx: u32,
+    sco_rx: u32,
+    byte_rx: u32,
+    byte_tx: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct HciDevInfo {
+    dev_id: u16,
+    name: [u8; 8],
+    bdaddr: [u8; 6],
+    flags: u32,
+    dev_type: u8,
+    features: [u8; 8],
+    pkt_type: u32,
+    link_policy: u32,
+    link_mode: u32,
+    acl_mtu: u16,
+    acl_pkts: u16,
+    sco_mtu: u16,
+    sco_pkts: u16,
+    stat: HciDevStats,
+}
+
+/// The index of an adapter named like `hci0`.
+pub fn adapter_index(name: &str) -> Option<u16> {
+    name.strip_prefix("hci")?.parse().ok()
+}
+
+/// The RSSI and transmit power of the connection to a device, with the Get
+/// Connection Information command. Needs `CAP_NET_ADMIN`.
+pub fn connection_info(
+    index: u16, addr: Address, address_type: AddressType,
+) -> io::Result<ConnectionInfo> {
+    let socket = hci_socket()?;
+    let address = SockaddrHci {
+        hci_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+        hci_dev: HCI_DEV_NONE,
+        hci_channel: HCI_CHANNEL_CONTROL,
+    };
+    // SAFETY: the address is a `sockaddr_hci` of the given size
+    let bound = unsafe {
+        libc::bind(
+            socket.as_raw_fd(),
+            (&address as *const SockaddrHci).cast(),
+            mem::size_of::<SockaddrHci>() as libc::socklen_t,
+        )
+    };
+    if bound < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    set_receive_timeout(&socket, MGMT_TIMEOUT)?;
+
+    let mut socket = File::from(socket);
+    socket.write_all(&conn_info_command(index, addr, address_type))?;
+    let mut packet = [0; 512];
+    loop {
+        let len = socket.read(&mut packet).map_err(|err| {
+            if err.kind() == io::ErrorKind::WouldBlock {
+                io::Error::new(io::ErrorKind::TimedOut, "no answer of the kernel")
+            } else {
+                err
+            }
+        })?;
+        // other events of the control channel come along
+        if let Some(reply) = conn_info_reply(&packet[..len], index) {
+            return reply;
+        }
+    }
+}
+
+/// The packets of an adapter since it came up, of every device it's
+/// connected to.
+pub fn adapter_packets(index: u16) -> io::Result<PacketStats> {
+    let socket = hci_socket()?;
+    let mut info = HciDevInfo {
+        dev_id: index,
+        ..HciDevInfo::default()
+    };
+    // SAFETY: the kernel fills in a `hci_dev_info`, which `info` is laid out
+    // like
+    let result = unsafe {
+        libc::ioctl(
+            socket.as_raw_fd(),
+            HCIGETDEVINFO as _,
+            &mut info as *mut HciDevInfo,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let stat = &info.stat;
+    Ok(PacketStats {
+        received: u64::from(stat.acl_rx) + u64::from(stat.sco_rx),
+        sent: u64::from(stat.acl_tx) + u64::from(stat.sco_tx),
+        errors: u64::from(stat.err_rx) + u64::from(stat.err_tx),
+    })
+}
+
+fn hci_socket() -> io::Result<OwnedFd> {
+    // SAFETY: a plain syscall, the descriptor is owned once it's valid
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_BLUETOOTH,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            BTPROTO_HCI,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor was just opened and isn't owned elsewhere
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn set_receive_timeout(socket: &OwnedFd, duration: Duration) -> io::Result<()> {
+    let timeout = libc::timeval {
+        tv_sec: duration.as_secs() as libc::time_t,
+        tv_usec: duration.subsec_micros() as libc::suseconds_t,
+    };
+    // SAFETY: the option is a `timeval` of the given size
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            (&timeout as *const libc::timeval).cast(),
+            mem::size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The Get Connection Information command, little endian like every field of
+/// the management API.
+fn conn_info_command(index: u16, addr: Address, address_type: AddressType) -> Vec<u8> {
+    let mut command = Vec::with_capacity(MGMT_HEADER_SIZE + 7);
+    command.extend(MGMT_OP_GET_CONN_INFO.to_le_bytes());
+    command.extend(index.to_le_bytes());
+    command.extend(7u16.to_le_bytes());
+    command.extend(addr.0.iter().rev());
+    command.push(match address_type {
+        AddressType::BrEdr => 0,
+        AddressType::LePublic => 1,
+        AddressType::LeRandom => 2,
+    });
+    command
+}
+
+/// The answer to Get Connection Information for the adapter in a packet of
+/// the control channel, `None` when the packet is something else.
+fn conn_info_reply(packet: &[u8], index: u16) -> Option<io::Result<ConnectionInfo>> {
+    let u16_at = |at: usize| Some(u16::from_le_bytes(packet.get(at..at + 2)?.try_into().ok()?));
+    let event = u16_at(0)?;
+    if u16_at(2)? != index
+        || !matches!(event, MGMT_EV_CMD_COMPLETE | MGMT_EV_CMD_STATUS)
+        || u16_at(MGMT_HEADER_SIZE)? != MGMT_OP_GET_CONN_INFO
+    {
+        return None;
+    }
+
+    let status = *packet.get(MGMT_HEADER_SIZE + 2)?;
+    if status != MGMT_STATUS_SUCCESS {
+        return Some(Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("get connection information failed with status {status:#04x}"),
+        )));
+    }
+    // after the status come the address and its type, then the powers
+    let power = |at: usize| {
+        packet
+            .get(MGMT_HEADER_SIZE + 3 + at)
+            .map(|power| *power as i8)
+            .filter(|power| *power != HCI_UNKNOWN_POWER)
+    };
+    Some(Ok(ConnectionInfo {
+        rssi: power(7),
+        tx_power: power(8),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR: Address = Address([0x00, 0x1b, 0x66, 0xaa, 0xbb, 0xcc]);
+
+    /// A reply of the kernel to Get Connection Information.
+    fn reply(event: u16, index: u16, status: u8, rssi: u8, tx_power: u8) -> Vec<u8> {
+        let mut packet = Vec::new();
+        packet.extend(event.to_le_bytes());
+        packet.extend(index.to_le_bytes());
+        packet.extend(13u16.to_le_bytes());
+        packet.extend(MGMT_OP_GET_CONN_INFO.to_le_bytes());
+        packet.push(status);
+        packet.extend(ADDR.0.iter().rev());
+        packet.push(0);
+        packet.extend([rssi, tx_power, 4]);
+        packet
+    }
+
+    #[test]
+    fn adapter_indexes() {
+        assert_eq!(adapter_index("hci0"), Some(0));
+        assert_eq!(adapter_index("hci12"), Some(12));
+        assert_eq!(adapter_index("hci"), None);
+        assert_eq!(adapter_index("usb0"), None);
+    }
+
+    #[test]
+    fn command_layout() {
+        assert_eq!(
+            conn_info_command(1, ADDR, AddressType::LeRandom),
+            [0x31, 0x00, 0x01, 0x00, 0x07, 0x00, 0xcc, 0xbb, 0xaa, 0x66, 0x1b, 0x00, 0x02]
+        );
+    }
+
+    #[test]
+    fn reply_powers() {
+        let info = conn_info_reply(&reply(MGMT_EV_CMD_COMPLETE, 0, 0, -6i8 as u8, 4), 0);
+        assert_eq!(
+            info.unwrap().unwrap(),
+            ConnectionInfo {
+                rssi: Some(-6),
+                tx_power: Some(4),
+            }
+        );
+
+        let unknown = HCI_UNKNOWN_POWER as u8;
+        let info = conn_info_reply(&reply(MGMT_EV_CMD_COMPLETE, 0, 0, unknown, unknown), 0);
+        assert_eq!(
+            info.unwrap().unwrap(),
+            ConnectionInfo {
+                rssi: None,
+                tx_power: None,
+            }
+        );
+    }
+
+    #[test]
+    fn failed_reply() {
+        let packet = reply(MGMT_EV_CMD_STATUS, 0, 0x02, 0, 0);
+        assert!(conn_info_reply(&packet[..MGMT_HEADER_SIZE + 3], 0)
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn other_packets_are_skipped() {
+        assert!(conn_info_reply(&reply(MGMT_EV_CMD_COMPLETE, 1, 0, 0, 0), 0).is_none());
+        // a device connected event
+        assert!(conn_info_reply(&reply(0x000b, 0, 0, 0, 0), 0).is_none());
+        assert!(conn_info_reply(&[0x01, 0x00, 0x00], 0).is_none());
+
+        let mut packet = reply(MGMT_EV_CMD_COMPLETE, 0, 0, 0, 0);
+        packet[MGMT_HEADER_SIZE] = 0x30;
+        assert!(conn_info_reply(&packet, 0).is_none());
+    }
+}
@@ -0,0 +1,481 @@
+use crate::{
+    bluetooth::{
+        backend::BluetoothBackend,
+        battery::{BudBattery, BudsBattery},
+        AudioProfile, BluetoothEvent, BluezVersion, DeviceDetails, DeviceInfo, LinkStats,
+        NowPlaying, PairingAdvertisement,
+    },
+    error::{Error, Result},
+};
+use async_trait::async_trait;
+use bluer::{Address, Uuid};
+use std::{
+    collections::{HashMap, VecDeque},
+    iter, mem,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{self, error::TrySendError},
+        watch,
+    },
+    time::sleep,
+};
+
+// the version reported as bluetoothd, a recent one
+const MOCK_BLUEZ_VERSION: BluezVersion = BluezVersion {
+    major: 5,
+    minor: 66,
+};
+
+// the classes of device of the simulated headphones and phone
+const HEADPHONES_CLASS: u32 = 0x240418;
+const PHONE_CLASS: u32 = 0x5a020c;
+
+// the company ID of Apple, the maker of the simulated earbuds
+const APPLE: u16 = 0x004c;
+
+// the network access point the simulated phone offers
+const NAP_UUID: u128 = 0x00001116_0000_1000_8000_00805f9b34fb;
+
+// the interface a tethered phone gets
+const MOCK_INTERFACE: &str = "bnep0";
+
+// what the connected devices tell about themselves
+const MOCK_MANUFACTURER: &str = "Mock Audio";
+const MOCK_FIRMWARE: &str = "1.0.0";
+
+// the volume of a device until it's set
+const DEFAULT_VOLUME: u8 = 50;
+
+/// In-memory backend with a fixed set of devices, for exercising the connect
+/// flow and its statuses without Bluetooth hardware.
+pub struct MockBackend {
+    powered: AtomicBool,
+    discovering: AtomicBool,
+    devices: Mutex<Vec<DeviceInfo>>,
+    /// Removed devices still in range, found again by the next scan.
+    removed: Mutex<Vec<DeviceInfo>>,
+    /// Reasons the connect of a device fails with.
+    connect_failures: HashMap<Address, String>,
+    /// Errors the next connects of a device fail with, in order.
+    connect_failures_left: Mutex<HashMap<Address, VecDeque<Error>>>,
+    /// PINs of the legacy devices.
+    pins: HashMap<Address, String>,
+    operation_delay: Duration,
+    volumes: Mutex<HashMap<Address, u8>>,
+    event_txs: Mutex<Vec<mpsc::Sender<BluetoothEvent>>>,
+    adapter_tx: watch::Sender<bool>,
+}
+
+impl MockBackend {
+    pub fn new(devices: Vec<DeviceInfo>) -> Self {
+        Self {
+            powered: AtomicBool::new(true),
+            discovering: AtomicBool::new(false),
+            devices: Mutex::new(devices),
+            removed: Mutex::new(Vec::new()),
+            connect_failures: HashMap::new(),
+            connect_failures_left: Mutex::new(HashMap::new()),
+            pins: HashMap::new(),
+            operation_delay: Duration::ZERO,
+            volumes: Mutex::new(HashMap::new()),
+            event_txs: Mutex::new(Vec::new()),
+            adapter_tx: watch::channel(true).0,
+        }
+    }
+
+    /// A scripted environment for developing the UI on a desktop: a few
+    /// headsets and unnamed devices, a phone, slow operations, a device which
+    /// can't be connected and one with a PIN none of the defaults match.
+    pub fn simulation() -> Self {
+        let device = |last: u8, name: &str, paired: bool| DeviceInfo {
+            addr: Address::new([0x00, 0x11, 0x22, 0x33, 0x44, last]),
+            name: name.to_string(),
+            paired,
+            connected: false,
+            rssi: Some(-40 - i16::from(last) * 6),
+            tx_power: None,
+            battery: paired.then_some(80),
+            class: (!name.is_empty()).then_some(HEADPHONES_CLASS),
+            uuids: Vec::new(),
+            vendor: None,
+            buds_battery: None,
+            pairing_advertisement: None,
+        };
+        let phone = DeviceInfo {
+            battery: None,
+            class: Some(PHONE_CLASS),
+            uuids: vec![Uuid::from_u128(NAP_UUID)],
+            ..device(0x08, "Pixel 7", true)
+        };
+
+        let airpods = DeviceInfo {
+            vendor: Some(APPLE),
+            buds_battery: Some((
+                APPLE,
+                BudsBattery {
+                    left: Some(BudBattery {
+                        level: 70,
+                        charging: false,
+                    }),
+                    right: Some(BudBattery {
+                        level: 60,
+                        charging: false,
+                    }),
+                    case: None,
+                },
+            )),
+            ..device(0x07, "AirPods Pro", false)
+        };
+
+        let devices = vec![
+            device(0x01, "SoundCore Q30", true),
+            DeviceInfo {
+                pairing_advertisement: Some(PairingAdvertisement::FastPair),
+                ..device(0x02, "WH-1000XM4", false)
+            },
+            device(0x03, "", false),
+            device(0x04, "Living Room TV Speaker With A Very Long Name", false),
+            device(0x05, "JBL Flip 5", true),
+            device(0x06, "", false),
+            airpods,
+            phone,
+        ];
+
+        Self::new(devices)
+            .fail_connect(
+                Address::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x05]),
+                "br-connection-page-timeout",
+            )
+            .require_pin(Address::new([0x00, 0x11, 0x22, 0x33, 0x44, 0x04]), "4321")
+            .operation_delay(Duration::from_millis(1500))
+    }
+
+    /// Make connecting the device always fail.
+    pub fn fail_connect(mut self, addr: Address, reason: impl Into<String>) -> Self {
+        self.connect_failures.insert(addr, reason.into());
+        self
+    }
+
+    /// Make connecting the device fail the next `times`, like a headset still
+    /// waking up or one which forgot its pairing.
+    pub fn fail_connect_times(
+        self, addr: Address, reason: impl Into<String>, times: usize,
+    ) -> Self {
+        let error = Error::bluez(reason);
+        self.connect_failures_left
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_default()
+            .extend(iter::repeat(error).take(times));
+        self
+    }
+
+    /// Make the next connect of the device after the failing ones fail with
+    /// the error, like `InProgress` from BlueZ right after a pairing.
+    pub fn then_fail_connect(self, addr: Address, error: Error) -> Self {
+        self.connect_failures_left
+            .lock()
+            .unwrap()
+            .entry(addr)
+            .or_default()
+            .push_back(error);
+        self
+    }
+
+    /// Make the device ask for a PIN when pairing.
+    pub fn require_pin(mut self, addr: Address, pin: impl Into<String>) -> Self {
+        self.pins.insert(addr, pin.into());
+        self
+    }
+
+    /// How long pairing, connecting and disconnecting take.
+    pub fn operation_delay(mut self, delay: Duration) -> Self {
+        self.operation_delay = delay;
+        self
+    }
+
+    pub fn powered(self, powered: bool) -> Self {
+        self.powered.store(powered, Ordering::SeqCst);
+        self
+    }
+
+    /// Unplug the adapter or plug it in again.
+    pub fn set_adapter_present(&self, present: bool) {
+        self.adapter_tx.send_replace(present);
+    }
+
+    fn update(&self, addr: Address, f: impl FnOnce(&mut DeviceInfo)) -> Result<()> {
+        let mut devices = self.devices.lock().unwrap();
+        let info = devices
+            .iter_mut()
+            .find(|info| info.addr == addr)
+            .ok_or(Error::DeviceNotFound(addr))?;
+        f(info);
+        Ok(())
+    }
+
+    /// Send the event to every subscriber still listening.
+    fn emit(&self, event: BluetoothEvent) {
+        self.event_txs.lock().unwrap().retain(|event_tx| {
+            !matches!(
+                event_tx.try_send(event.clone()),
+                Err(TrySendError::Closed(_))
+            )
+        });
+    }
+
+    fn ensure_adapter(&self) -> Result<()> {
+        if !*self.adapter_tx.borrow() {
+            return Err(Error::AdapterMissing);
+        }
+        Ok(())
+    }
+
+    fn ensure_powered(&self) -> Result<()> {
+        self.ensure_adapter()?;
+        if !self.powered.load(Ordering::SeqCst) {
+            return Err(Error::PoweredOff);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BluetoothBackend for MockBackend {
+    async fn is_powered(&self) -> Result<bool> {
+        self.ensure_adapter()?;
+        Ok(self.powered.load(Ordering::SeqCst))
+    }
+
+    async fn set_powered(&self, powered: bool) -> Result<()> {
+        self.powered.store(powered, Ordering::SeqCst);
+        if !powered {
+            let mut disconnected = Vec::new();
+            for info in self.devices.lock().unwrap().iter_mut() {
+                if info.connected {
+                    info.connected = false;
+                    disconnected.push(info.addr);
+                }
+            }
+            for addr in disconnected {
+                self.emit(BluetoothEvent::Disconnected(addr));
+            }
+        }
+        Ok(())
+    }
+
+    async fn bluez_version(&self) -> Result<Option<BluezVersion>> {
+        self.ensure_adapter()?;
+        Ok(Some(MOCK_BLUEZ_VERSION))
+    }
+
+    async fn scan(&self, duration: Duration) -> Result<Vec<DeviceInfo>> {
+        self.ensure_powered()?;
+        let _discovery = Discovery::start(&self.discovering);
+        sleep(duration).await;
+        let found = mem::take(&mut *self.removed.lock().unwrap());
+        let devices = {
+            let mut devices = self.devices.lock().unwrap();
+            devices.extend(found);
+            devices.clone()
+        };
+        for info in &devices {
+            self.emit(BluetoothEvent::DeviceFound(info.clone()));
+        }
+        Ok(devices)
+    }
+
+    async fn is_discovering(&self) -> Result<bool> {
+        self.ensure_adapter()?;
+        Ok(self.discovering.load(Ordering::SeqCst))
+    }
+
+    async fn known_devices(&self) -> Result<Vec<DeviceInfo>> {
+        Ok(self.devices.lock().unwrap().clone())
+    }
+
+    async fn properties(&self, addr: Address) -> Result<DeviceInfo> {
+        self.devices
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|info| info.addr == addr)
+            .cloned()
+            .ok_or(Error::DeviceNotFound(addr))
+    }
+
+    async fn details(&self, addr: Address) -> Result<DeviceDetails> {
+        let info = self.properties(addr).await?;
+        if !info.connected {
+            return Ok(DeviceDetails {
+                addr,
+                ..DeviceDetails::default()
+            });
+        }
+        Ok(DeviceDetails {
+            addr,
+            manufacturer: Some(MOCK_MANUFACTURER.to_string()),
+            model: Some(info.name),
+            firmware: Some(MOCK_FIRMWARE.to_string()),
+            hardware: None,
+        })
+    }
+
+    async fn pair(&self, addr: Address, pin: &str) -> Result<bool> {
+        self.ensure_powered()?;
+        sleep(self.operation_delay).await;
+        let asked = match self.pins.get(&addr) {
+            Some(expected) if expected != pin => return Err(Error::WrongPin),
+            Some(_) => true,
+            None => false,
+        };
+        self.update(addr, |info| info.paired = true)?;
+        Ok(asked)
+    }
+
+    async fn cancel_pairing(&self, addr: Address) -> Result<()> {
+        self.properties(addr).await.map(drop)
+    }
+
+    async fn connect(&self, addr: Address) -> Result<()> {
+        self.ensure_powered()?;
+        sleep(self.operation_delay).await;
+        if let Some(reason) = self.connect_failures.get(&addr) {
+            return Err(Error::bluez(reason.clone()));
+        }
+        let failure = self
+            .connect_failures_left
+            .lock()
+            .unwrap()
+            .get_mut(&addr)
+            .and_then(VecDeque::pop_front);
+        if let Some(error) = failure {
+            return Err(error);
+        }
+        self.update(addr, |info| info.connected = true)?;
+        self.emit(BluetoothEvent::Connected(addr));
+        self.emit(BluetoothEvent::MediaChanged(addr));
+        Ok(())
+    }
+
+    async fn connect_profile(&self, addr: Address, _profile: AudioProfile) -> Result<()> {
+        self.connect(addr).await
+    }
+
+    async fn set_trusted(&self, addr: Address, _trusted: bool) -> Result<()> {
+        self.properties(addr).await.map(drop)
+    }
+
+    async fn has_audio(&self, addr: Address) -> Result<bool> {
+        Ok(self.properties(addr).await?.connected)
+    }
+
+    async fn link_stats(&self, addr: Address) -> Result<LinkStats> {
+        let info = self.properties(addr).await?;
+        if !info.connected {
+            return Err(Error::AudioRoutingFailed);
+        }
+        // the RSSI of the connection is under the range from -65 dBm on
+        Ok(LinkStats {
+            rssi: info.rssi.map(|rssi| (rssi + 65).clamp(-128, 0) as i8),
+            tx_power: info
+                .tx_power
+                .map(|tx_power| tx_power.clamp(-128, 127) as i8),
+            packets: None,
+        })
+    }
+
+    async fn volume(&self, addr: Address) -> Result<u8> {
+        if !self.properties(addr).await?.connected {
+            return Err(Error::AudioRoutingFailed);
+        }
+        let volumes = self.volumes.lock().unwrap();
+        Ok(volumes.get(&addr).copied().unwrap_or(DEFAULT_VOLUME))
+    }
+
+    async fn now_playing(&self, addr: Address) -> Result<Option<NowPlaying>> {
+        if !self.properties(addr).await?.connected {
+            return Err(Error::AudioRoutingFailed);
+        }
+        Ok(None)
+    }
+
+    async fn set_volume(&self, addr: Address, volume: u8) -> Result<()> {
+        if !self.properties(addr).await?.connected {
+            return Err(Error::AudioRoutingFailed);
+        }
+        self.volumes.lock().unwrap().insert(addr, volume.min(100));
+        Ok(())
+    }
+
+    async fn disconnect(&self, addr: Address) -> Result<()> {
+        self.ensure_powered()?;
+        sleep(self.operation_delay).await;
+        self.update(addr, |info| info.connected = false)?;
+        self.emit(BluetoothEvent::Disconnected(addr));
+        self.emit(BluetoothEvent::MediaChanged(addr));
+        Ok(())
+    }
+
+    async fn connect_network(&self, addr: Address) -> Result<String> {
+        if !self.properties(addr).await?.offers_tethering() {
+            return Err(Error::bluez("org.bluez.Error.NotSupported"));
+        }
+        self.connect(addr).await?;
+        Ok(MOCK_INTERFACE.to_string())
+    }
+
+    async fn disconnect_network(&self, addr: Address) -> Result<()> {
+        self.properties(addr).await.map(drop)
+    }
+
+    async fn remove(&self, addr: Address) -> Result<()> {
+        self.ensure_powered()?;
+        let mut devices = self.devices.lock().unwrap();
+        if let Some(index) = devices.iter().position(|info| info.addr == addr) {
+            let info = devices.remove(index);
+            self.removed.lock().unwrap().push(DeviceInfo {
+                paired: false,
+                connected: false,
+                ..info
+            });
+        }
+        drop(devices);
+        self.emit(BluetoothEvent::DeviceRemoved(addr));
+        Ok(())
+    }
+
+    async fn events(&self) -> Result<mpsc::Receiver<BluetoothEvent>> {
+        let (event_tx, event_rx) = mpsc::channel(64);
+        self.event_txs.lock().unwrap().push(event_tx);
+        Ok(event_rx)
+    }
+
+    fn adapter_present(&self) -> watch::Receiver<bool> {
+        self.adapter_tx.subscribe()
+    }
+}
+
+/// Marks the adapter discovering until dropped, like the discovery session of
+/// BlueZ.
+struct Discovery<'a>(&'a AtomicBool);
+
+impl<'a> Discovery<'a> {
+    fn start(discovering: &'a AtomicBool) -> Self {
+        discovering.store(true, Ordering::SeqCst);
+        Self(discovering)
+    }
+}
+
+impl Drop for Discovery<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{
+    bluetooth::{
+        AudioProfile, BluetoothEvent, BluezVersion, DeviceDetails, DeviceInfo, LinkStats,
+        NowPlaying,
+    },
+    error::Result,
+};
+use async_trait::async_trait;
+use bluer::Address;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+
+pub mod bluez;
+mod mgmt;
+pub mod mock;
+
+/// The Bluetooth operations the connector needs, implemented by BlueZ on the
+/// device and by a mock for tests and UI development.
+#[async_trait]
+pub trait BluetoothBackend: Send + Sync {
+    async fn is_powered(&self) -> Result<bool>;
+
+    async fn set_powered(&self, powered: bool) -> Result<()>;
+
+    /// The version of bluetoothd, `None` when the adapter doesn't tell, like
+    /// with a `DeviceID` set in `main.conf`.
+    async fn bluez_version(&self) -> Result<Option<BluezVersion>>;
+
+    /// Discover devices for `duration`, devices which vanish during the scan
+    /// are dropped from the result.
+    async fn scan(&self, duration: Duration) -> Result<Vec<DeviceInfo>>;
+
+    /// Whether the adapter is discovering, for this or another program.
+    async fn is_discovering(&self) -> Result<bool>;
+
+    /// The devices bluetoothd already knows, without discovering.
+    async fn known_devices(&self) -> Result<Vec<DeviceInfo>>;
+
+    /// Current properties of a known device.
+    async fn properties(&self, addr: Address) -> Result<DeviceInfo>;
+
+    /// The maker, model and versions of a known device, read from the device
+    /// while it's connected.
+    async fn details(&self, addr: Address) -> Result<DeviceDetails>;
+
+    /// Pair the device, answering with `pin` if it asks for a PIN or passkey.
+    /// Returns whether it asked, a refused PIN is `Error::WrongPin`.
+    async fn pair(&self, addr: Address, pin: &str) -> Result<bool>;
+
+    /// Stop a pairing in progress.
+    async fn cancel_pairing(&self, addr: Address) -> Result<()>;
+
+    async fn connect(&self, addr: Address) -> Result<()>;
+
+    /// Connect only the given profile of the device.
+    async fn connect_profile(&self, addr: Address, profile: AudioProfile) -> Result<()>;
+
+    async fn set_trusted(&self, addr: Address, trusted: bool) -> Result<()>;
+
+    /// Whether a connected device has an audio stream, it may drop it while
+    /// staying connected.
+    async fn has_audio(&self, addr: Address) -> Result<bool>;
+
+    /// The link of a connected device, as its controller tells it now.
+    async fn link_stats(&self, addr: Address) -> Result<LinkStats>;
+
+    /// The volume of the audio stream of a connected device, in percent.
+    async fn volume(&self, addr: Address) -> Result<u8>;
+
+    /// The track of the media player of a connected device, `None` when it
+    /// has no player.
+    async fn now_playing(&self, addr: Address) -> Result<Option<NowPlaying>>;
+
+    /// Set the volume of the audio stream of a connected device, in percent.
+    async fn set_volume(&self, addr: Address, volume: u8) -> Result<()>;
+
+    async fn disconnect(&self, addr: Address) -> Result<()>;
+
+    /// Connect the network access point of a paired phone and bring up the
+    /// interface it gets, with an address from the phone. Returns the name of
+    /// the interface.
+    async fn connect_network(&self, addr: Address) -> Result<String>;
+
+    /// Take down the network of the phone, which stays connected, and undo
+    /// what bringing it up changed on the handheld. Also called once the phone
+    /// disconnected, to undo the changes only.
+    async fn disconnect_network(&self, addr: Address) -> Result<()>;
+
+    /// Remove the pairing, which also disconnects the device.
+    async fn remove(&self, addr: Address) -> Result<()>;
+
+    /// Subscribe to the changes of the adapter and its devices, until the
+    /// receiver is dropped.
+    async fn events(&self) -> Result<mpsc::Receiver<BluetoothEvent>>;
+
+    /// Whether there is an adapter, a USB dongle may be unplugged and plugged
+    /// in again while running. Every other call fails with
+    /// `Error::AdapterMissing` while there is none.
+    fn adapter_present(&self) -> watch::Receiver<bool>;
+}
@@ -0,0 +1,228 @@
+use crate::bluetooth::DeviceInfo;
+use std::collections::HashMap;
+
+// the company ID of Apple in the manufacturer data
+const APPLE: u16 = 0x004c;
+
+// the proximity pairing message of AirPods and Beats, and its length
+const APPLE_PROXIMITY_PAIRING: u8 = 0x07;
+const APPLE_PROXIMITY_PAIRING_LEN: u8 = 0x19;
+
+// a level nibble above 10 tells the part isn't there, like a bud in the ear
+// of someone else or a case out of reach
+const APPLE_MAX_LEVEL: u8 = 10;
+
+// earbuds advertising from another address count as the connected ones only
+// this close, in dBm, other people nearby may have the same ones
+const NEARBY_RSSI: i16 = -60;
+
+/// The battery of one bud or of the case.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BudBattery {
+    /// Percent.
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// The batteries of true wireless earbuds and their case, told by a vendor
+/// protocol. A part is `None` when the earbuds don't tell it, like the case
+/// while both buds are out of it.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct BudsBattery {
+    pub left: Option<BudBattery>,
+    pub right: Option<BudBattery>,
+    pub case: Option<BudBattery>,
+}
+
+/// Reads the batteries of earbuds from the manufacturer data a vendor puts in
+/// their advertisements. Another vendor is supported by adding its provider
+/// to `PROVIDERS`. Vendors telling them only over a protocol of their app, like
+/// Samsung and Xiaomi, don't fit here.
+trait BatteryProvider: Sync {
+    /// The company ID the manufacturer data is under.
+    fn company_id(&self) -> u16;
+
+    /// Whether the earbuds advertise from a random address instead of the one
+    /// they're connected with. Their batteries go to the connected earbuds of
+    /// the vendor when they're close.
+    fn random_address(&self) -> bool;
+
+    fn parse(&self, data: &[u8]) -> Option<BudsBattery>;
+}
+
+static PROVIDERS: [&dyn BatteryProvider; 1] = [&AppleProvider];
+
+/// The proximity pairing message of AirPods and Beats.
+struct AppleProvider;
+
+impl BatteryProvider for AppleProvider {
+    fn company_id(&self) -> u16 {
+        APPLE
+    }
+
+    fn random_address(&self) -> bool {
+        true
+    }
+
+    fn parse(&self, data: &[u8]) -> Option<BudsBattery> {
+        if data.len() < usize::from(APPLE_PROXIMITY_PAIRING_LEN) + 2
+            || data[0] != APPLE_PROXIMITY_PAIRING
+            || data[1] != APPLE_PROXIMITY_PAIRING_LEN
+        {
+            return None;
+        }
+
+        // the buds swap their nibbles depending on which one sends
+        let flipped = data[5] & 0x20 == 0;
+        let (first, second) = (data[6] >> 4, data[6] & 0x0f);
+        let (case, charging) = (data[7] & 0x0f, data[7] >> 4);
+        let bud = |level: u8, charging_bit: u8| {
+            (level <= APPLE_MAX_LEVEL).then_some(BudBattery {
+                level: level * 10,
+                charging: charging & charging_bit != 0,
+            })
+        };
+
+        let (left, right) = if flipped {
+            (bud(first, 0b010), bud(second, 0b001))
+        } else {
+            (bud(second, 0b001), bud(first, 0b010))
+        };
+        Some(BudsBattery {
+            left,
+            right,
+            case: bud(case, 0b100),
+        })
+    }
+}
+
+/// The batteries in the manufacturer data of a device, by the first provider
+/// which understands it, with the company ID of its vendor.
+pub fn buds_battery(manufacturer_data: &HashMap<u16, Vec<u8>>) -> Option<(u16, BudsBattery)> {
+    PROVIDERS.iter().find_map(|provider| {
+        let data = manufacturer_data.get(&provider.company_id())?;
+        let battery = provider.parse(data)?;
+        Some((provider.company_id(), battery))
+    })
+}
+
+/// Give the connected earbuds without batteries the ones advertised by the
+/// closest earbuds of their vendor from a random address.
+pub fn attach_nearby(devices: &mut [DeviceInfo]) {
+    let random_address = |vendor: u16| {
+        PROVIDERS
+            .iter()
+            .any(|provider| provider.company_id() == vendor && provider.random_address())
+    };
+    let closest = |vendor: u16| {
+        devices
+            .iter()
+            .filter(|info| !info.connected && info.rssi.is_some_and(|rssi| rssi >= NEARBY_RSSI))
+            .filter_map(|info| match info.buds_battery {
+                Some((advertised, battery)) if advertised == vendor => Some((info.rssi, battery)),
+                _ => None,
+            })
+            .max_by_key(|(rssi, _)| *rssi)
+            .map(|(_, battery)| battery)
+    };
+
+    let attached = devices
+        .iter()
+        .enumerate()
+        .filter(|(_, info)| info.connected && info.buds_battery.is_none())
+        .filter_map(|(index, info)| {
+            let vendor = info.vendor.filter(|vendor| random_address(*vendor))?;
+            Some((index, vendor, closest(vendor)?))
+        })
+        .collect::<Vec<_>>();
+    for (index, vendor, battery) in attached {
+        devices[index].buds_battery = Some((vendor, battery));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A proximity pairing message with the level and charging bytes.
+    fn apple_data(flags: u8, levels: u8, charging: u8) -> HashMap<u16, Vec<u8>> {
+        let mut data = vec![0; usize::from(APPLE_PROXIMITY_PAIRING_LEN) + 2];
+        data[0] = APPLE_PROXIMITY_PAIRING;
+        data[1] = APPLE_PROXIMITY_PAIRING_LEN;
+        data[5] = flags;
+        data[6] = levels;
+        data[7] = charging;
+        HashMap::from([(APPLE, data)])
+    }
+
+    fn bud(level: u8, charging: bool) -> Option<BudBattery> {
+        Some(BudBattery { level, charging })
+    }
+
+    #[test]
+    fn apple_batteries() {
+        let (vendor, battery) = buds_battery(&apple_data(0x20, 0x78, 0x15)).unwrap();
+        assert_eq!(vendor, APPLE);
+        assert_eq!(
+            battery,
+            BudsBattery {
+                left: bud(80, true),
+                right: bud(70, false),
+                case: bud(50, false),
+            }
+        );
+    }
+
+    #[test]
+    fn apple_batteries_of_the_other_bud() {
+        let (_, battery) = buds_battery(&apple_data(0x00, 0x78, 0x45)).unwrap();
+        assert_eq!(battery.left, bud(70, false));
+        assert_eq!(battery.right, bud(80, false));
+        assert_eq!(battery.case, bud(50, true));
+    }
+
+    #[test]
+    fn missing_parts_have_no_battery() {
+        let (_, battery) = buds_battery(&apple_data(0x20, 0xf8, 0x0f)).unwrap();
+        assert_eq!(battery.left, bud(80, false));
+        assert_eq!(battery.right, None);
+        assert_eq!(battery.case, None);
+    }
+
+    #[test]
+    fn other_messages_are_ignored() {
+        let mut short = apple_data(0x20, 0x78, 0x15);
+        short.get_mut(&APPLE).unwrap().truncate(10);
+        assert_eq!(buds_battery(&short), None);
+
+        let mut other = apple_data(0x20, 0x78, 0x15);
+        other.get_mut(&APPLE).unwrap()[0] = 0x10;
+        assert_eq!(buds_battery(&other), None);
+
+        let data = apple_data(0x20, 0x78, 0x15).remove(&APPLE).unwrap();
+        assert_eq!(buds_battery(&HashMap::from([(0x0006, data)])), None);
+    }
+
+    #[test]
+    fn nearby_batteries_go_to_connected_buds() {
+        let (vendor, battery) = buds_battery(&apple_data(0x20, 0x78, 0x15)).unwrap();
+        let advertising = |rssi| DeviceInfo {
+            rssi: Some(rssi),
+            buds_battery: Some((vendor, battery)),
+            ..DeviceInfo::default()
+        };
+        let connected = DeviceInfo {
+            connected: true,
+            vendor: Some(APPLE),
+            ..DeviceInfo::default()
+        };
+
+        let mut devices = [connected.clone(), advertising(NEARBY_RSSI)];
+        attach_nearby(&mut devices);
+        assert_eq!(devices[0].buds_battery, Some((APPLE, battery)));
+
+        let mut devices = [connected, advertising(NEARBY_RSSI - 1)];
+        attach_nearby(&mut devices);
+        assert_eq!(devices[0].buds_battery, None);
+    }
+}
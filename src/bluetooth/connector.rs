@@ -0,0 +1,168 @@
+use crate::{
+    bluetooth::{backend::BluetoothBackend, AudioProfile, ConnectStage},
+    error::{Error, Result},
+};
+use bluer::Address;
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::time::{sleep, Instant};
+use tracing::{debug, warn};
+
+/// Tried in turn on legacy devices asking for a PIN, after the remembered one.
+pub const DEFAULT_PINS: [&str; 4] = ["0000", "1234", "1111", "8888"];
+
+// how long the device is looked for after removing it, BlueZ forgets a removed
+// device until it's discovered again
+const REDISCOVER_DURATION: Duration = Duration::from_secs(5);
+
+// how long pairing waits for the discovery to stop, and how often it looks
+const DISCOVERY_STOP_TIMEOUT: Duration = Duration::from_secs(3);
+const DISCOVERY_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// how often a call failing with a transient error is made again, and the wait
+// before each time
+const TRANSIENT_RETRIES: u32 = 2;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Pairs, connects and disconnects devices of the adapter.
+#[derive(Clone)]
+pub struct Connector {
+    backend: Arc<dyn BluetoothBackend>,
+}
+
+impl Connector {
+    pub fn new(backend: Arc<dyn BluetoothBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// Wait until the adapter stops discovering, cheap adapters often fail to
+    /// pair or connect during a discovery. A dropped scan stops its discovery
+    /// a moment later, another program may keep discovering, so the connect
+    /// goes on anyway after a while.
+    pub async fn wait_discovery_stopped(&self) {
+        let deadline = Instant::now() + DISCOVERY_STOP_TIMEOUT;
+        loop {
+            match self.backend.is_discovering().await {
+                Ok(false) => return,
+                Ok(true) if Instant::now() >= deadline => {
+                    warn!("adapter still discovering, maybe for another program, connect anyway");
+                    return;
+                }
+                Ok(true) => sleep(DISCOVERY_POLL_INTERVAL).await,
+                Err(err) => {
+                    debug!(?err, "get discovering failed");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Pair the device if it isn't paired yet. A legacy device asking for a PIN
+    /// gets `remembered` and then the `DEFAULT_PINS`, the one it accepted is
+    /// returned to be remembered.
+    pub async fn pair(&self, addr: Address, remembered: Option<&str>) -> Result<Option<String>> {
+        if self.backend.properties(addr).await?.paired {
+            return Ok(None);
+        }
+
+        let defaults = DEFAULT_PINS
+            .into_iter()
+            .filter(|pin| Some(*pin) != remembered);
+        for pin in remembered.into_iter().chain(defaults) {
+            match retry_transient(addr, || self.backend.pair(addr, pin)).await {
+                Ok(asked) => return Ok(asked.then(|| pin.to_string())),
+                Err(Error::WrongPin) => debug!(%addr, pin, "pin refused, try the next one"),
+                Err(err) => return Err(err),
+            }
+        }
+        Err(Error::PinRequired(addr))
+    }
+
+    /// Remove a device which lost its pairing, find it again and pair it, like
+    /// `pair`.
+    pub async fn repair(&self, addr: Address, remembered: Option<&str>) -> Result<Option<String>> {
+        self.backend.remove(addr).await?;
+
+        let found = self.backend.scan(REDISCOVER_DURATION).await?;
+        if !found.iter().any(|info| info.addr == addr) {
+            return Err(Error::DeviceNotFound(addr));
+        }
+        self.wait_discovery_stopped().await;
+
+        self.pair(addr, remembered).await
+    }
+
+    /// Connect the device if it isn't connected yet.
+    pub async fn connect(&self, addr: Address, profile: AudioProfile) -> Result<()> {
+        if self.backend.properties(addr).await?.connected {
+            return Ok(());
+        }
+        retry_transient(addr, || async move {
+            match profile {
+                AudioProfile::A2dp => self.backend.connect(addr).await,
+                profile => self.backend.connect_profile(addr, profile).await,
+            }
+        })
+        .await
+    }
+
+    pub async fn trust(&self, addr: Address) -> Result<()> {
+        self.backend.set_trusted(addr, true).await
+    }
+
+    pub async fn volume(&self, addr: Address) -> Result<u8> {
+        self.backend.volume(addr).await
+    }
+
+    pub async fn set_volume(&self, addr: Address, volume: u8) -> Result<()> {
+        self.backend.set_volume(addr, volume).await
+    }
+
+    /// Check the device is still connected, some headsets accept the
+    /// connection and drop it right away when the audio profile fails.
+    pub async fn verify(&self, addr: Address) -> Result<()> {
+        if !self.backend.properties(addr).await?.connected {
+            return Err(Error::AudioRoutingFailed);
+        }
+        Ok(())
+    }
+
+    /// Stop what BlueZ still does for a connect whose call was dropped at
+    /// `stage`.
+    pub async fn cancel(&self, addr: Address, stage: ConnectStage) -> Result<()> {
+        match stage {
+            ConnectStage::DisconnectingPrevious => Ok(()),
+            ConnectStage::Pairing => self.backend.cancel_pairing(addr).await,
+            ConnectStage::Connecting | ConnectStage::Repairing | ConnectStage::VerifyingAudio => {
+                self.backend.disconnect(addr).await
+            }
+        }
+    }
+
+    pub async fn disconnect(&self, addr: Address) -> Result<()> {
+        self.backend.disconnect(addr).await
+    }
+
+    /// Remove the pairing, which also disconnects the device.
+    pub async fn forget(&self, addr: Address) -> Result<()> {
+        self.backend.remove(addr).await
+    }
+}
+
+/// Make the call again after a short wait while it fails with a transient
+/// error, the last error is returned once the retries are used up.
+async fn retry_transient<T, F>(addr: Address, mut call: impl FnMut() -> F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let mut retries = 0;
+    loop {
+        match call().await {
+            Err(err) if err.is_transient() && retries < TRANSIENT_RETRIES => {
+                retries += 1;
+                debug!(?err, %addr, retries, "transient error, retry");
+                sleep(RETRY_DELAY).await;
+            }
+            result => return result,
+        }
+    }
+}
@@ -0,0 +1,1513 @@
+use crate::{
+    bluetooth::{
+        backend::BluetoothBackend,
+        connector::Connector,
+        scanner::{Scanner, DEFAULT_SCAN_DURATION},
+        BluetoothConnectState, BluetoothEvent, BluetoothScanStatus, BluezVersion, ConnectStage,
+        DeviceInfo, DeviceSettings, LinkQuality, LinkStats, TetherState,
+    },
+    error::{Error, Result},
+    state::AppState,
+};
+use bluer::Address;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::{self, Future},
+    mem,
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot, watch, Mutex},
+    task::JoinHandle,
+    time::{interval, sleep, Instant, MissedTickBehavior},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+// how often the signal of the connected device is read
+const LINK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// the link reads kept for the history of the connected device, five minutes
+const LINK_HISTORY: usize = 60;
+
+// how often the position of a playing track is read, the track and the audio
+// follow the events
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
+// how long a connect waits for another operation of BlueZ to finish, and how
+// often it tries again meanwhile
+const BUSY_TIMEOUT: Duration = Duration::from_secs(15);
+const BUSY_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long the callers of `Command::ConnectAndReply` wait for it, a scan, the
+/// waits for a busy BlueZ and a pairing made again fit in it.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(120);
+
+// the pause before a panicked manager is started again, so a panic at start
+// doesn't spin
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Requests handled by the `BluetoothManager`, one at a time in order. While a
+/// connect runs, other connects are rejected and the rest wait, with repeated
+/// requests dropped.
+#[derive(Debug)]
+pub enum Command {
+    Scan,
+    /// Scan without showing it, adding what is found to the list. Stopped by
+    /// the next command.
+    BackgroundScan,
+    Connect(Address),
+    /// Stop the running connect, ignored when there is none.
+    CancelConnect,
+    /// Stop the running scan, keeping the devices found before it. Ignored
+    /// when there is none.
+    StopScan,
+    /// Power on and connect the device, unless it's connected already. Used to
+    /// keep a device connected without disturbing a working connection.
+    EnsureConnected(Address),
+    /// Disconnect every connected device.
+    Disconnect,
+    PowerOn,
+    PowerOff,
+    /// Remove the pairing of the device.
+    Forget(Address),
+    /// Change the volume of the connected device by this many percent.
+    ChangeVolume(i8),
+    /// Use the internet of the paired phone, instead of the one used now.
+    Tether(Address),
+    /// Stop using the internet of the phone, which stays connected.
+    Untether,
+    /// Read the maker, model and firmware of the device into the state.
+    ReadDetails(Address),
+    /// Read the adapter and connection again after the system woke up, the
+    /// state may be stale.
+    Revalidate,
+    /// Apply changed options, from the next scan or exit on.
+    SetOptions(ManagerOptions),
+    /// Answered once the commands before it are handled, to check the manager
+    /// isn't stuck.
+    Ping(oneshot::Sender<()>),
+    /// `Connect`, or `EnsureConnected` with `ensure`, answering how it ended.
+    /// Rejected for another connect with `Error::InProgress`, a cancelled one
+    /// is left unanswered.
+    ConnectAndReply {
+        addr: Address,
+        ensure: bool,
+        reply_tx: oneshot::Sender<Result<()>>,
+    },
+}
+
+impl Command {
+    /// Answer a command waiting for its outcome with `err`, for one which
+    /// isn't run.
+    fn reject(self, err: Error) {
+        if let Command::ConnectAndReply { reply_tx, .. } = self {
+            let _ = reply_tx.send(Err(err));
+        }
+    }
+}
+
+/// What to do with the connection when the manager shuts down.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExitPolicy {
+    /// Leave the device connected, so the launcher keeps playing through it.
+    #[default]
+    KeepConnected,
+    Disconnect,
+    /// Turn Bluetooth off, which also disconnects.
+    PowerOff,
+}
+
+/// Behavior of the `BluetoothManager` that isn't driven by commands.
+#[derive(Debug, Clone)]
+pub struct ManagerOptions {
+    pub scan_duration: Duration,
+    pub exit_policy: ExitPolicy,
+    /// Power on and connect the device at start, before the first scan. Only
+    /// read at start.
+    pub auto_connect: Option<Address>,
+    /// Applied when connecting each device, the defaults for the others.
+    pub device_settings: HashMap<Address, DeviceSettings>,
+    /// Left out of the scan results unless connected.
+    pub hidden: HashSet<Address>,
+    /// Leave the devices without a name out of the scan results, unless
+    /// they're paired or connected. Most of them are BLE beacons and the like.
+    pub hide_unnamed: bool,
+    /// Connect the device again when it was lost while the system slept.
+    pub reconnect_on_resume: bool,
+    /// Scan at start and when an adapter is plugged in, for a frontend showing
+    /// the devices.
+    pub scan_at_start: bool,
+}
+
+impl Default for ManagerOptions {
+    fn default() -> Self {
+        Self {
+            scan_duration: DEFAULT_SCAN_DURATION,
+            exit_policy: ExitPolicy::default(),
+            auto_connect: None,
+            device_settings: HashMap::new(),
+            hidden: HashSet::new(),
+            hide_unnamed: true,
+            reconnect_on_resume: true,
+            scan_at_start: true,
+        }
+    }
+}
+
+impl ManagerOptions {
+    /// Whether a found device goes into the scan results.
+    pub fn lists(&self, info: &DeviceInfo) -> bool {
+        if info.connected {
+            return true;
+        }
+        !self.hidden.contains(&info.addr)
+            && (!self.hide_unnamed || info.paired || !info.name.is_empty())
+    }
+}
+
+/// The channels of a spawned `BluetoothManager`.
+pub struct ManagerHandle {
+    pub command_tx: mpsc::Sender<Command>,
+    pub state_rx: watch::Receiver<AppState>,
+    shutdown: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+impl ManagerHandle {
+    /// Abort the running command, stop the discovery and wait for the manager
+    /// to finish its exit work.
+    pub async fn shutdown(self) {
+        self.shutdown.cancel();
+        if let Err(err) = self.task.await {
+            error!(?err, "bluetooth manager task failed");
+        }
+    }
+}
+
+/// The only user of the backend, every Bluetooth operation goes through its
+/// command channel.
+pub struct BluetoothManager {
+    backend: Arc<dyn BluetoothBackend>,
+    scanner: Scanner,
+    connector: Connector,
+    options: ManagerOptions,
+    // the options given last, for a restarted manager
+    options_tx: Arc<watch::Sender<ManagerOptions>>,
+    state: AppState,
+    state_tx: Arc<watch::Sender<AppState>>,
+    // a background scan was stopped for the next connect, it goes on after
+    scan_paused: bool,
+    // the connected device had its audio, its loss isn't the connect
+    // setting it up
+    audio_seen: Option<Address>,
+}
+
+impl BluetoothManager {
+    /// Spawn the manager task, it reports the power state at once and scans if
+    /// Bluetooth is already on. A panicking manager is started again, taking
+    /// the commands of the same channel.
+    pub fn spawn(backend: Arc<dyn BluetoothBackend>, options: ManagerOptions) -> ManagerHandle {
+        let (command_tx, command_rx) = mpsc::channel(16);
+        let (state_tx, state_rx) = watch::channel(AppState::default());
+        let shutdown = CancellationToken::new();
+
+        let task = tokio::spawn(supervise(
+            backend,
+            Arc::new(watch::channel(options).0),
+            Arc::new(Mutex::new(command_rx)),
+            Arc::new(state_tx),
+            shutdown.clone(),
+        ));
+
+        ManagerHandle {
+            command_tx,
+            state_rx,
+            shutdown,
+            task,
+        }
+    }
+
+    /// A manager starting from the published state, without the work which
+    /// was running.
+    fn new(
+        backend: Arc<dyn BluetoothBackend>, options_tx: Arc<watch::Sender<ManagerOptions>>,
+        state_tx: Arc<watch::Sender<AppState>>,
+    ) -> Self {
+        let options = options_tx.borrow().clone();
+        let mut state = state_tx.borrow().clone();
+        if state.connect_state.is_in_progress() {
+            state.connect_state = BluetoothConnectState::Idle;
+        }
+        if state.scan_status == BluetoothScanStatus::Scanning {
+            state.scan_status = BluetoothScanStatus::Failed;
+        }
+        state.bluez_busy = false;
+        state_tx.send_replace(state.clone());
+
+        Self {
+            scanner: Scanner::new(backend.clone()).duration(options.scan_duration),
+            connector: Connector::new(backend.clone()),
+            backend,
+            options,
+            options_tx,
+            state,
+            state_tx,
+            scan_paused: false,
+            audio_seen: None,
+        }
+    }
+
+    async fn run(
+        mut self, command_rx: Arc<Mutex<mpsc::Receiver<Command>>>, shutdown: CancellationToken,
+    ) {
+        // released by a panic too, for the restarted manager
+        let mut command_rx = command_rx.lock_owned().await;
+
+        // dropping the serving future drops a running discovery, which stops
+        // it on the adapter
+        tokio::select! {
+            () = self.serve(&mut command_rx) => {}
+            () = shutdown.cancelled() => {}
+        }
+
+        self.exit().await;
+    }
+
+    async fn exit(&mut self) {
+        info!("shut down bluetooth manager");
+
+        if !self.state.powered {
+            return;
+        }
+
+        match self.options.exit_policy {
+            ExitPolicy::KeepConnected => {}
+            ExitPolicy::Disconnect => {
+                if let Err(err) = self.disconnect_all().await {
+                    error!(?err, "disconnect device on exit failed");
+                }
+            }
+            ExitPolicy::PowerOff => {
+                if let Err(err) = self.backend.set_powered(false).await {
+                    error!(?err, "close bluetooth on exit failed");
+                }
+            }
+        }
+    }
+
+    async fn serve(&mut self, command_rx: &mut mpsc::Receiver<Command>) {
+        let mut requests = Requests {
+            command_rx,
+            queued: VecDeque::new(),
+        };
+
+        let mut adapter_rx = self.backend.adapter_present();
+        let mut events = self.subscribe().await;
+        let mut link_check = interval(LINK_CHECK_INTERVAL);
+        link_check.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut position_check = interval(POSITION_INTERVAL);
+        position_check.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        self.start(&mut requests).await;
+
+        loop {
+            tokio::select! {
+                command = requests.next() => {
+                    let Some(command) = command else {
+                        break;
+                    };
+                    self.handle(command, &mut requests).await;
+                }
+                event = next_event(&mut events) => match event {
+                    Some(event) => self.apply_event(event).await,
+                    None => {
+                        debug!("bluetooth events ended");
+                        events = None;
+                    }
+                },
+                _ = link_check.tick(), if self.state.connected_device().is_some() => {
+                    self.check_link().await;
+                }
+                _ = position_check.tick(), if self.is_playing() => {
+                    self.check_now_playing().await;
+                }
+                Ok(()) = adapter_rx.changed() => {
+                    let present = *adapter_rx.borrow_and_update();
+                    if present {
+                        // the events of the old adapter ended with it
+                        events = self.subscribe().await;
+                    }
+                    self.set_adapter_present(present, &mut requests).await;
+                }
+            }
+        }
+    }
+
+    /// The changes made outside of the manager, like a headset reconnecting
+    /// by itself or taken by a phone.
+    async fn subscribe(&self) -> Option<mpsc::Receiver<BluetoothEvent>> {
+        match self.backend.events().await {
+            Ok(events) => Some(events),
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "watch bluetooth events failed, outside changes aren't shown"
+                );
+                None
+            }
+        }
+    }
+
+    fn is_playing(&self) -> bool {
+        self.state
+            .now_playing
+            .as_ref()
+            .is_some_and(|now_playing| now_playing.playing)
+    }
+
+    /// Read the track of the connected device again.
+    async fn check_now_playing(&mut self) {
+        let Some(addr) = self.state.connected_device().map(|info| info.addr) else {
+            return;
+        };
+        let now_playing = match self.backend.now_playing(addr).await {
+            Ok(now_playing) => now_playing,
+            Err(err) => {
+                debug!(?err, %addr, "read now playing failed");
+                None
+            }
+        };
+        if now_playing != self.state.now_playing {
+            self.update(|state| state.now_playing = now_playing);
+        }
+    }
+
+    /// Tell when the connected device drops its audio but stays connected, as
+    /// a multipoint headset does when it switches to a phone, and when the
+    /// audio comes back.
+    async fn check_audio(&mut self) {
+        // a connect sets up the audio itself
+        if self.state.connect_state.is_in_progress() {
+            return;
+        }
+        let Some(addr) = self.state.connected_device().map(|info| info.addr) else {
+            return;
+        };
+        let has_audio = match self.backend.has_audio(addr).await {
+            Ok(has_audio) => has_audio,
+            Err(err) => {
+                debug!(?err, %addr, "read audio of connected device failed");
+                return;
+            }
+        };
+
+        if has_audio {
+            self.audio_seen = Some(addr);
+            if self.state.audio_switched_away {
+                info!(%addr, "audio came back");
+                self.update(|state| state.audio_switched_away = false);
+            }
+        } else if self.audio_seen == Some(addr) && !self.state.audio_switched_away {
+            info!(%addr, "audio dropped while connected, switched to another device");
+            self.update(|state| state.audio_switched_away = true);
+        }
+    }
+
+    /// Read the signal of the connected device again, to warn before the
+    /// audio drops out.
+    async fn check_link(&mut self) {
+        let Some(connected) = self.state.connected_device().cloned() else {
+            return;
+        };
+        let quality = self.state.link_quality();
+        self.read_link_stats(connected.addr).await;
+        self.read_signal(&connected).await;
+
+        let now = self.state.link_quality();
+        if now == Some(LinkQuality::Weak) && quality != now {
+            let stats = self.state.link_history.last();
+            warn!(addr = %connected.addr, ?stats, "weak signal, audio may drop out");
+        }
+    }
+
+    /// Read the signal strength bluetoothd has of the connected device again,
+    /// shown when the controller doesn't tell the one of the connection.
+    async fn read_signal(&mut self, connected: &DeviceInfo) {
+        let info = match self.backend.properties(connected.addr).await {
+            Ok(info) => info,
+            Err(err) => {
+                debug!(?err, addr = %connected.addr, "read link of connected device failed");
+                return;
+            }
+        };
+        if (info.rssi, info.tx_power) == (connected.rssi, connected.tx_power) {
+            return;
+        }
+
+        debug!(addr = %info.addr, rssi = ?info.rssi, tx_power = ?info.tx_power, "link changed");
+        self.update(|state| {
+            if let Some(device) = state
+                .devices
+                .iter_mut()
+                .find(|device| device.addr == info.addr)
+            {
+                device.rssi = info.rssi;
+                device.tx_power = info.tx_power;
+            }
+        });
+    }
+
+    /// Add what the controller tells about the link to its history.
+    async fn read_link_stats(&mut self, addr: Address) {
+        let stats = match self.backend.link_stats(addr).await {
+            Ok(stats) => stats,
+            Err(err) => {
+                debug!(?err, %addr, "read link stats failed");
+                return;
+            }
+        };
+        if stats == LinkStats::default() {
+            return;
+        }
+
+        debug!(?stats, %addr, "link stats");
+        self.update(|state| {
+            if state.link_history.len() == LINK_HISTORY {
+                state.link_history.remove(0);
+            }
+            state.link_history.push(stats);
+        });
+    }
+
+    async fn apply_event(&mut self, event: BluetoothEvent) {
+        debug!(?event, "bluetooth event");
+        match event {
+            BluetoothEvent::Connected(addr) => {
+                let listed = self.state.devices.iter().any(|info| info.addr == addr);
+                // connected without a scan, the banner needs its name
+                let found = if listed {
+                    None
+                } else {
+                    match self.backend.properties(addr).await {
+                        Ok(info) => Some(info),
+                        Err(err) => {
+                            warn!(?err, %addr, "get connected device failed");
+                            return;
+                        }
+                    }
+                };
+                self.update(|state| {
+                    match state.devices.iter_mut().find(|info| info.addr == addr) {
+                        Some(info) => info.connected = true,
+                        None => state.devices.extend(found),
+                    }
+                    if !state.connect_state.is_in_progress() {
+                        state.connect_state = BluetoothConnectState::Connected;
+                    }
+                });
+            }
+            BluetoothEvent::Disconnected(addr) => {
+                if self.state.tether.addr() == Some(addr) {
+                    // the interface went with the connection, the route and
+                    // name servers of its setup are still to undo
+                    if let Err(err) = self.backend.disconnect_network(addr).await {
+                        debug!(?err, %addr, "network of the phone already down");
+                    }
+                }
+                self.update(|state| {
+                    if let Some(info) = state.devices.iter_mut().find(|info| info.addr == addr) {
+                        info.connected = false;
+                    }
+                    if state.connect_state == BluetoothConnectState::Connected
+                        && state.connected_device().is_none()
+                    {
+                        state.connect_state = BluetoothConnectState::Idle;
+                    }
+                    // the interface went with the connection
+                    if state.tether.addr() == Some(addr) {
+                        state.tether = TetherState::Off;
+                    }
+                });
+            }
+            BluetoothEvent::BatteryChanged { addr, battery } => {
+                self.update(|state| {
+                    if let Some(info) = state.devices.iter_mut().find(|info| info.addr == addr) {
+                        info.battery = Some(battery);
+                    }
+                });
+            }
+            BluetoothEvent::MediaChanged(addr) => {
+                if self.state.connected_device().map(|info| info.addr) == Some(addr) {
+                    self.check_now_playing().await;
+                    self.check_audio().await;
+                }
+            }
+            // the list is only changed by scans
+            BluetoothEvent::DeviceFound(_) | BluetoothEvent::DeviceRemoved(_) => {}
+        }
+    }
+
+    /// Read the power state, connect the auto connect device and scan, at
+    /// start and when an adapter is plugged in again.
+    async fn start(&mut self, requests: &mut Requests<'_>) {
+        self.read_bluez_version().await;
+
+        match self.backend.is_powered().await {
+            Ok(powered) => {
+                self.update(|state| state.powered = powered);
+                if let Some(addr) = self.options.auto_connect {
+                    info!(%addr, "auto connect");
+                    self.ensure_connected(addr, requests).await;
+                }
+                if self.state.powered && self.options.scan_at_start {
+                    self.scan().await;
+                }
+            }
+            Err(err) => {
+                error!(?err, "get bluetooth powered failed");
+            }
+        }
+    }
+
+    /// Publish the version of bluetoothd, warning when it's too old for some
+    /// of the features.
+    async fn read_bluez_version(&mut self) {
+        match self.backend.bluez_version().await {
+            Ok(Some(version)) => {
+                if version.is_outdated() {
+                    warn!(
+                        %version,
+                        minimum = %BluezVersion::MINIMUM,
+                        "bluez is outdated, some features may not work"
+                    );
+                } else {
+                    info!(%version, "bluez version");
+                }
+                self.update(|state| state.bluez_version = Some(version));
+            }
+            Ok(None) => debug!("bluez version unknown"),
+            Err(err) => warn!(?err, "get bluez version failed"),
+        }
+    }
+
+    async fn handle(&mut self, command: Command, requests: &mut Requests<'_>) {
+        debug!(?command, "handle bluetooth command");
+
+        let needs_adapter = !matches!(
+            command,
+            Command::CancelConnect | Command::StopScan | Command::SetOptions(_) | Command::Ping(_)
+        );
+        if needs_adapter && !self.state.adapter_present {
+            warn!(?command, "no bluetooth adapter, drop command");
+            command.reject(Error::AdapterMissing);
+            return;
+        }
+
+        let resume_scan = mem::take(&mut self.scan_paused);
+
+        match command {
+            Command::Scan => self.stoppable_scan(requests).await,
+            Command::BackgroundScan => self.background_scan(requests).await,
+            Command::Connect(addr) => {
+                self.connect(addr, requests).await;
+            }
+            Command::CancelConnect => debug!("no connect to cancel"),
+            Command::StopScan => debug!("no scan to stop"),
+            Command::EnsureConnected(addr) => {
+                self.ensure_connected(addr, requests).await;
+            }
+            Command::Disconnect => self.disconnect().await,
+            Command::PowerOn => {
+                if self.set_powered(true).await.is_ok() {
+                    self.scan().await;
+                }
+            }
+            Command::PowerOff => {
+                // told by the state
+                let _ = self.set_powered(false).await;
+            }
+            Command::Forget(addr) => self.forget(addr).await,
+            Command::ChangeVolume(step) => self.change_volume(step).await,
+            Command::Tether(addr) => self.tether(addr).await,
+            Command::Untether => self.untether().await,
+            Command::ReadDetails(addr) => self.read_details(addr).await,
+            Command::Revalidate => self.revalidate(requests).await,
+            Command::SetOptions(options) => self.set_options(options),
+            Command::Ping(reply_tx) => {
+                let _ = reply_tx.send(());
+            }
+            Command::ConnectAndReply {
+                addr,
+                ensure,
+                reply_tx,
+            } => {
+                let outcome = if ensure {
+                    self.ensure_connected(addr, requests).await
+                } else {
+                    self.connect(addr, requests).await
+                };
+                if let Some(outcome) = outcome {
+                    let _ = reply_tx.send(outcome);
+                }
+            }
+        }
+
+        // discovering would disturb the audio of a connected device
+        if resume_scan && self.state.powered && self.state.connected_device().is_none() {
+            debug!("resume background scan after connect");
+            requests.push(Command::BackgroundScan);
+        }
+    }
+
+    async fn set_adapter_present(&mut self, present: bool, requests: &mut Requests<'_>) {
+        if present == self.state.adapter_present {
+            return;
+        }
+
+        if present {
+            info!("bluetooth adapter is back");
+            self.update(|state| state.adapter_present = true);
+            self.start(requests).await;
+        } else {
+            warn!("bluetooth adapter removed");
+            // the devices went with the adapter
+            self.update(|state| {
+                state.adapter_present = false;
+                state.powered = false;
+                state.scan_status = BluetoothScanStatus::Disable;
+                state.devices.clear();
+                state.connect_state = BluetoothConnectState::Idle;
+            });
+        }
+    }
+
+    fn set_options(&mut self, options: ManagerOptions) {
+        self.scanner = Scanner::new(self.backend.clone()).duration(options.scan_duration);
+        self.options_tx.send_replace(options.clone());
+        self.options = options;
+    }
+
+    /// Keep the PIN a device paired with for its next pairing, in the options
+    /// a restarted manager starts from as well, and tell the app to save it.
+    fn remember_pin(&mut self, addr: Address, pin: String) {
+        self.options.device_settings.entry(addr).or_default().pin = Some(pin.clone());
+        self.options_tx.send_modify(|options| {
+            options.device_settings.entry(addr).or_default().pin = Some(pin.clone());
+        });
+        self.update(|state| state.paired_pin = Some((addr, pin)));
+    }
+
+    /// Change the state and publish a snapshot of it.
+    fn update(&mut self, f: impl FnOnce(&mut AppState)) {
+        f(&mut self.state);
+        // the track, the audio and the link went with the device
+        if self.state.connected_device().is_none() {
+            self.state.now_playing = None;
+            self.state.audio_switched_away = false;
+            self.state.link_history.clear();
+            self.audio_seen = None;
+        }
+        self.state_tx.send_replace(self.state.clone());
+    }
+
+    fn advance_connect(&mut self, stage: ConnectStage) {
+        self.update(|state| state.connect_state.advance(stage));
+    }
+
+    fn set_scan_status(&mut self, status: BluetoothScanStatus) {
+        self.update(|state| state.scan_status = status);
+    }
+
+    /// Switch the adapter on or off, a failure is logged here already.
+    async fn set_powered(&mut self, powered: bool) -> Result<()> {
+        if powered {
+            info!("open bluetooth");
+        } else {
+            info!("close bluetooth");
+        }
+
+        if let Err(err) = self.backend.set_powered(powered).await {
+            error!(?err, powered, "set bluetooth powered failed");
+            return Err(err);
+        }
+
+        self.update(|state| {
+            state.powered = powered;
+            state.scan_status = BluetoothScanStatus::Disable;
+        });
+        Ok(())
+    }
+
+    async fn scan(&mut self) {
+        self.set_scan_status(BluetoothScanStatus::Scanning);
+        let result = self.scanner.scan().await;
+        self.finish_scan(result);
+    }
+
+    /// Like `scan`, but `StopScan` drops it, which stops the discovery. The
+    /// other commands wait for the scan.
+    async fn stoppable_scan(&mut self, requests: &mut Requests<'_>) {
+        self.set_scan_status(BluetoothScanStatus::Scanning);
+
+        let result = {
+            let mut scan = pin!(self.scanner.scan());
+            loop {
+                tokio::select! {
+                    result = &mut scan => break Some(result),
+                    command = requests.command_rx.recv() => match command {
+                        Some(Command::StopScan) => break None,
+                        Some(command) => requests.push(command),
+                        // shutting down, the scan ends by itself
+                        None => break Some(scan.await),
+                    },
+                }
+            }
+        };
+
+        match result {
+            Some(result) => self.finish_scan(result),
+            None => {
+                info!("scan stopped");
+                self.set_scan_status(BluetoothScanStatus::Finished);
+            }
+        }
+    }
+
+    fn finish_scan(&mut self, result: Result<Vec<DeviceInfo>>) {
+        match result {
+            Ok(mut devices) => {
+                devices.retain(|info| self.options.lists(info));
+                self.update(|state| {
+                    if devices.iter().any(|info| info.connected) {
+                        state.connect_state = BluetoothConnectState::Connected;
+                    }
+                    state.devices = devices;
+                    state.scan_status = BluetoothScanStatus::Finished;
+                });
+            }
+            Err(err) => {
+                error!(?err, "discover devices failed");
+                self.set_scan_status(BluetoothScanStatus::Failed);
+            }
+        }
+    }
+
+    async fn background_scan(&mut self, requests: &mut Requests<'_>) {
+        debug!("background scan");
+        let result = {
+            let mut scan = pin!(self.scanner.scan());
+            tokio::select! {
+                result = &mut scan => result,
+                command = requests.command_rx.recv() => {
+                    // dropping the scan stops the discovery
+                    debug!(?command, "stop background scan for a command");
+                    if let Some(command) = command {
+                        self.scan_paused = matches!(
+                            command,
+                            Command::Connect(_)
+                                | Command::EnsureConnected(_)
+                                | Command::ConnectAndReply { .. }
+                        );
+                        requests.queued.push_front(command);
+                    }
+                    return;
+                }
+            }
+        };
+
+        match result {
+            Ok(mut devices) => {
+                devices.retain(|info| self.options.lists(info));
+                // merged, devices out of range stay until the next full scan
+                self.update(|state| {
+                    for info in devices {
+                        match state
+                            .devices
+                            .iter_mut()
+                            .find(|known| known.addr == info.addr)
+                        {
+                            Some(known) => *known = info,
+                            None => state.devices.push(info),
+                        }
+                    }
+                });
+            }
+            Err(err) => {
+                warn!(?err, "background scan failed");
+            }
+        }
+    }
+
+    async fn revalidate(&mut self, requests: &mut Requests<'_>) {
+        info!("check bluetooth after resume");
+        let connected_before = self.state.connected_device().map(|info| info.addr);
+
+        let powered = match self.backend.is_powered().await {
+            Ok(powered) => powered,
+            Err(err) => {
+                error!(?err, "get bluetooth powered failed");
+                return;
+            }
+        };
+
+        let mut connected = HashSet::new();
+        if powered {
+            match self.backend.known_devices().await {
+                Ok(devices) => {
+                    connected.extend(
+                        devices
+                            .iter()
+                            .filter(|info| info.connected)
+                            .map(|info| info.addr),
+                    );
+                }
+                Err(err) => {
+                    warn!(?err, "get known devices failed");
+                    return;
+                }
+            }
+        }
+
+        self.update(|state| {
+            if state.powered && !powered {
+                state.scan_status = BluetoothScanStatus::Disable;
+            }
+            state.powered = powered;
+            for info in &mut state.devices {
+                info.connected = connected.contains(&info.addr);
+            }
+            if state.connect_state == BluetoothConnectState::Connected && connected.is_empty() {
+                state.connect_state = BluetoothConnectState::Idle;
+            }
+        });
+
+        match connected_before {
+            Some(addr) if !connected.contains(&addr) => {
+                if self.options.reconnect_on_resume {
+                    info!(%addr, "device lost during sleep, reconnect");
+                    self.ensure_connected(addr, requests).await;
+                } else {
+                    info!(%addr, "device lost during sleep");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Like `connect`, but powering on first and leaving a connected device
+    /// alone.
+    async fn ensure_connected(
+        &mut self, addr: Address, requests: &mut Requests<'_>,
+    ) -> Option<Result<()>> {
+        match self.backend.is_powered().await {
+            Ok(true) => {}
+            Ok(false) => {
+                if let Err(err) = self.set_powered(true).await {
+                    return Some(Err(err));
+                }
+            }
+            Err(err) => {
+                error!(?err, "get bluetooth powered failed");
+                return Some(Err(err));
+            }
+        }
+
+        match self.backend.properties(addr).await {
+            Ok(info) if info.connected => {
+                self.update(|state| state.connect_state = BluetoothConnectState::Connected);
+                Some(Ok(()))
+            }
+            Ok(_) => {
+                info!(%addr, "device is not connected, reconnect");
+                self.connect(addr, requests).await
+            }
+            // not known to BlueZ, like a device which was never in range
+            // since bluetoothd started
+            Err(err) => {
+                info!(?err, %addr, "device is unknown, scan before connecting");
+                self.scan().await;
+                self.connect(addr, requests).await
+            }
+        }
+    }
+
+    /// Connect while still taking commands, to cancel it or queue them.
+    /// Returns how it ended, `None` when it was cancelled.
+    async fn connect(&mut self, addr: Address, requests: &mut Requests<'_>) -> Option<Result<()>> {
+        let result = {
+            let mut connect = pin!(self.try_connect(addr));
+            loop {
+                tokio::select! {
+                    result = &mut connect => break Some(result),
+                    command = requests.command_rx.recv() => match command {
+                        Some(Command::CancelConnect) => break None,
+                        Some(Command::Connect(other) | Command::EnsureConnected(other)) => {
+                            warn!(%addr, %other, "connect in progress, reject connect");
+                        }
+                        Some(command @ Command::ConnectAndReply { .. }) => {
+                            warn!(%addr, ?command, "connect in progress, reject connect");
+                            command.reject(Error::InProgress);
+                        }
+                        // the manager is alive, just busy
+                        Some(Command::Ping(reply_tx)) => {
+                            let _ = reply_tx.send(());
+                        }
+                        Some(command) => requests.push(command),
+                        // every sender is gone, just finish the connect
+                        None => break Some((&mut connect).await),
+                    },
+                }
+            }
+        };
+
+        let Some(result) = result else {
+            info!(%addr, "cancel connect");
+            if let BluetoothConnectState::InProgress(stage) = self.state.connect_state {
+                // BlueZ goes on after the call is dropped
+                if let Err(err) = self.connector.cancel(addr, stage).await {
+                    warn!(?err, %addr, "stop cancelled connect failed");
+                }
+            }
+            self.update(|state| {
+                state.connect_state = BluetoothConnectState::Idle;
+                state.bluez_busy = false;
+            });
+            return None;
+        };
+
+        match result {
+            Ok(()) => {
+                self.update(|state| {
+                    state.connect_state.succeed();
+                    state.last_connected = Some(addr);
+                });
+                // the events of the audio set up came during the connect
+                self.check_now_playing().await;
+                self.check_audio().await;
+                Some(Ok(()))
+            }
+            Err(err) => {
+                error!(?err, stage = ?self.state.connect_state, "connect device failed");
+                self.update(|state| state.connect_state.fail(err.clone()));
+                Some(Err(err))
+            }
+        }
+    }
+
+    async fn try_connect(&mut self, addr: Address) -> Result<()> {
+        let settings = self
+            .options
+            .device_settings
+            .get(&addr)
+            .cloned()
+            .unwrap_or_default();
+
+        // 先断开之前的连接
+        self.advance_connect(ConnectStage::DisconnectingPrevious);
+        self.disconnect_all().await?;
+
+        // 等待扫描停止
+        self.connector.wait_discovery_stopped().await;
+
+        // 再重新连接
+        self.advance_connect(ConnectStage::Pairing);
+        let remembered = settings.pin.as_deref();
+        let paired = self
+            .wait_while_busy(|connector| async move { connector.pair(addr, remembered).await })
+            .await?;
+        if let Some(pin) = paired {
+            info!(%addr, "paired with pin");
+            self.remember_pin(addr, pin);
+        }
+        if settings.trust {
+            self.connector.trust(addr).await?;
+        }
+
+        self.advance_connect(ConnectStage::Connecting);
+        let profile = settings.profile;
+        let connected = self
+            .wait_while_busy(|connector| async move { connector.connect(addr, profile).await })
+            .await;
+        match connected {
+            Ok(()) => {}
+            Err(err) if err.is_auth_failure() => {
+                warn!(?err, %addr, "pairing lost, pair again");
+                self.advance_connect(ConnectStage::Repairing);
+                let remembered = self
+                    .options
+                    .device_settings
+                    .get(&addr)
+                    .and_then(|settings| settings.pin.clone());
+                if let Some(pin) = self.connector.repair(addr, remembered.as_deref()).await? {
+                    self.remember_pin(addr, pin);
+                }
+                if settings.trust {
+                    self.connector.trust(addr).await?;
+                }
+                // BlueZ is often still busy with the pairing
+                self.wait_while_busy(
+                    |connector| async move { connector.connect(addr, profile).await },
+                )
+                .await?;
+            }
+            Err(err) => return Err(err),
+        }
+
+        self.advance_connect(ConnectStage::VerifyingAudio);
+        self.connector.verify(addr).await?;
+
+        // the audio works without it, so a failure isn't a failed connect
+        if let Some(volume) = settings.volume {
+            if let Err(err) = self.connector.set_volume(addr, volume).await {
+                warn!(?err, %addr, volume, "set initial volume failed");
+            }
+        }
+
+        self.update(|state| {
+            if let Some(info) = state.devices.iter_mut().find(|info| info.addr == addr) {
+                info.connected = true;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Make the call again while BlueZ is busy with another operation, up to
+    /// `BUSY_TIMEOUT`, showing the wait.
+    async fn wait_while_busy<T, F>(&mut self, call: impl Fn(Connector) -> F) -> Result<T>
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let deadline = Instant::now() + BUSY_TIMEOUT;
+        let result = loop {
+            match call(self.connector.clone()).await {
+                Err(Error::InProgress) if Instant::now() < deadline => {
+                    if !self.state.bluez_busy {
+                        info!("bluez is busy with another operation, wait");
+                        self.update(|state| state.bluez_busy = true);
+                    }
+                    sleep(BUSY_RETRY_INTERVAL).await;
+                }
+                result => break result,
+            }
+        };
+        if self.state.bluez_busy {
+            self.update(|state| state.bluez_busy = false);
+        }
+        result
+    }
+
+    async fn disconnect(&mut self) {
+        match self.disconnect_all().await {
+            Ok(()) => {
+                self.update(|state| state.connect_state = BluetoothConnectState::Idle);
+            }
+            Err(err) => {
+                error!(?err, "disconnect device failed");
+                self.update(|state| {
+                    state.connect_state = BluetoothConnectState::Failed {
+                        stage: ConnectStage::DisconnectingPrevious,
+                        error: err,
+                    }
+                });
+            }
+        }
+    }
+
+    async fn disconnect_all(&mut self) -> Result<()> {
+        let connected = self
+            .state
+            .devices
+            .iter()
+            .filter(|info| info.connected)
+            .map(|info| info.addr)
+            .collect::<Vec<_>>();
+
+        for addr in connected {
+            self.connector.disconnect(addr).await?;
+            self.update(|state| {
+                if let Some(info) = state.devices.iter_mut().find(|info| info.addr == addr) {
+                    info.connected = false;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn forget(&mut self, addr: Address) {
+        if let Err(err) = self.connector.forget(addr).await {
+            error!(?err, %addr, "forget device failed");
+            return;
+        }
+
+        self.update(|state| {
+            let was_connected = state
+                .devices
+                .iter()
+                .any(|info| info.addr == addr && info.connected);
+            state.devices.retain(|info| info.addr != addr);
+
+            if was_connected {
+                state.connect_state = BluetoothConnectState::Idle;
+            }
+        });
+    }
+
+    async fn change_volume(&mut self, step: i8) {
+        let Some(addr) = self.state.connected_device().map(|info| info.addr) else {
+            debug!("no connected device, ignore volume change");
+            return;
+        };
+
+        let volume = match self.connector.volume(addr).await {
+            Ok(volume) => volume,
+            Err(err) => {
+                warn!(?err, %addr, "get volume failed");
+                return;
+            }
+        };
+        let volume = volume.saturating_add_signed(step).min(100);
+        match self.connector.set_volume(addr, volume).await {
+            Ok(()) => info!(%addr, volume, "change volume"),
+            Err(err) => warn!(?err, %addr, volume, "set volume failed"),
+        }
+    }
+
+    async fn tether(&mut self, addr: Address) {
+        match &self.state.tether {
+            TetherState::Connected { addr: current, .. } if *current == addr => {
+                debug!(%addr, "already tethered");
+                return;
+            }
+            TetherState::Connected { .. } => self.untether().await,
+            _ => {}
+        }
+
+        info!(%addr, "tether phone");
+        self.update(|state| state.tether = TetherState::Connecting(addr));
+        let tether = match self.backend.connect_network(addr).await {
+            Ok(interface) => {
+                info!(%addr, interface, "phone tethered");
+                TetherState::Connected { addr, interface }
+            }
+            Err(error) => {
+                warn!(?error, %addr, "tether phone failed");
+                TetherState::Failed { addr, error }
+            }
+        };
+        self.update(|state| state.tether = tether);
+    }
+
+    async fn untether(&mut self) {
+        if let TetherState::Connected { addr, .. } = self.state.tether {
+            match self.backend.disconnect_network(addr).await {
+                Ok(()) => info!(%addr, "phone untethered"),
+                Err(err) => warn!(?err, %addr, "untether phone failed"),
+            }
+        }
+        self.update(|state| state.tether = TetherState::Off);
+    }
+
+    async fn read_details(&mut self, addr: Address) {
+        match self.backend.details(addr).await {
+            Ok(details) => {
+                debug!(?details, "read device details");
+                self.update(|state| state.details = Some(details));
+            }
+            Err(err) => warn!(?err, %addr, "read device details failed"),
+        }
+    }
+}
+
+/// Run the manager until it ends, starting a new one each time it panics.
+/// The new one publishes the state it starts from, so a connect or scan left
+/// running by the panic doesn't show forever.
+async fn supervise(
+    backend: Arc<dyn BluetoothBackend>, options_tx: Arc<watch::Sender<ManagerOptions>>,
+    command_rx: Arc<Mutex<mpsc::Receiver<Command>>>, state_tx: Arc<watch::Sender<AppState>>,
+    shutdown: CancellationToken,
+) {
+    loop {
+        let manager = BluetoothManager::new(backend.clone(), options_tx.clone(), state_tx.clone());
+        match tokio::spawn(manager.run(command_rx.clone(), shutdown.clone())).await {
+            Ok(()) => break,
+            Err(err) if err.is_panic() && !shutdown.is_cancelled() => {
+                error!(?err, "bluetooth manager panicked, restart it");
+            }
+            Err(err) => {
+                error!(?err, "bluetooth manager task failed");
+                break;
+            }
+        }
+
+        tokio::select! {
+            () = sleep(RESTART_DELAY) => {}
+            () = shutdown.cancelled() => break,
+        }
+        // connected again at start only
+        options_tx.send_modify(|options| options.auto_connect = None);
+    }
+}
+
+/// The next event, pending forever without a subscription.
+async fn next_event(events: &mut Option<mpsc::Receiver<BluetoothEvent>>) -> Option<BluetoothEvent> {
+    match events {
+        Some(events) => events.recv().await,
+        None => future::pending().await,
+    }
+}
+
+/// The commands not handled yet, taken from the channel while a connect runs.
+struct Requests<'a> {
+    command_rx: &'a mut mpsc::Receiver<Command>,
+    queued: VecDeque<Command>,
+}
+
+impl Requests<'_> {
+    async fn next(&mut self) -> Option<Command> {
+        match self.queued.pop_front() {
+            Some(command) => Some(command),
+            None => self.command_rx.recv().await,
+        }
+    }
+
+    /// Queue the command unless the same one is waiting already, newer options
+    /// replace the queued ones.
+    fn push(&mut self, command: Command) {
+        match self
+            .queued
+            .iter_mut()
+            .find(|queued| same_request(queued, &command))
+        {
+            Some(queued) if matches!(command, Command::SetOptions(_)) => *queued = command,
+            Some(_) => debug!(?command, "drop repeated command"),
+            None => self.queued.push_back(command),
+        }
+    }
+}
+
+fn same_request(a: &Command, b: &Command) -> bool {
+    match (a, b) {
+        (Command::Forget(a), Command::Forget(b)) => a == b,
+        (Command::ReadDetails(a), Command::ReadDetails(b)) => a == b,
+        (Command::EnsureConnected(a), Command::EnsureConnected(b)) => a == b,
+        _ => matches!(
+            (a, b),
+            (Command::Scan, Command::Scan)
+                | (Command::BackgroundScan, Command::BackgroundScan)
+                | (Command::Disconnect, Command::Disconnect)
+                | (Command::PowerOn, Command::PowerOn)
+                | (Command::PowerOff, Command::PowerOff)
+                | (Command::Revalidate, Command::Revalidate)
+                | (Command::Untether, Command::Untether)
+                | (Command::SetOptions(_), Command::SetOptions(_))
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::backend::mock::MockBackend;
+
+    // long enough for a second command to arrive while the connect runs, the
+    // clock of the tests is paused and jumps over it
+    const SLOW_OPERATION: Duration = Duration::from_secs(10);
+
+    fn addr(last: u8) -> Address {
+        Address::new([0x00, 0x11, 0x22, 0x33, 0x44, last])
+    }
+
+    fn device(last: u8, paired: bool) -> DeviceInfo {
+        DeviceInfo {
+            addr: addr(last),
+            name: format!("Headset {last}"),
+            paired,
+            ..DeviceInfo::default()
+        }
+    }
+
+    fn spawn(backend: &Arc<MockBackend>) -> ManagerHandle {
+        let options = ManagerOptions {
+            scan_at_start: false,
+            ..ManagerOptions::default()
+        };
+        BluetoothManager::spawn(backend.clone(), options)
+    }
+
+    async fn connect(handle: &ManagerHandle, addr: Address) -> oneshot::Receiver<Result<()>> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        handle
+            .command_tx
+            .send(Command::ConnectAndReply {
+                addr,
+                ensure: false,
+                reply_tx,
+            })
+            .await
+            .unwrap();
+        reply_rx
+    }
+
+    async fn wait_connecting(handle: &mut ManagerHandle) {
+        handle
+            .state_rx
+            .wait_for(|state| state.connect_state.is_in_progress())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_pairs_and_connects() {
+        let backend = Arc::new(MockBackend::new(vec![device(1, false)]));
+        let handle = spawn(&backend);
+
+        assert_eq!(connect(&handle, addr(1)).await.unwrap(), Ok(()));
+        let state = handle.state_rx.borrow().clone();
+        assert_eq!(state.connect_state, BluetoothConnectState::Connected);
+        assert_eq!(state.last_connected, Some(addr(1)));
+        let info = backend.properties(addr(1)).await.unwrap();
+        assert!(info.paired && info.connected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_tries_the_default_pins() {
+        let backend =
+            Arc::new(MockBackend::new(vec![device(1, false)]).require_pin(addr(1), "1234"));
+        let handle = spawn(&backend);
+
+        assert_eq!(connect(&handle, addr(1)).await.unwrap(), Ok(()));
+        assert_eq!(
+            handle.state_rx.borrow().paired_pin,
+            Some((addr(1), "1234".to_string()))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_retries_transient_errors() {
+        let backend = Arc::new(MockBackend::new(vec![device(1, true)]).fail_connect_times(
+            addr(1),
+            "br-connection-busy",
+            2,
+        ));
+        let handle = spawn(&backend);
+
+        assert_eq!(connect(&handle, addr(1)).await.unwrap(), Ok(()));
+        assert!(backend.properties(addr(1)).await.unwrap().connected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_fails_once_the_retries_are_used_up() {
+        let backend = Arc::new(
+            MockBackend::new(vec![device(1, true)])
+                .fail_connect(addr(1), "br-connection-page-timeout"),
+        );
+        let handle = spawn(&backend);
+
+        assert_eq!(
+            connect(&handle, addr(1)).await.unwrap(),
+            Err(Error::PageTimeout)
+        );
+        assert_eq!(
+            handle.state_rx.borrow().connect_state,
+            BluetoothConnectState::Failed {
+                stage: ConnectStage::Connecting,
+                error: Error::PageTimeout,
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_pairs_again_when_the_pairing_was_lost() {
+        let backend = Arc::new(MockBackend::new(vec![device(1, true)]).fail_connect_times(
+            addr(1),
+            "br-connection-key-missing",
+            1,
+        ));
+        let handle = spawn(&backend);
+
+        assert_eq!(connect(&handle, addr(1)).await.unwrap(), Ok(()));
+        let info = backend.properties(addr(1)).await.unwrap();
+        assert!(info.paired && info.connected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn connect_after_pairing_again_waits_while_busy() {
+        let backend = Arc::new(
+            MockBackend::new(vec![device(1, true)])
+                .fail_connect_times(addr(1), "br-connection-key-missing", 1)
+                .then_fail_connect(addr(1), Error::InProgress),
+        );
+        let handle = spawn(&backend);
+
+        assert_eq!(connect(&handle, addr(1)).await.unwrap(), Ok(()));
+        assert!(backend.properties(addr(1)).await.unwrap().connected);
+        assert!(!handle.state_rx.borrow().bluez_busy);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_connect_is_left_unanswered() {
+        let backend =
+            Arc::new(MockBackend::new(vec![device(1, false)]).operation_delay(SLOW_OPERATION));
+        let mut handle = spawn(&backend);
+
+        let reply_rx = connect(&handle, addr(1)).await;
+        wait_connecting(&mut handle).await;
+        handle
+            .command_tx
+            .send(Command::CancelConnect)
+            .await
+            .unwrap();
+
+        assert!(reply_rx.await.is_err());
+        assert_eq!(
+            handle.state_rx.borrow().connect_state,
+            BluetoothConnectState::Idle
+        );
+        assert!(!backend.properties(addr(1)).await.unwrap().connected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn second_connect_is_rejected() {
+        let backend = Arc::new(
+            MockBackend::new(vec![device(1, true), device(2, true)])
+                .operation_delay(SLOW_OPERATION),
+        );
+        let mut handle = spawn(&backend);
+
+        let first_rx = connect(&handle, addr(1)).await;
+        wait_connecting(&mut handle).await;
+        let second_rx = connect(&handle, addr(2)).await;
+
+        assert_eq!(second_rx.await.unwrap(), Err(Error::InProgress));
+        assert_eq!(first_rx.await.unwrap(), Ok(()));
+        assert!(!backend.properties(addr(2)).await.unwrap().connected);
+    }
+
+    #[test]
+    fn repeated_requests_are_the_same() {
+        assert!(same_request(&Command::Scan, &Command::Scan));
+        assert!(same_request(
+            &Command::EnsureConnected(addr(1)),
+            &Command::EnsureConnected(addr(1))
+        ));
+        assert!(!same_request(
+            &Command::EnsureConnected(addr(1)),
+            &Command::EnsureConnected(addr(2))
+        ));
+        assert!(!same_request(
+            &Command::Forget(addr(1)),
+            &Command::Forget(addr(2))
+        ));
+        assert!(!same_request(&Command::Scan, &Command::BackgroundScan));
+        // each connect is answered on its own
+        assert!(!same_request(
+            &Command::Connect(addr(1)),
+            &Command::Connect(addr(1))
+        ));
+    }
+}
@@ -0,0 +1,444 @@
+use crate::{bluetooth::battery::BudsBattery, error::Error};
+use bluer::{Address, Uuid};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt, time::Duration};
+
+pub mod backend;
+pub mod battery;
+pub mod connector;
+pub mod manager;
+pub mod scanner;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BluetoothScanStatus {
+    Disable,
+    Scanning,
+    Finished,
+    Failed,
+}
+
+/// Steps of connecting a device, in the order they run.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum ConnectStage {
+    DisconnectingPrevious,
+    Pairing,
+    Connecting,
+    /// The device lost its pairing, it's removed, paired and connected again.
+    Repairing,
+    VerifyingAudio,
+}
+
+/// State machine of the connect workflow, `Failed` keeps the stage that went
+/// wrong.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub enum BluetoothConnectState {
+    #[default]
+    Idle,
+    InProgress(ConnectStage),
+    Connected,
+    Failed {
+        stage: ConnectStage,
+        error: Error,
+    },
+}
+
+impl BluetoothConnectState {
+    pub fn is_in_progress(&self) -> bool {
+        matches!(self, Self::InProgress(_))
+    }
+
+    /// Start a connect, or move it on to `stage`, stages only go forward.
+    pub fn advance(&mut self, stage: ConnectStage) {
+        debug_assert!(match self {
+            Self::InProgress(current) => *current < stage,
+            _ => stage == ConnectStage::DisconnectingPrevious,
+        });
+        *self = Self::InProgress(stage);
+    }
+
+    /// The connect in progress has finished.
+    pub fn succeed(&mut self) {
+        debug_assert!(self.is_in_progress());
+        *self = Self::Connected;
+    }
+
+    /// The connect in progress has failed at its current stage.
+    pub fn fail(&mut self, error: Error) {
+        if let Self::InProgress(stage) = *self {
+            *self = Self::Failed { stage, error };
+        }
+    }
+}
+
+/// Internet through a phone, over the network access point of its PAN.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub enum TetherState {
+    #[default]
+    Off,
+    Connecting(Address),
+    /// The network of the phone is up on `interface`, like `bnep0`.
+    Connected {
+        addr: Address,
+        interface: String,
+    },
+    Failed {
+        addr: Address,
+        error: Error,
+    },
+}
+
+impl TetherState {
+    /// The phone tethered or being tethered.
+    pub fn addr(&self) -> Option<Address> {
+        match self {
+            Self::Off => None,
+            Self::Connecting(addr) | Self::Connected { addr, .. } | Self::Failed { addr, .. } => {
+                Some(*addr)
+            }
+        }
+    }
+}
+
+/// What a device tells about itself, from the Device Information Service of its
+/// GATT, or else the numbers of its device ID. `None` when it tells neither.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DeviceDetails {
+    pub addr: Address,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub firmware: Option<String>,
+    pub hardware: Option<String>,
+}
+
+/// The track of the media player of a device, like a phone playing to the
+/// handheld or headphones with their own player, read over AVRCP.
+#[derive(Debug, PartialEq, Clone)]
+pub struct NowPlaying {
+    pub title: String,
+    pub artist: String,
+    pub position: Duration,
+    /// Unknown for streams.
+    pub duration: Option<Duration>,
+    pub playing: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DeviceInfo {
+    pub addr: Address,
+    pub name: String,
+    pub paired: bool,
+    pub connected: bool,
+    /// Signal strength in dBm, known for devices seen by a scan and read again
+    /// while connected, when BlueZ has it.
+    pub rssi: Option<i16>,
+    /// Transmit power the device advertises, in dBm.
+    pub tx_power: Option<i16>,
+    /// Battery level in percent, reported by some headsets once connected.
+    pub battery: Option<u8>,
+    /// Class of device of a BR/EDR device, telling what kind of device it is.
+    pub class: Option<u32>,
+    /// The services the device advertises, known once it's been seen or
+    /// connected.
+    pub uuids: Vec<Uuid>,
+    /// The company ID of the maker, from the device ID of the device.
+    pub vendor: Option<u16>,
+    /// The batteries of earbuds by a vendor protocol, with the company ID of
+    /// the vendor.
+    pub buds_battery: Option<(u16, BudsBattery)>,
+    /// The device advertises that it's ready to pair, seen by a scan.
+    pub pairing_advertisement: Option<PairingAdvertisement>,
+}
+
+// the service of Google Fast Pair, and the length of its data in pairing mode,
+// the model ID alone
+const FAST_PAIR_UUID: u128 = 0x0000fe2c_0000_1000_8000_00805f9b34fb;
+const FAST_PAIR_MODEL_ID_LEN: usize = 3;
+
+// the company ID of Microsoft, and the beacon ID and the scenarios of Swift
+// Pair in its manufacturer data
+const MICROSOFT: u16 = 0x0006;
+const SWIFT_PAIR_BEACON: u8 = 0x03;
+const SWIFT_PAIR_SCENARIOS: [u8; 3] = [0x00, 0x01, 0x02];
+
+/// How a device tells it's ready to pair, in pairing mode and not just
+/// discoverable.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PairingAdvertisement {
+    /// Google Fast Pair.
+    FastPair,
+    /// Microsoft Swift Pair.
+    SwiftPair,
+}
+
+impl PairingAdvertisement {
+    /// Whether the service data has Fast Pair in pairing mode. Out of it the
+    /// data is the filter of the accounts the device is paired with instead.
+    pub fn is_fast_pair(service_data: &HashMap<Uuid, Vec<u8>>) -> bool {
+        service_data
+            .get(&Uuid::from_u128(FAST_PAIR_UUID))
+            .is_some_and(|data| data.len() == FAST_PAIR_MODEL_ID_LEN)
+    }
+
+    /// Whether the manufacturer data has a Swift Pair beacon, which is only
+    /// sent in pairing mode.
+    pub fn is_swift_pair(manufacturer_data: &HashMap<u16, Vec<u8>>) -> bool {
+        manufacturer_data.get(&MICROSOFT).is_some_and(|data| {
+            data.len() >= 2
+                && data[0] == SWIFT_PAIR_BEACON
+                && SWIFT_PAIR_SCENARIOS.contains(&data[1])
+        })
+    }
+}
+
+// the major class of headsets, speakers and other audio devices
+const AUDIO_VIDEO_MAJOR_CLASS: u32 = 0x04;
+
+// A2DP sink, headset, hands-free and the LE Audio capabilities
+const AUDIO_UUIDS: [u128; 4] = [
+    0x0000110b_0000_1000_8000_00805f9b34fb,
+    0x00001108_0000_1000_8000_00805f9b34fb,
+    0x0000111e_0000_1000_8000_00805f9b34fb,
+    0x00001850_0000_1000_8000_00805f9b34fb,
+];
+
+// the network access point of the PAN, offered by phones sharing their
+// internet
+const NAP_UUID: u128 = 0x00001116_0000_1000_8000_00805f9b34fb;
+
+// the audio of most headsets starts dropping out below the fair signal
+const GOOD_RSSI: i16 = -65;
+const FAIR_RSSI: i16 = -80;
+
+// the RSSI of a connection is told in dB under the range the controller wants,
+// which starts about at the good signal, so the fair one is this far under it
+const FAIR_LINK_RSSI: i8 = (FAIR_RSSI - GOOD_RSSI) as i8;
+
+impl DeviceInfo {
+    /// Whether the device can play audio, from its services and class, `None`
+    /// when it tells neither. Phones and computers advertise audio sources
+    /// but not sinks, so they aren't audio devices.
+    pub fn is_audio(&self) -> Option<bool> {
+        if self
+            .uuids
+            .iter()
+            .any(|uuid| AUDIO_UUIDS.contains(&uuid.as_u128()))
+        {
+            return Some(true);
+        }
+        if let Some(class) = self.class {
+            return Some((class >> 8) & 0x1f == AUDIO_VIDEO_MAJOR_CLASS);
+        }
+        (!self.uuids.is_empty()).then_some(false)
+    }
+
+    /// Whether the device shares its internet over Bluetooth, as phones with
+    /// tethering turned on do.
+    pub fn offers_tethering(&self) -> bool {
+        self.uuids.iter().any(|uuid| uuid.as_u128() == NAP_UUID)
+    }
+
+    /// How well the audio will get through, from the signal strength.
+    pub fn link_quality(&self) -> Option<LinkQuality> {
+        let quality = match self.rssi? {
+            rssi if rssi >= GOOD_RSSI => LinkQuality::Good,
+            rssi if rssi >= FAIR_RSSI => LinkQuality::Fair,
+            _ => LinkQuality::Weak,
+        };
+        Some(quality)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LinkQuality {
+    Good,
+    Fair,
+    /// Weak enough for audio dropouts.
+    Weak,
+}
+
+/// The link of a connected device as its controller tells it, read now and
+/// then to match audio dropouts with link problems. `None` when the
+/// controller doesn't tell it.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct LinkStats {
+    /// The RSSI of the connection in dB, 0 inside the range the controller
+    /// wants, below it under.
+    pub rssi: Option<i8>,
+    /// The transmit power of the adapter on the connection, in dBm.
+    pub tx_power: Option<i8>,
+    /// The packets of the adapter, not only of this device.
+    pub packets: Option<PacketStats>,
+}
+
+impl LinkStats {
+    /// How well the audio gets through, from the RSSI of the connection.
+    pub fn link_quality(&self) -> Option<LinkQuality> {
+        let quality = match self.rssi? {
+            rssi if rssi >= 0 => LinkQuality::Good,
+            rssi if rssi >= FAIR_LINK_RSSI => LinkQuality::Fair,
+            _ => LinkQuality::Weak,
+        };
+        Some(quality)
+    }
+}
+
+/// The ACL and SCO packets of the adapter since it came up, of every device
+/// it's connected to.
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct PacketStats {
+    pub received: u64,
+    pub sent: u64,
+    /// The packets received or sent which failed.
+    pub errors: u64,
+}
+
+/// Version of bluetoothd, like 5.66.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub struct BluezVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl BluezVersion {
+    /// The oldest BlueZ with everything the connector uses, battery levels
+    /// come with `Battery1` of 5.48.
+    pub const MINIMUM: Self = Self {
+        major: 5,
+        minor: 48,
+    };
+
+    /// From the version of the device ID BlueZ gives its adapters, which is
+    /// `major << 8 | minor`.
+    pub fn from_device_id(version: u32) -> Self {
+        Self {
+            major: (version >> 8) as u8,
+            minor: version as u8,
+        }
+    }
+
+    pub fn is_outdated(self) -> bool {
+        self < Self::MINIMUM
+    }
+}
+
+impl fmt::Display for BluezVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Changes bluetoothd reports on its own, whoever caused them.
+#[derive(Debug, Clone)]
+pub enum BluetoothEvent {
+    /// A device became known, by a discovery of this or another program.
+    DeviceFound(DeviceInfo),
+    DeviceRemoved(Address),
+    Connected(Address),
+    Disconnected(Address),
+    BatteryChanged {
+        addr: Address,
+        battery: u8,
+    },
+    /// The audio stream or the media player of a device came, went or
+    /// changed, like a new track.
+    MediaChanged(Address),
+}
+
+/// How a device is set up by the connect workflow, chosen per device by the
+/// user.
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+    /// Connect the device at start, read by the frontend.
+    pub auto_connect: bool,
+    /// Mark the device trusted once paired, so it may reconnect by itself.
+    pub trust: bool,
+    pub profile: AudioProfile,
+    /// Volume in percent set once connected, `None` keeps the volume of the
+    /// device.
+    pub volume: Option<u8>,
+    /// The PIN a legacy device was paired with, tried first when pairing it
+    /// again.
+    pub pin: Option<String>,
+}
+
+/// The audio profile a device is connected with.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioProfile {
+    /// High quality playback, let BlueZ connect every profile of the device.
+    #[default]
+    A2dp,
+    /// Playback with the microphone, in phone call quality.
+    Hfp,
+}
+
+impl AudioProfile {
+    /// UUID of the profile on the headset side.
+    pub fn uuid(self) -> Uuid {
+        match self {
+            Self::A2dp => Uuid::from_u128(0x0000110b_0000_1000_8000_00805f9b34fb),
+            Self::Hfp => Uuid::from_u128(0x0000111e_0000_1000_8000_00805f9b34fb),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fast_pair_needs_a_model_id() {
+        let fast_pair = |data: &[u8]| {
+            let service_data = HashMap::from([(Uuid::from_u128(FAST_PAIR_UUID), data.to_vec())]);
+            PairingAdvertisement::is_fast_pair(&service_data)
+        };
+        assert!(fast_pair(&[0x0e, 0x30, 0xc3]));
+        // the account key data of a device already paired to a phone
+        assert!(!fast_pair(&[0x00, 0x11, 0x22, 0x33, 0x44]));
+        assert!(!PairingAdvertisement::is_fast_pair(&HashMap::new()));
+    }
+
+    #[test]
+    fn swift_pair_needs_its_beacon() {
+        let swift_pair = |company_id: u16, data: &[u8]| {
+            PairingAdvertisement::is_swift_pair(&HashMap::from([(company_id, data.to_vec())]))
+        };
+        assert!(swift_pair(MICROSOFT, &[0x03, 0x00, 0x80]));
+        assert!(swift_pair(MICROSOFT, &[0x03, 0x02]));
+        assert!(!swift_pair(MICROSOFT, &[0x03, 0x03]));
+        assert!(!swift_pair(MICROSOFT, &[0x01, 0x00]));
+        assert!(!swift_pair(MICROSOFT, &[0x03]));
+        assert!(!swift_pair(0x004c, &[0x03, 0x00]));
+    }
+
+    #[test]
+    fn link_quality_of_connections() {
+        let quality = |rssi| {
+            LinkStats {
+                rssi,
+                ..LinkStats::default()
+            }
+            .link_quality()
+        };
+        assert_eq!(quality(None), None);
+        assert_eq!(quality(Some(0)), Some(LinkQuality::Good));
+        assert_eq!(quality(Some(FAIR_LINK_RSSI)), Some(LinkQuality::Fair));
+        assert_eq!(quality(Some(FAIR_LINK_RSSI - 1)), Some(LinkQuality::Weak));
+    }
+
+    #[test]
+    fn link_quality_of_advertisements() {
+        let quality = |rssi| {
+            DeviceInfo {
+                rssi,
+                ..DeviceInfo::default()
+            }
+            .link_quality()
+        };
+        assert_eq!(quality(None), None);
+        assert_eq!(quality(Some(GOOD_RSSI)), Some(LinkQuality::Good));
+        assert_eq!(quality(Some(GOOD_RSSI - 1)), Some(LinkQuality::Fair));
+        assert_eq!(quality(Some(FAIR_RSSI - 1)), Some(LinkQuality::Weak));
+    }
+}
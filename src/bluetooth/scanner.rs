@@ -0,0 +1,36 @@
+use crate::{
+    bluetooth::{backend::BluetoothBackend, battery, DeviceInfo},
+    error::Result,
+};
+use std::{sync::Arc, time::Duration};
+
+pub const DEFAULT_SCAN_DURATION: Duration = Duration::from_secs(6);
+
+/// Discovers the devices nearby the adapter.
+pub struct Scanner {
+    backend: Arc<dyn BluetoothBackend>,
+    duration: Duration,
+}
+
+impl Scanner {
+    pub fn new(backend: Arc<dyn BluetoothBackend>) -> Self {
+        Self {
+            backend,
+            duration: DEFAULT_SCAN_DURATION,
+        }
+    }
+
+    /// How long a scan listens for devices.
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// The devices found, the connected earbuds get the batteries their
+    /// vendor advertises nearby.
+    pub async fn scan(&self) -> Result<Vec<DeviceInfo>> {
+        let mut devices = self.backend.scan(self.duration).await?;
+        battery::attach_nearby(&mut devices);
+        Ok(devices)
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{data::AppData, exit::ExitStatus, signals::QuitSignals};
+use anyhow::Context;
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{AudioProfile, BluetoothBackend, Connector, Error};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::time::{sleep, Instant};
+use tracing::{info, warn};
+
+// the pause after each connect and disconnect, headsets refuse a connect
+// coming right after a disconnect
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+const CSV_HEADER: &str = "run,connected,connect_ms,disconnect_ms,error";
+
+/// `--burn-in`: connect and disconnect the device `runs` times, writing a row
+/// of timings to the CSV file for each run. For checking firmware and BlueZ
+/// upgrades, the exit code is `ConnectFailed` when a run failed.
+pub async fn run(
+    backend: Arc<dyn BluetoothBackend>, addr: Address, runs: u32, csv: &Path,
+    mut signals: QuitSignals,
+) -> anyhow::Result<ExitStatus> {
+    let file = File::create(csv).with_context(|| format!("create {}", csv.display()))?;
+    let mut csv = BufWriter::new(file);
+    writeln!(csv, "{CSV_HEADER}")?;
+
+    if !backend.is_powered().await? {
+        info!("open bluetooth");
+        backend.set_powered(true).await?;
+    }
+
+    let settings = AppData::load(&AppData::path()).settings(addr);
+    let connector = Connector::new(backend);
+    connector.pair(addr, settings.pin.as_deref()).await?;
+
+    info!(%addr, runs, "start burn-in");
+    let mut connected = 0;
+    let mut connect_times = Duration::ZERO;
+    for run in 1..=runs {
+        let result = tokio::select! {
+            result = cycle(&connector, addr, settings.profile) => result,
+            name = signals.recv() => {
+                info!(signal = name, run, "burn-in cancelled by signal");
+                csv.flush()?;
+                return Ok(ExitStatus::Cancelled);
+            }
+        };
+
+        let row = match result {
+            Ok((connect_time, disconnect_time)) => {
+                connected += 1;
+                connect_times += connect_time;
+                info!(run, ?connect_time, ?disconnect_time, "burn-in run passed");
+                format!(
+                    "{run},true,{},{},",
+                    connect_time.as_millis(),
+                    disconnect_time.as_millis()
+                )
+            }
+            Err((connect_time, err)) => {
+                warn!(run, ?err, "burn-in run failed");
+                // the error may have commas or quotes of its own
+                let err = err.to_string().replace('"', "\"\"");
+                format!("{run},false,{},,\"{err}\"", connect_time.as_millis())
+            }
+        };
+        writeln!(csv, "{row}")?;
+        // a crash keeps the rows written so far
+        csv.flush()?;
+    }
+
+    let mean = connect_times.checked_div(connected).unwrap_or_default();
+    info!(connected, runs, ?mean, "burn-in done");
+    println!(
+        "{connected}/{runs} connected, {:.1}% success, mean connect time {} ms",
+        f64::from(connected) * 100.0 / f64::from(runs.max(1)),
+        mean.as_millis()
+    );
+
+    if connected == runs {
+        Ok(ExitStatus::Success)
+    } else {
+        Ok(ExitStatus::ConnectFailed)
+    }
+}
+
+/// Connect and then disconnect the device, the times of both or the time until
+/// the connect failed.
+async fn cycle(
+    connector: &Connector, addr: Address, profile: AudioProfile,
+) -> Result<(Duration, Duration), (Duration, Error)> {
+    let start = Instant::now();
+    let result = async {
+        connector.connect(addr, profile).await?;
+        connector.verify(addr).await
+    }
+    .await;
+    let connect_time = start.elapsed();
+    if let Err(err) = result {
+        // a half open connection would fail the next run too
+        if let Err(err) = connector.disconnect(addr).await {
+            warn!(?err, %addr, "disconnect after failed connect failed");
+        }
+        sleep(SETTLE_TIME).await;
+        return Err((connect_time, err));
+    }
+    sleep(SETTLE_TIME).await;
+
+    let start = Instant::now();
+    let result = connector.disconnect(addr).await;
+    let disconnect_time = start.elapsed();
+    sleep(SETTLE_TIME).await;
+    match result {
+        Ok(()) => Ok((connect_time, disconnect_time)),
+        Err(err) => Err((connect_time, err)),
+    }
+}
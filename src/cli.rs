@@ -0,0 +1,280 @@
+use crate::{
+    burn_in,
+    config::{Args, Config},
+    data::{AppData, SavedDevice},
+    exit::ExitStatus,
+    signals::QuitSignals,
+};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{
+    bluetooth::scanner::Scanner, AppState, BluetoothBackend, BluetoothEvent, BluetoothManager,
+    Command, DeviceInfo, ExitPolicy, CONNECT_TIMEOUT,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::{sync::oneshot, time::timeout};
+use tracing::{error, info, warn};
+
+#[derive(Serialize)]
+pub struct DeviceOutput<'a> {
+    address: String,
+    name: &'a str,
+    paired: bool,
+    connected: bool,
+    rssi: Option<i16>,
+    battery: Option<u8>,
+    pairing_mode: bool,
+}
+
+impl<'a> From<&'a DeviceInfo> for DeviceOutput<'a> {
+    fn from(info: &'a DeviceInfo) -> Self {
+        Self {
+            address: info.addr.to_string(),
+            name: &info.name,
+            paired: info.paired,
+            connected: info.connected,
+            rssi: info.rssi,
+            battery: info.battery,
+            pairing_mode: info.pairing_advertisement.is_some(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum EventOutput<'a> {
+    DeviceFound(DeviceOutput<'a>),
+    DeviceRemoved { address: String },
+    Connected { address: String },
+    Disconnected { address: String },
+    BatteryChanged { address: String, battery: u8 },
+    MediaChanged { address: String },
+}
+
+impl<'a> From<&'a BluetoothEvent> for EventOutput<'a> {
+    fn from(event: &'a BluetoothEvent) -> Self {
+        match event {
+            BluetoothEvent::DeviceFound(info) => Self::DeviceFound(info.into()),
+            BluetoothEvent::DeviceRemoved(addr) => Self::DeviceRemoved {
+                address: addr.to_string(),
+            },
+            BluetoothEvent::Connected(addr) => Self::Connected {
+                address: addr.to_string(),
+            },
+            BluetoothEvent::Disconnected(addr) => Self::Disconnected {
+                address: addr.to_string(),
+            },
+            BluetoothEvent::BatteryChanged { addr, battery } => Self::BatteryChanged {
+                address: addr.to_string(),
+                battery: *battery,
+            },
+            BluetoothEvent::MediaChanged(addr) => Self::MediaChanged {
+                address: addr.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct StatusOutput<'a> {
+    powered: bool,
+    connected: Option<DeviceOutput<'a>>,
+}
+
+impl<'a> From<&'a AppState> for StatusOutput<'a> {
+    fn from(state: &'a AppState) -> Self {
+        Self {
+            powered: state.powered,
+            connected: state.connected_device().map(DeviceOutput::from),
+        }
+    }
+}
+
+/// Save `--nickname`, it needs no Bluetooth.
+pub fn set_nickname(address: &str, name: &str) -> anyhow::Result<ExitStatus> {
+    let addr: Address = address.parse()?;
+
+    let path = AppData::path();
+    let mut data = AppData::load(&path);
+    if name.is_empty() {
+        data.nicknames.remove(&addr.to_string());
+    } else {
+        data.nicknames.insert(addr.to_string(), name.to_string());
+    }
+    data.save(&path)?;
+
+    info!(%addr, name, "set nickname");
+    Ok(ExitStatus::Success)
+}
+
+/// Answer `--list`, `--status`, `--watch`, `--connect`, `--reconnect-last` or
+/// `--burn-in` without the UI.
+pub async fn run(
+    args: &Args, config: &Config, backend: Arc<dyn BluetoothBackend>, mut signals: QuitSignals,
+) -> anyhow::Result<ExitStatus> {
+    if let Some(addr) = args.burn_in {
+        return burn_in::run(backend, addr, args.burn_in_runs, &args.burn_in_csv, signals).await;
+    }
+    if args.reconnect_last {
+        let Some(addr) = reconnect_target(config) else {
+            warn!("no device to reconnect");
+            return Ok(ExitStatus::NoDevice);
+        };
+        info!(%addr, "reconnect last device");
+        let status = tokio::select! {
+            status = connect(backend.clone(), config, addr) => status?,
+            name = signals.recv() => {
+                info!(signal = name, "reconnect cancelled by signal");
+                return Ok(ExitStatus::Cancelled);
+            }
+        };
+        // the reconnect is what the exit code tells
+        if let Err(err) = print_current_status(backend.as_ref(), args.json).await {
+            warn!(?err, "print status after reconnect failed");
+        }
+        Ok(status)
+    } else if let Some(addr) = args.connect {
+        tokio::select! {
+            status = connect(backend, config, addr) => status,
+            name = signals.recv() => {
+                info!(signal = name, "connect cancelled by signal");
+                Ok(ExitStatus::Cancelled)
+            }
+        }
+    } else if args.watch {
+        let mut events = backend.events().await?;
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Some(event) => println!("{}", serde_json::to_string(&EventOutput::from(&event))?),
+                    None => break,
+                },
+                name = signals.recv() => {
+                    info!(signal = name, "stop watching by signal");
+                    break;
+                }
+            }
+        }
+        Ok(ExitStatus::Success)
+    } else if args.list {
+        let mut devices = Scanner::new(backend)
+            .duration(config.scan_duration())
+            .scan()
+            .await?;
+        let hidden = config.hidden_devices();
+        devices.retain(|info| info.connected || !hidden.contains(&info.addr));
+        print_list(&devices, args.json)?;
+        Ok(ExitStatus::Success)
+    } else {
+        print_current_status(backend.as_ref(), args.json).await?;
+        Ok(ExitStatus::Success)
+    }
+}
+
+/// The device connected last, or the preferred one without a last device.
+fn reconnect_target(config: &Config) -> Option<Address> {
+    AppData::load(&AppData::path())
+        .last_device
+        .as_ref()
+        .and_then(SavedDevice::addr)
+        .or_else(|| config.preferred_device())
+}
+
+/// Power on and connect the device through a manager, like the app does,
+/// unless it's connected already. Fails after `CONNECT_TIMEOUT`.
+async fn connect(
+    backend: Arc<dyn BluetoothBackend>, config: &Config, addr: Address,
+) -> anyhow::Result<ExitStatus> {
+    let path = AppData::path();
+    let mut data = AppData::load(&path);
+    let mut options = config.manager_options(&data);
+    // connected by the command, which answers how it ended
+    options.auto_connect = None;
+    options.scan_at_start = false;
+    options.exit_policy = ExitPolicy::KeepConnected;
+    let manager = BluetoothManager::spawn(backend, options);
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let command = Command::ConnectAndReply {
+        addr,
+        ensure: true,
+        reply_tx,
+    };
+    manager.command_tx.send(command).await?;
+    let reply = timeout(CONNECT_TIMEOUT, reply_rx).await;
+
+    let paired_pin = manager.state_rx.borrow().paired_pin.clone();
+    if let Some((addr, pin)) = paired_pin {
+        if data.set_pin(addr, pin) {
+            if let Err(err) = data.save(&path) {
+                warn!(?err, %addr, "save pin failed");
+            }
+        }
+    }
+    // stops a connect given up
+    manager.shutdown().await;
+
+    match reply {
+        Ok(Ok(Ok(()))) => {
+            info!(%addr, "device connected");
+            Ok(ExitStatus::Success)
+        }
+        Ok(Ok(Err(err))) => {
+            error!(?err, %addr, "connect device failed");
+            Ok(ExitStatus::ConnectFailed)
+        }
+        Ok(Err(_)) => anyhow::bail!("bluetooth manager stopped"),
+        Err(_) => {
+            error!(%addr, timeout = ?CONNECT_TIMEOUT, "connect took too long, give up");
+            Ok(ExitStatus::ConnectFailed)
+        }
+    }
+}
+
+fn print_list(devices: &[DeviceInfo], json: bool) -> anyhow::Result<()> {
+    if json {
+        let output = devices.iter().map(DeviceOutput::from).collect::<Vec<_>>();
+        println!("{}", serde_json::to_string(&output)?);
+        return Ok(());
+    }
+
+    for info in devices {
+        let mut flags = Vec::new();
+        if info.paired {
+            flags.push("paired");
+        }
+        if info.connected {
+            flags.push("connected");
+        }
+        println!("{}\t{}\t{}", info.addr, flags.join(","), info.name);
+    }
+    Ok(())
+}
+
+async fn print_current_status(backend: &dyn BluetoothBackend, json: bool) -> anyhow::Result<()> {
+    let powered = backend.is_powered().await?;
+    let devices = if powered {
+        backend.known_devices().await?
+    } else {
+        Vec::new()
+    };
+    print_status(powered, devices.iter().find(|info| info.connected), json)
+}
+
+fn print_status(powered: bool, connected: Option<&DeviceInfo>, json: bool) -> anyhow::Result<()> {
+    if json {
+        let output = StatusOutput {
+            powered,
+            connected: connected.map(DeviceOutput::from),
+        };
+        println!("{}", serde_json::to_string(&output)?);
+        return Ok(());
+    }
+
+    println!("powered: {}", if powered { "yes" } else { "no" });
+    match connected {
+        Some(info) => println!("connected: {} {}", info.addr, info.name),
+        None => println!("connected: none"),
+    }
+    Ok(())
+}
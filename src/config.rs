@@ -0,0 +1,682 @@
+use crate::{
+    data::AppData,
+    files,
+    input::{Direction, Keymap},
+    logging,
+    migrate::{self, Migration},
+    paths, status_file,
+    ui::{BACKGROUND_FADE, FONT_SIZE, LARGE_FONT_SIZE, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+use anyhow::Context;
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{
+    bluetooth::scanner::DEFAULT_SCAN_DURATION, AudioProfile, ExitPolicy, ManagerOptions,
+};
+use clap::{Parser, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{sync::watch, time::interval};
+use tracing::{info, warn};
+
+const CONFIG_FILE: &str = "config.toml";
+
+// the migration from version `n` of the config is at index `n`
+const MIGRATIONS: &[Migration] = &[migrate::add_version];
+
+// how often the config file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Parser, Clone)]
+#[command(version, about)]
+pub struct Args {
+    /// Configuration file, created with the defaults when missing [default:
+    /// config.toml next to the binary]
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Use a larger font and wrap long hints, for TV docking or low vision.
+    #[arg(long)]
+    pub large_text: bool,
+
+    /// Rotate the whole UI clockwise, for panels mounted rotated.
+    #[arg(long, value_enum)]
+    pub rotation: Option<Rotation>,
+
+    /// Image drawn behind the UI, to match custom launcher skins.
+    #[arg(long)]
+    pub background: Option<PathBuf>,
+
+    /// Use fake devices instead of the Bluetooth adapter, for UI development.
+    #[arg(long)]
+    pub simulate: bool,
+
+    /// Run without the UI, keeping Bluetooth on and the preferred device
+    /// connected. Control commands are also read from stdin.
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Scan and print the devices found, then exit.
+    #[arg(long, group = "query")]
+    pub list: bool,
+
+    /// Print whether Bluetooth is on and the connected device, then exit.
+    #[arg(long, group = "query")]
+    pub status: bool,
+
+    /// Print every Bluetooth event as a line of JSON until quit, devices are
+    /// only found while something else is scanning.
+    #[arg(long, group = "query")]
+    pub watch: bool,
+
+    /// Connect the device and exit, the exit code tells the result.
+    #[arg(long, group = "query", value_name = "ADDRESS")]
+    pub connect: Option<Address>,
+
+    /// Connect the device connected last, or else the preferred one, print
+    /// the status and exit. The exit code tells the result, for a launcher
+    /// shortcut.
+    #[arg(long, group = "query")]
+    pub reconnect_last: bool,
+
+    /// Connect the preferred device, or disconnect it when it's connected,
+    /// show the result for a moment and exit. For a launcher entry which
+    /// acts like a Bluetooth toggle.
+    #[arg(long, conflicts_with_all = ["query", "daemon"])]
+    pub toggle: bool,
+
+    /// Connect the device without the device list, showing only the progress
+    /// and the result. For a launcher entry of each device.
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["query", "daemon", "toggle"])]
+    pub device: Option<Address>,
+
+    /// Connect and disconnect the device again and again, writing the timings
+    /// to a CSV file. For testing firmware and BlueZ upgrades.
+    #[arg(long, group = "query", value_name = "ADDRESS", hide = true)]
+    pub burn_in: Option<Address>,
+
+    /// How many times `--burn-in` connects the device.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 50,
+        requires = "burn_in",
+        hide = true
+    )]
+    pub burn_in_runs: u32,
+
+    /// The CSV file `--burn-in` writes.
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "burn-in.csv",
+        requires = "burn_in",
+        hide = true
+    )]
+    pub burn_in_csv: PathBuf,
+
+    /// Print `--list`, `--status` and `--reconnect-last` as JSON, for
+    /// launcher scripts.
+    #[arg(long, requires = "query")]
+    pub json: bool,
+
+    /// Where the daemon serves its metrics.
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "ADDR", default_value = "0.0.0.0:9101")]
+    pub metrics_addr: std::net::SocketAddr,
+
+    /// Draw the UI in the terminal instead of a window.
+    #[cfg(feature = "tui")]
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Serve a remote control page, e.g. `0.0.0.0:8080` for phones on the
+    /// same Wi-Fi.
+    #[cfg(feature = "web")]
+    #[arg(long, value_name = "ADDR")]
+    pub web: Option<std::net::SocketAddr>,
+
+    /// Give a device a name of your own, an empty name removes it.
+    #[arg(long, group = "query", num_args = 2, value_names = ["ADDRESS", "NAME"])]
+    pub nickname: Option<Vec<String>>,
+
+    /// Save the nicknames, favorites and preferred device to a JSON file.
+    #[arg(long, group = "query", value_name = "FILE")]
+    pub export: Option<PathBuf>,
+
+    /// Restore a file written by `--export`, merging it into the current
+    /// settings.
+    #[arg(long, group = "query", value_name = "FILE")]
+    pub import: Option<PathBuf>,
+
+    /// Start with the remap wizard, for handhelds whose buttons don't match
+    /// the default layout.
+    #[arg(long)]
+    pub remap: bool,
+
+    /// Disconnect the connected device when quitting, instead of leaving it
+    /// playing the audio of the launcher.
+    #[arg(long)]
+    pub disconnect_on_exit: bool,
+}
+
+impl Args {
+    pub fn config_path(&self) -> PathBuf {
+        match &self.config {
+            Some(path) => path.clone(),
+            None => paths::config_dir().join(CONFIG_FILE),
+        }
+    }
+
+    /// Whether the run prints a result to stdout instead of showing the UI.
+    pub fn is_query(&self) -> bool {
+        self.list
+            || self.status
+            || self.watch
+            || self.connect.is_some()
+            || self.reconnect_last
+            || self.burn_in.is_some()
+            || self.nickname.is_some()
+            || self.export.is_some()
+            || self.import.is_some()
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn is_tui(&self) -> bool {
+        self.tui
+    }
+
+    #[cfg(not(feature = "tui"))]
+    pub fn is_tui(&self) -> bool {
+        false
+    }
+
+    /// The flags given on the command line win over the config file.
+    pub fn apply(&self, config: &mut Config) {
+        if self.large_text {
+            config.large_text = true;
+        }
+        if let Some(rotation) = self.rotation {
+            config.rotation = rotation;
+        }
+        if let Some(background) = &self.background {
+            config.background = Some(background.clone());
+        }
+        if self.disconnect_on_exit {
+            config.exit_policy = ExitPolicy::Disconnect;
+        }
+    }
+}
+
+/// Settings read from `config.toml`, every key is optional.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Format of the file, upgraded when it's read by a newer release.
+    pub version: u32,
+    /// How long a scan listens for devices.
+    pub scan_duration_secs: u64,
+    /// Rescan quietly after the device list was left alone this long with
+    /// nothing connected, 0 turns it off.
+    pub idle_rescan_secs: u64,
+    /// What is logged, in the syntax of `RUST_LOG` which wins over it, e.g.
+    /// `debug,bluer=warn` to quiet bluer.
+    pub log_filter: String,
+    pub theme: Theme,
+    pub language: Language,
+    /// What happens to the connection when quitting.
+    pub exit_policy: ExitPolicy,
+    /// Connect the device again when it was lost while the handheld slept.
+    pub reconnect_on_resume: bool,
+    /// Change the volume of the connected device with the volume keys of the
+    /// handheld, which otherwise only change the speaker.
+    pub forward_volume_keys: bool,
+    /// Address of the device the cursor starts on after a scan.
+    pub preferred_device: Option<String>,
+    /// Connect the preferred device right at start, without the device list.
+    pub auto_connect: bool,
+    /// Addresses left out of the scan results, like the TV of a neighbor.
+    pub hidden_devices: Vec<String>,
+    /// Leave the devices without a name out of the list, unless they're
+    /// paired.
+    pub hide_unnamed_devices: bool,
+    /// Devices listed after a scan, the ones with a weaker signal are behind
+    /// a last row which lists them, 0 lists every device.
+    pub max_listed_devices: usize,
+    /// Put the address after the name of each device in the list, for
+    /// devices with the same name.
+    pub show_addresses: bool,
+    /// Named pipe the connection and battery events are written to as
+    /// `KEY=VALUE` lines, for shell scripts. Not created when unset.
+    pub event_fifo: Option<PathBuf>,
+    /// Kept with the status for launcher overlays while the app runs, another
+    /// one for each when several run.
+    pub status_file: PathBuf,
+    /// Broker the status is published to, with the `mqtt` feature.
+    pub mqtt: Option<MqttConfig>,
+    /// The web remote of `--web`, with the `web` feature.
+    pub web: Option<WebConfig>,
+    pub font: PathBuf,
+    pub font_size: u16,
+    pub large_font_size: u16,
+    pub large_text: bool,
+    pub rotation: Rotation,
+    pub background: Option<PathBuf>,
+    /// Alpha of the layer drawn over the background image.
+    pub background_fade: u8,
+    /// SDL game controller mappings, one per line of `gamecontrollerdb.txt`,
+    /// for built-in controllers SDL maps wrong. Applied over the mapping
+    /// files.
+    pub controller_mappings: Vec<String>,
+    /// Devices with their audio settings, picked from the profiles menu.
+    pub profiles: Vec<ConnectionProfile>,
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: MIGRATIONS.len() as u32,
+            scan_duration_secs: DEFAULT_SCAN_DURATION.as_secs(),
+            idle_rescan_secs: 30,
+            log_filter: logging::DEFAULT_FILTER.to_string(),
+            theme: Theme::Light,
+            language: Language::Zh,
+            exit_policy: ExitPolicy::KeepConnected,
+            reconnect_on_resume: true,
+            forward_volume_keys: true,
+            preferred_device: None,
+            auto_connect: false,
+            hidden_devices: Vec::new(),
+            hide_unnamed_devices: true,
+            max_listed_devices: 20,
+            show_addresses: false,
+            event_fifo: None,
+            status_file: PathBuf::from(status_file::STATUS_PATH),
+            mqtt: None,
+            web: None,
+            font: PathBuf::from("wqy-microhei.ttc"),
+            font_size: FONT_SIZE,
+            large_font_size: LARGE_FONT_SIZE,
+            large_text: false,
+            rotation: Rotation::Deg0,
+            background: None,
+            background_fade: BACKGROUND_FADE,
+            controller_mappings: Vec::new(),
+            profiles: Vec::new(),
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file, writing the defaults to it on first run.
+    pub fn load_or_create(path: &Path) -> anyhow::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                Self::parse(path, &content).with_context(|| format!("parse {}", path.display()))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                let config = Self::default();
+                match config.save(path) {
+                    Ok(()) => info!(?path, "create default config"),
+                    Err(err) => warn!(?path, ?err, "save default config failed"),
+                }
+                Ok(config)
+            }
+            Err(err) => Err(err).with_context(|| format!("read {}", path.display())),
+        }
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<Self, toml::de::Error> {
+        let mut table = content.parse::<toml::Table>()?;
+        migrate::migrate(path, &mut table, MIGRATIONS);
+        toml::Value::Table(table).try_into()
+    }
+
+    /// Like `load_or_create`, but a file that doesn't parse, such as one cut
+    /// by a power loss, is set aside and replaced by the defaults. Only for the
+    /// start, a reload keeps the current config instead.
+    pub fn load_or_recover(path: &Path) -> anyhow::Result<Self> {
+        match Self::load_or_create(path) {
+            Err(err) if err.downcast_ref::<toml::de::Error>().is_some() => {
+                warn!(?err, "parse config failed, start with the defaults");
+                let broken_path = files::set_aside(path)
+                    .with_context(|| format!("set aside {}", path.display()))?;
+                info!(?broken_path, "set aside broken config");
+                Self::load_or_create(path)
+            }
+            result => result,
+        }
+    }
+
+    /// Change the file itself, without the overrides of the command line, the
+    /// reloader publishes the result.
+    pub fn edit(path: &Path, f: impl FnOnce(&mut Config)) -> anyhow::Result<()> {
+        let mut config = Self::load_or_create(path)?;
+        f(&mut config);
+        config.save(path)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        files::write_atomic(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn font_size(&self) -> u16 {
+        if self.large_text {
+            self.large_font_size
+        } else {
+            self.font_size
+        }
+    }
+
+    pub fn scan_duration(&self) -> Duration {
+        Duration::from_secs(self.scan_duration_secs)
+    }
+
+    pub fn idle_rescan(&self) -> Option<Duration> {
+        (self.idle_rescan_secs > 0).then(|| Duration::from_secs(self.idle_rescan_secs))
+    }
+
+    /// The options of the manager, with the per device settings kept in the
+    /// app data.
+    pub fn manager_options(&self, data: &AppData) -> ManagerOptions {
+        let preferred = if self.auto_connect {
+            self.preferred_device()
+        } else {
+            None
+        };
+        ManagerOptions {
+            scan_duration: self.scan_duration(),
+            exit_policy: self.exit_policy,
+            auto_connect: preferred.or_else(|| data.auto_connect_device()),
+            device_settings: data.parsed_device_settings(),
+            hidden: self.hidden_devices(),
+            hide_unnamed: self.hide_unnamed_devices,
+            reconnect_on_resume: self.reconnect_on_resume,
+            scan_at_start: true,
+        }
+    }
+
+    pub fn hidden_devices(&self) -> HashSet<Address> {
+        self.hidden_devices
+            .iter()
+            .filter_map(|addr| addr.parse().ok())
+            .collect()
+    }
+
+    pub fn preferred_device(&self) -> Option<Address> {
+        let addr = self.preferred_device.as_deref()?;
+        match addr.parse() {
+            Ok(addr) => Some(addr),
+            Err(err) => {
+                warn!(addr, %err, "invalid preferred device");
+                None
+            }
+        }
+    }
+}
+
+/// Check the modification time of the config file and publish it again when
+/// it changes, so settings can be tweaked without restarting. The command line
+/// flags still win over the file.
+pub fn spawn_reloader(path: PathBuf, args: Args, config: Config) -> watch::Receiver<Config> {
+    let (config_tx, config_rx) = watch::channel(config);
+
+    tokio::spawn(async move {
+        let mut modified = modified_time(&path);
+        let mut interval = interval(RELOAD_INTERVAL);
+
+        while !config_tx.is_closed() {
+            interval.tick().await;
+
+            let current = modified_time(&path);
+            if current == modified {
+                continue;
+            }
+            modified = current;
+
+            match Config::load_or_create(&path) {
+                Ok(mut config) => {
+                    args.apply(&mut config);
+                    info!(?path, "reload config");
+                    config_tx.send_replace(config);
+                }
+                Err(err) => {
+                    warn!(?err, "reload config failed, keep the current one");
+                }
+            }
+        }
+    });
+
+    config_rx
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// A device and how to play through it, like "TV Speaker" or "Earbuds", applied
+/// together when picked from the profiles menu.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ConnectionProfile {
+    pub name: String,
+    pub address: String,
+    /// Volume in percent set once connected, unset keeps the device volume.
+    #[serde(default)]
+    pub volume: Option<u8>,
+    #[serde(default)]
+    pub profile: AudioProfile,
+    #[serde(default)]
+    pub latency: LatencyHint,
+}
+
+impl ConnectionProfile {
+    pub fn addr(&self) -> Option<Address> {
+        self.address.parse().ok()
+    }
+}
+
+/// The `[mqtt]` section, the status is published to `<topic>/status`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MqttConfig {
+    /// `host` or `host:port`, an IPv6 address with a port in brackets like
+    /// `[::1]:1883`.
+    pub broker: String,
+    #[serde(default = "default_mqtt_topic")]
+    pub topic: String,
+    #[serde(default = "default_mqtt_client_id")]
+    pub client_id: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+// the config is logged, the password isn't
+impl fmt::Debug for MqttConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MqttConfig")
+            .field("broker", &self.broker)
+            .field("topic", &self.topic)
+            .field("client_id", &self.client_id)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The `[web]` section, read at start.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebConfig {
+    /// Wanted as `Authorization: Bearer <token>` by the actions of the web
+    /// remote, the page takes it from its address as `?token=<token>`.
+    pub token: String,
+}
+
+// the config is logged, the token isn't
+impl fmt::Debug for WebConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebConfig").finish_non_exhaustive()
+    }
+}
+
+fn default_mqtt_topic() -> String {
+    "tg5040/bluetooth".to_string()
+}
+
+fn default_mqtt_client_id() -> String {
+    "bt-audio-connector".to_string()
+}
+
+/// Handed to the hooks as `BT_LATENCY_HINT`, for scripts sizing the audio
+/// buffer: low for games, high for music on a weak link.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LatencyHint {
+    #[default]
+    Normal,
+    Low,
+    High,
+}
+
+impl LatencyHint {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LatencyHint::Normal => "normal",
+            LatencyHint::Low => "low",
+            LatencyHint::High => "high",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Light => Self::Dark,
+            Self::Dark => Self::Light,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Zh,
+    En,
+}
+
+impl Language {
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Zh => Self::En,
+            Self::En => Self::Zh,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum, Serialize, Deserialize)]
+pub enum Rotation {
+    #[value(name = "0")]
+    #[serde(rename = "0")]
+    Deg0,
+    #[value(name = "90")]
+    #[serde(rename = "90")]
+    Deg90,
+    #[value(name = "180")]
+    #[serde(rename = "180")]
+    Deg180,
+    #[value(name = "270")]
+    #[serde(rename = "270")]
+    Deg270,
+}
+
+impl Rotation {
+    pub fn angle(self) -> f64 {
+        match self {
+            Rotation::Deg0 => 0.,
+            Rotation::Deg90 => 90.,
+            Rotation::Deg180 => 180.,
+            Rotation::Deg270 => 270.,
+        }
+    }
+
+    /// Size of the UI before rotating, the panel is portrait for 90/270.
+    pub fn logical_size(self) -> (u32, u32) {
+        match self {
+            Rotation::Deg0 | Rotation::Deg180 => (SCREEN_WIDTH, SCREEN_HEIGHT),
+            Rotation::Deg90 | Rotation::Deg270 => (SCREEN_HEIGHT, SCREEN_WIDTH),
+        }
+    }
+
+    /// Map a pressed D-pad direction to the direction on the rotated UI.
+    pub fn map_direction(self, direction: Direction) -> Direction {
+        let turns = match self {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => 3,
+            Rotation::Deg180 => 2,
+            Rotation::Deg270 => 1,
+        };
+        Direction::from_index((direction as usize + turns) % 4)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIRECTIONS: [Direction; 4] = [
+        Direction::Up,
+        Direction::Right,
+        Direction::Down,
+        Direction::Left,
+    ];
+
+    #[test]
+    fn unrotated_directions_stay() {
+        for direction in DIRECTIONS {
+            assert_eq!(Rotation::Deg0.map_direction(direction), direction);
+        }
+    }
+
+    #[test]
+    fn upside_down_directions_flip() {
+        assert_eq!(
+            Rotation::Deg180.map_direction(Direction::Up),
+            Direction::Down
+        );
+        assert_eq!(
+            Rotation::Deg180.map_direction(Direction::Left),
+            Direction::Right
+        );
+    }
+
+    #[test]
+    fn quarter_turns_undo_each_other() {
+        assert_eq!(
+            Rotation::Deg90.map_direction(Direction::Up),
+            Direction::Left
+        );
+        assert_eq!(
+            Rotation::Deg270.map_direction(Direction::Up),
+            Direction::Right
+        );
+        for direction in DIRECTIONS {
+            let turned = Rotation::Deg90.map_direction(direction);
+            assert_eq!(Rotation::Deg270.map_direction(turned), direction);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{
+    bluetooth::{
+        manager::{Command, CONNECT_TIMEOUT},
+        BluetoothConnectState, BluetoothScanStatus,
+    },
+    state::AppState,
+};
+use bluer::Address;
+use std::{fs, io, os::unix::net, path::PathBuf, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc, oneshot, watch},
+    task::JoinHandle,
+    time::{sleep, timeout},
+};
+use tracing::{debug, error, info};
+
+pub const SOCKET_PATH: &str = "/tmp/bt-audio-connector.sock";
+
+// an accept failing like on EMFILE fails again at once, until a client or
+// another descriptor is closed
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Line based control socket for scripts and other paks, one command per line
+/// and one reply per line:
+///
+/// - `status`: `powered=<bool> scan=<status> connect=<state> [device=<addr>
+///   name=<name>]`
+/// - `scan`, `disconnect`: `ok` once the command is queued
+/// - `cancel`: `ok` once the running connect is asked to stop
+/// - `connect <addr>`: `ok` or `error <reason>` once the connect has finished,
+///   rejected while another connect runs
+///
+/// Refuses to start while another server answers on the path. The socket file
+/// is removed when the server is dropped.
+pub struct ControlServer {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl ControlServer {
+    pub fn spawn(
+        path: impl Into<PathBuf>, command_tx: mpsc::Sender<Command>,
+        state_rx: watch::Receiver<AppState>,
+    ) -> io::Result<Self> {
+        let path = path.into();
+
+        if net::UnixStream::connect(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("another connector serves {}", path.display()),
+            ));
+        }
+        // the socket of a killed run would make the bind fail
+        if fs::remove_file(&path).is_ok() {
+            info!(path = %path.display(), "removed stale control socket");
+        }
+        let listener = UnixListener::bind(&path)?;
+
+        let task = tokio::spawn(accept(listener, command_tx, state_rx));
+        Ok(Self { path, task })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+async fn accept(
+    listener: UnixListener, command_tx: mpsc::Sender<Command>, state_rx: watch::Receiver<AppState>,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve(stream, command_tx.clone(), state_rx.clone()));
+            }
+            Err(err) => {
+                error!(?err, "accept control client failed");
+                sleep(ACCEPT_RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn serve(
+    stream: UnixStream, command_tx: mpsc::Sender<Command>, state_rx: watch::Receiver<AppState>,
+) {
+    let (reader, writer) = stream.into_split();
+    serve_lines(reader, writer, command_tx, state_rx).await;
+}
+
+/// Speak the control protocol over any pair of streams, until the reader
+/// ends.
+pub async fn serve_lines(
+    reader: impl AsyncRead + Unpin, mut writer: impl AsyncWrite + Unpin,
+    command_tx: mpsc::Sender<Command>, state_rx: watch::Receiver<AppState>,
+) {
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        debug!(line, "handle control command");
+        let reply = handle_line(line.trim(), &command_tx, &state_rx).await;
+        let written = writer.write_all(format!("{reply}\n").as_bytes()).await;
+        if written.is_err() || writer.flush().await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn handle_line(
+    line: &str, command_tx: &mpsc::Sender<Command>, state_rx: &watch::Receiver<AppState>,
+) -> String {
+    let mut words = line.split_whitespace();
+    let command = match (words.next(), words.next()) {
+        (Some("status"), None) => return status_line(&state_rx.borrow()),
+        (Some("scan"), None) => Command::Scan,
+        (Some("connect"), Some(addr)) => match addr.parse() {
+            Ok(addr) => return connect(addr, command_tx).await,
+            Err(_) => return format!("error invalid address {addr}"),
+        },
+        (Some("disconnect"), None) => Command::Disconnect,
+        (Some("cancel"), None) => Command::CancelConnect,
+        _ => return format!("error unknown command {line}"),
+    };
+
+    if command_tx.send(command).await.is_err() {
+        return "error bluetooth manager stopped".to_string();
+    }
+    "ok".to_string()
+}
+
+/// Queue a connect and wait for how it ends, the manager answers this connect
+/// and not another one finishing meanwhile.
+async fn connect(addr: Address, command_tx: &mpsc::Sender<Command>) -> String {
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let command = Command::ConnectAndReply {
+        addr,
+        ensure: false,
+        reply_tx,
+    };
+    if command_tx.send(command).await.is_err() {
+        return "error bluetooth manager stopped".to_string();
+    }
+
+    match timeout(CONNECT_TIMEOUT, reply_rx).await {
+        Ok(Ok(Ok(()))) => "ok".to_string(),
+        Ok(Ok(Err(err))) => format!("error {err}"),
+        // unanswered when cancelled, or when the manager stopped
+        Ok(Err(_)) => "error connect cancelled".to_string(),
+        Err(_) => "error connect timed out".to_string(),
+    }
+}
+
+fn status_line(state: &AppState) -> String {
+    let scan = match state.scan_status {
+        BluetoothScanStatus::Disable => "disable",
+        BluetoothScanStatus::Scanning => "scanning",
+        BluetoothScanStatus::Finished => "finished",
+        BluetoothScanStatus::Failed => "failed",
+    };
+    let connect = match state.connect_state {
+        BluetoothConnectState::Idle => "idle",
+        BluetoothConnectState::InProgress(_) => "connecting",
+        BluetoothConnectState::Connected => "connected",
+        BluetoothConnectState::Failed { .. } => "failed",
+    };
+
+    let mut line = format!(
+        "powered={} scan={} connect={}",
+        state.powered, scan, connect
+    );
+    if let Some(info) = state.connected_device() {
+        line.push_str(&format!(" device={} name={}", info.addr, info.name));
+    }
+    line
+}
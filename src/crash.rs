@@ -0,0 +1,108 @@
+use crate::paths;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, Write},
+    panic::{self, PanicHookInfo},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+// how many log lines go into a crash report and the log screen
+const RECENT_LOG_LINES: usize = 500;
+
+// holds the file name of the crash report not shown to the user yet
+const LAST_CRASH_FILE: &str = "last-crash";
+
+/// Log writer keeping the latest lines, for crash reports and the log screen.
+#[derive(Clone, Default)]
+pub struct RecentLogs {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl RecentLogs {
+    fn push(&self, text: &str) {
+        let mut lines = self.lines.lock().unwrap_or_else(|err| err.into_inner());
+        for line in text.lines() {
+            if lines.len() == RECENT_LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(|err| err.into_inner());
+        lines.iter().cloned().collect()
+    }
+}
+
+impl<'a> MakeWriter<'a> for RecentLogs {
+    type Writer = RecentLogsWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RecentLogsWriter { logs: self.clone() }
+    }
+}
+
+pub struct RecentLogsWriter {
+    logs: RecentLogs,
+}
+
+impl Write for RecentLogsWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.logs.push(&String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write a crash report to the data directory on panic, before the default hook
+/// prints it.
+pub fn install_panic_hook(logs: RecentLogs) {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Err(err) = write_crash_report(info, &logs) {
+            eprintln!("write crash report failed: {err}");
+        }
+        default_hook(info);
+    }));
+}
+
+/// The file name of the crash report of the last run, only returned once.
+pub fn take_last_crash() -> Option<String> {
+    let marker = paths::data_dir().join(LAST_CRASH_FILE);
+    let name = fs::read_to_string(&marker).ok()?;
+    let _ = fs::remove_file(&marker);
+    Some(name.trim().to_string())
+}
+
+fn write_crash_report(info: &PanicHookInfo, logs: &RecentLogs) -> io::Result<()> {
+    let dir = paths::data_dir();
+    fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let name = format!("crash-{timestamp}.log");
+
+    let mut report = String::new();
+    report.push_str(&format!("{info}\n\n"));
+    report.push_str(&format!(
+        "backtrace:\n{}\n\n",
+        std::backtrace::Backtrace::force_capture()
+    ));
+    report.push_str("recent logs:\n");
+    for line in logs.lines() {
+        report.push_str(&line);
+        report.push('\n');
+    }
+
+    fs::write(dir.join(&name), report)?;
+    fs::write(dir.join(LAST_CRASH_FILE), &name)?;
+    Ok(())
+}
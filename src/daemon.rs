@@ -0,0 +1,131 @@
+use crate::{
+    config::Config,
+    data::AppData,
+    signals::{ActionSignals, QuitSignals, SignalAction},
+};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{control, Command, ManagerHandle};
+use sd_notify::NotifyState;
+use std::time::Duration;
+use tokio::{
+    io,
+    sync::{mpsc, oneshot, watch},
+    time::{interval, timeout},
+};
+use tracing::{error, info, warn};
+
+// how often the preferred device is checked and reconnected
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Run without any UI: keep Bluetooth on and the preferred device connected
+/// until a signal arrives.
+pub async fn run(
+    manager: ManagerHandle, mut config_rx: watch::Receiver<Config>, mut signals: QuitSignals,
+    mut action_signals: ActionSignals,
+) -> anyhow::Result<()> {
+    let mut target = target_device(&config_rx.borrow_and_update());
+    let mut interval = interval(CHECK_INTERVAL);
+
+    notify(&[NotifyState::Ready]);
+    spawn_watchdog(manager.command_tx.clone());
+
+    // the same commands as the control socket, for driving it over SSH pipes
+    tokio::spawn(control::serve_lines(
+        io::stdin(),
+        io::stdout(),
+        manager.command_tx.clone(),
+        manager.state_rx.clone(),
+    ));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                let command = match target {
+                    Some(addr) => Command::EnsureConnected(addr),
+                    None if !manager.state_rx.borrow().powered => Command::PowerOn,
+                    None => continue,
+                };
+                let _ = manager.command_tx.send(command).await;
+            }
+            changed = config_rx.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                let config = config_rx.borrow_and_update().clone();
+                target = target_device(&config);
+                let data = AppData::load(&AppData::path());
+                let _ = manager
+                    .command_tx
+                    .send(Command::SetOptions(config.manager_options(&data)))
+                    .await;
+            }
+            action = action_signals.recv() => {
+                info!(?action, "handle signal action");
+                let command = match action {
+                    SignalAction::Rescan => Command::Scan,
+                    SignalAction::Reconnect => {
+                        match manager.state_rx.borrow().last_connected.or(target) {
+                            Some(addr) => Command::EnsureConnected(addr),
+                            None => continue,
+                        }
+                    }
+                };
+                let _ = manager.command_tx.send(command).await;
+            }
+            name = signals.recv() => {
+                info!(signal = name, "quit by signal");
+                break;
+            }
+        }
+    }
+
+    notify(&[NotifyState::Stopping]);
+    manager.shutdown().await;
+
+    Ok(())
+}
+
+/// Tell systemd about the daemon, does nothing when not run as a service.
+fn notify(state: &[NotifyState]) {
+    if let Err(err) = sd_notify::notify(false, state) {
+        warn!(?err, "notify systemd failed");
+    }
+}
+
+/// Keep the systemd watchdog fed while the manager answers pings, so a
+/// Bluetooth call hanging in bluetoothd gets the service restarted. A connect
+/// answers pings while it runs, so the `WatchdogSec` of the unit only has to
+/// be longer than the slowest other call, not the connect.
+fn spawn_watchdog(command_tx: mpsc::Sender<Command>) {
+    let mut usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut usec) {
+        return;
+    }
+    let period = Duration::from_micros(usec / 2);
+    info!(?period, "feed systemd watchdog");
+
+    tokio::spawn(async move {
+        let mut interval = interval(period);
+        loop {
+            interval.tick().await;
+
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if command_tx.send(Command::Ping(reply_tx)).await.is_err() {
+                break;
+            }
+            match timeout(period, reply_rx).await {
+                Ok(Ok(())) => notify(&[NotifyState::Watchdog]),
+                Ok(Err(_)) => break,
+                Err(_) => error!("bluetooth manager is not responding"),
+            }
+        }
+    });
+}
+
+fn target_device(config: &Config) -> Option<Address> {
+    let addr = config.preferred_device();
+    if addr.is_none() {
+        warn!("no preferred device configured, only keep bluetooth on");
+    }
+    addr
+}
@@ -0,0 +1,207 @@
+use crate::{
+    files,
+    migrate::{self, Migration},
+    paths,
+};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::DeviceSettings;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+const DATA_FILE: &str = "data.toml";
+
+// the migration from version `n` of the data is at index `n`
+const MIGRATIONS: &[Migration] = &[migrate::add_version];
+
+/// What the app remembers between runs, kept apart from the config the user
+/// edits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppData {
+    /// Format of the file, upgraded when it's read by a newer release.
+    pub version: u32,
+    /// The device connected successfully last.
+    pub last_device: Option<SavedDevice>,
+    /// Pinned at the top of the list, the first nine have number hotkeys.
+    pub favorites: Vec<SavedDevice>,
+    /// Names given by the user, shown instead of the BlueZ name and kept when
+    /// the device is forgotten, keyed by address.
+    pub nicknames: BTreeMap<String, String>,
+    /// Set up in the settings screen of each device, keyed by address.
+    pub device_settings: BTreeMap<String, DeviceSettings>,
+    /// Name of the connection profile picked last, for the hooks.
+    pub active_profile: Option<String>,
+    pub ui: UiPrefs,
+}
+
+impl Default for AppData {
+    fn default() -> Self {
+        Self {
+            version: MIGRATIONS.len() as u32,
+            last_device: None,
+            favorites: Vec::new(),
+            nicknames: BTreeMap::new(),
+            device_settings: BTreeMap::new(),
+            active_profile: None,
+            ui: UiPrefs::default(),
+        }
+    }
+}
+
+/// How the UI was left at the last exit. The theme, the language and the
+/// filter of unnamed devices are switched in the app too, but kept in the
+/// config, where the user may set them as well.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiPrefs {
+    /// Address of the device under the cursor.
+    pub last_selected: Option<String>,
+    pub sort: SortOrder,
+}
+
+/// The order of the list after the favorites.
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// In the order BlueZ found them.
+    #[default]
+    Found,
+    /// By the name shown, ignoring case.
+    Name,
+    /// The strongest signal first.
+    Signal,
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 3] = [SortOrder::Found, SortOrder::Name, SortOrder::Signal];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|order| *order == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedDevice {
+    pub address: String,
+    pub name: String,
+}
+
+impl SavedDevice {
+    pub fn addr(&self) -> Option<Address> {
+        self.address.parse().ok()
+    }
+
+    /// The name, or the address for unnamed devices.
+    pub fn display_name(&self) -> &str {
+        if self.name.is_empty() {
+            &self.address
+        } else {
+            &self.name
+        }
+    }
+}
+
+impl AppData {
+    /// Position of the device among the favorites.
+    pub fn favorite_index(&self, addr: Address) -> Option<usize> {
+        self.favorites
+            .iter()
+            .position(|device| device.addr() == Some(addr))
+    }
+
+    pub fn nickname(&self, addr: Address) -> Option<&str> {
+        self.nicknames.get(&addr.to_string()).map(String::as_str)
+    }
+
+    /// The settings of the device, the defaults when it has none.
+    pub fn settings(&self, addr: Address) -> DeviceSettings {
+        self.device_settings
+            .get(&addr.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Store the settings, dropping the ones back to the defaults. Only one
+    /// device connects at start, so it takes that over from the others.
+    pub fn set_settings(&mut self, addr: Address, settings: DeviceSettings) {
+        if settings.auto_connect {
+            for other in self.device_settings.values_mut() {
+                other.auto_connect = false;
+            }
+        }
+        self.device_settings.insert(addr.to_string(), settings);
+        self.device_settings
+            .retain(|_, settings| *settings != DeviceSettings::default());
+    }
+
+    /// Remember the PIN a legacy device accepted, returns whether it changed.
+    pub fn set_pin(&mut self, addr: Address, pin: String) -> bool {
+        let mut settings = self.settings(addr);
+        if settings.pin.as_ref() == Some(&pin) {
+            return false;
+        }
+        settings.pin = Some(pin);
+        self.set_settings(addr, settings);
+        true
+    }
+
+    /// The settings of every device with a valid address, for the manager.
+    pub fn parsed_device_settings(&self) -> HashMap<Address, DeviceSettings> {
+        self.device_settings
+            .iter()
+            .filter_map(|(address, settings)| Some((address.parse().ok()?, settings.clone())))
+            .collect()
+    }
+
+    /// The first device set to connect at start.
+    pub fn auto_connect_device(&self) -> Option<Address> {
+        self.device_settings
+            .iter()
+            .filter(|(_, settings)| settings.auto_connect)
+            .find_map(|(address, _)| address.parse().ok())
+    }
+
+    pub fn path() -> PathBuf {
+        paths::data_dir().join(DATA_FILE)
+    }
+
+    /// Read the data file, starting empty when it's missing or broken.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => Self::parse(path, &content).unwrap_or_else(|err| {
+                warn!(?path, %err, "parse data file failed, start empty");
+                // keep it from being overwritten by the next save
+                match files::set_aside(path) {
+                    Ok(broken_path) => info!(?broken_path, "set aside broken data file"),
+                    Err(err) => warn!(?path, ?err, "set aside broken data file failed"),
+                }
+                Self::default()
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                warn!(?path, ?err, "read data file failed, start empty");
+                Self::default()
+            }
+        }
+    }
+
+    fn parse(path: &Path, content: &str) -> Result<Self, toml::de::Error> {
+        let mut table = content.parse::<toml::Table>()?;
+        migrate::migrate(path, &mut table, MIGRATIONS);
+        toml::Value::Table(table).try_into()
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        files::write_atomic(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
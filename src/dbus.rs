@@ -0,0 +1,157 @@
+use crate::{
+    bluetooth::{manager::Command, BluetoothScanStatus},
+    state::AppState,
+};
+use bluer::Address;
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinHandle,
+};
+use tracing::{error, warn};
+use zbus::{dbus_interface, dbus_proxy, fdo, Connection, ConnectionBuilder};
+
+pub const SERVICE_NAME: &str = "org.tg5040.BluetoothAudioConnector";
+
+pub const OBJECT_PATH: &str = "/org/tg5040/BluetoothAudioConnector";
+
+/// The app state and commands published on the system bus, so other system
+/// components don't need to speak the control socket protocol.
+pub struct DbusService {
+    // the name is released when the connection is dropped
+    _connection: Connection,
+    task: JoinHandle<()>,
+}
+
+impl DbusService {
+    pub async fn spawn(
+        command_tx: mpsc::Sender<Command>, state_rx: watch::Receiver<AppState>,
+    ) -> zbus::Result<Self> {
+        let interface = ConnectorInterface {
+            command_tx,
+            state_rx: state_rx.clone(),
+        };
+        let connection = ConnectionBuilder::system()?
+            .name(SERVICE_NAME)?
+            .serve_at(OBJECT_PATH, interface)?
+            .build()
+            .await?;
+
+        let task = tokio::spawn(notify_changes(connection.clone(), state_rx));
+        Ok(Self {
+            _connection: connection,
+            task,
+        })
+    }
+}
+
+impl Drop for DbusService {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The client side of the service, for companions like the OSD.
+#[dbus_proxy(
+    interface = "org.tg5040.BluetoothAudioConnector",
+    default_service = "org.tg5040.BluetoothAudioConnector",
+    default_path = "/org/tg5040/BluetoothAudioConnector"
+)]
+pub trait Connector {
+    fn connect(&self, address: &str) -> zbus::Result<()>;
+
+    fn disconnect(&self) -> zbus::Result<()>;
+
+    fn rescan(&self) -> zbus::Result<()>;
+
+    /// Address of the connected device, empty when there is none.
+    #[dbus_proxy(property)]
+    fn connected_device(&self) -> zbus::Result<String>;
+
+    #[dbus_proxy(property)]
+    fn scanning(&self) -> zbus::Result<bool>;
+}
+
+struct ConnectorInterface {
+    command_tx: mpsc::Sender<Command>,
+    state_rx: watch::Receiver<AppState>,
+}
+
+impl ConnectorInterface {
+    async fn send(&self, command: Command) -> fdo::Result<()> {
+        self.command_tx
+            .send(command)
+            .await
+            .map_err(|_| fdo::Error::Failed("bluetooth manager stopped".to_string()))
+    }
+}
+
+#[dbus_interface(name = "org.tg5040.BluetoothAudioConnector")]
+impl ConnectorInterface {
+    /// Queue a connect, follow `ConnectedDevice` for the result.
+    async fn connect(&self, address: &str) -> fdo::Result<()> {
+        let addr = address
+            .parse()
+            .map_err(|_| fdo::Error::InvalidArgs(format!("invalid address {address}")))?;
+        self.send(Command::Connect(addr)).await
+    }
+
+    async fn disconnect(&self) -> fdo::Result<()> {
+        self.send(Command::Disconnect).await
+    }
+
+    async fn rescan(&self) -> fdo::Result<()> {
+        self.send(Command::Scan).await
+    }
+
+    /// Address of the connected device, empty when there is none.
+    #[dbus_interface(property)]
+    fn connected_device(&self) -> String {
+        connected_device(&self.state_rx.borrow())
+            .map(|addr| addr.to_string())
+            .unwrap_or_default()
+    }
+
+    #[dbus_interface(property)]
+    fn scanning(&self) -> bool {
+        self.state_rx.borrow().scan_status == BluetoothScanStatus::Scanning
+    }
+}
+
+fn connected_device(state: &AppState) -> Option<Address> {
+    state.connected_device().map(|info| info.addr)
+}
+
+/// Emit `PropertiesChanged` for every property the new state changes.
+async fn notify_changes(connection: Connection, mut state_rx: watch::Receiver<AppState>) {
+    let interface = match connection
+        .object_server()
+        .interface::<_, ConnectorInterface>(OBJECT_PATH)
+        .await
+    {
+        Ok(interface) => interface,
+        Err(err) => {
+            error!(?err, "get dbus interface failed");
+            return;
+        }
+    };
+
+    let mut last = state_rx.borrow_and_update().clone();
+    while state_rx.changed().await.is_ok() {
+        let state = state_rx.borrow_and_update().clone();
+        let context = interface.signal_context();
+        let object = interface.get().await;
+
+        if connected_device(&state) != connected_device(&last) {
+            if let Err(err) = object.connected_device_changed(context).await {
+                warn!(?err, "notify dbus connected device failed");
+            }
+        }
+        if state.scan_status != last.scan_status {
+            if let Err(err) = object.scanning_changed(context).await {
+                warn!(?err, "notify dbus scanning failed");
+            }
+        }
+
+        last = state;
+    }
+}
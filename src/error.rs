@@ -0,0 +1,160 @@
+use bluer::{Address, ErrorKind};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// What went wrong in a Bluetooth operation, coarse enough for the UI to tell
+/// the user what to do about it.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum Error {
+    #[error("no bluetooth adapter found")]
+    AdapterMissing,
+
+    /// bluetoothd isn't running or D-Bus can't be reached.
+    #[error("bluez is not available: {0}")]
+    BluezDown(String),
+
+    #[error("bluetooth is powered off")]
+    PoweredOff,
+
+    /// The device refused the pairing, or failed to check it.
+    #[error("pairing rejected: {0}")]
+    PairingRejected(String),
+
+    /// The pairing was cancelled or timed out halfway, like a headset leaving
+    /// pairing mode. The bond it may have is still good.
+    #[error("pairing interrupted: {0}")]
+    PairingInterrupted(String),
+
+    /// A legacy device asked for a PIN and refused the one given.
+    #[error("wrong pin")]
+    WrongPin,
+
+    /// A legacy device refused every default PIN, the user has to enter it.
+    #[error("device {0} requires a pin")]
+    PinRequired(Address),
+
+    /// The device didn't answer, it's off or out of range.
+    #[error("device did not respond")]
+    PageTimeout,
+
+    /// BlueZ is still busy with another connect or scan.
+    #[error("operation already in progress")]
+    InProgress,
+
+    /// The device connected but no audio profile came up.
+    #[error("audio routing failed")]
+    AudioRoutingFailed,
+
+    #[error("device {0} not found")]
+    DeviceNotFound(Address),
+
+    /// The network of a phone came up but gave no address, its tethering is
+    /// likely turned off.
+    #[error("no network address from the phone")]
+    NoNetworkAddress,
+
+    /// The phone gave an address but putting it on the interface failed, like
+    /// without the rights for `ip`.
+    #[error("network setup failed: {0}")]
+    NetworkSetup(String),
+
+    /// Any other error reported by BlueZ, with its raw message.
+    #[error("{0}")]
+    Bluez(String),
+}
+
+// BlueZ errors which usually go away when the call is made again
+const TRANSIENT_BLUEZ_ERRORS: [&str; 4] = [
+    "br-connection-busy",
+    "br-connection-create-socket",
+    "br-connection-aborted-by-local",
+    "le-connection-abort-by-local",
+];
+
+impl Error {
+    /// Whether the same call made again a moment later is likely to work, like
+    /// when a headset was still waking up. `InProgress` is waited out longer
+    /// by the manager instead.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::PageTimeout | Self::PairingInterrupted(_) => true,
+            Self::Bluez(message) => TRANSIENT_BLUEZ_ERRORS
+                .iter()
+                .any(|name| message.contains(name)),
+            _ => false,
+        }
+    }
+
+    /// Whether the device refused the connection for the pairing, usually as it
+    /// forgot this handheld. Only then is the bond removed to pair again, an
+    /// interrupted pairing is retried instead.
+    pub fn is_auth_failure(&self) -> bool {
+        match self {
+            Self::PairingRejected(_) => true,
+            Self::Bluez(message) => message.contains("key-missing"),
+            _ => false,
+        }
+    }
+
+    /// Classify a raw BlueZ error message, like `br-connection-page-timeout`.
+    pub fn bluez(message: impl Into<String>) -> Self {
+        let message = message.into();
+        if message.contains("page-timeout") {
+            Self::PageTimeout
+        } else {
+            Self::Bluez(message)
+        }
+    }
+}
+
+impl From<bluer::Error> for Error {
+    fn from(err: bluer::Error) -> Self {
+        match err.kind {
+            ErrorKind::AuthenticationFailed | ErrorKind::AuthenticationRejected => {
+                Self::PairingRejected(err.message)
+            }
+            ErrorKind::AuthenticationCanceled | ErrorKind::AuthenticationTimeout => {
+                Self::PairingInterrupted(err.message)
+            }
+            ErrorKind::NotReady => Self::PoweredOff,
+            ErrorKind::InProgress => Self::InProgress,
+            ErrorKind::Internal(_) => Self::BluezDown(err.message),
+            _ => Self::bluez(err.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transient_errors() {
+        assert!(Error::PageTimeout.is_transient());
+        assert!(Error::PairingInterrupted("canceled".into()).is_transient());
+        assert!(Error::bluez("br-connection-busy").is_transient());
+        assert!(!Error::bluez("br-connection-refused").is_transient());
+        assert!(!Error::InProgress.is_transient());
+        assert!(!Error::PairingRejected("failed".into()).is_transient());
+    }
+
+    #[test]
+    fn auth_failures() {
+        assert!(Error::PairingRejected("failed".into()).is_auth_failure());
+        assert!(Error::bluez("br-connection-key-missing").is_auth_failure());
+        assert!(!Error::PairingInterrupted("canceled".into()).is_auth_failure());
+        assert!(!Error::PageTimeout.is_auth_failure());
+    }
+
+    #[test]
+    fn classify_bluez_messages() {
+        assert_eq!(
+            Error::bluez("br-connection-page-timeout"),
+            Error::PageTimeout
+        );
+        assert_eq!(
+            Error::bluez("br-connection-unknown"),
+            Error::Bluez("br-connection-unknown".into())
+        );
+    }
+}
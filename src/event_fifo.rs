@@ -0,0 +1,145 @@
+use anyhow::{bail, Context};
+use bluetooth_audio_connector_tg5040::{AppState, DeviceInfo};
+use std::{
+    ffi::CString,
+    fs, io,
+    os::unix::{ffi::OsStrExt, fs::FileTypeExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tokio::{io::AsyncWriteExt, net::unix::pipe, sync::watch, task::JoinHandle, time::timeout};
+use tracing::{debug, info, warn};
+
+// a reader which stopped reading fills the pipe, its events are dropped after
+// this long so the next reader gets the new ones
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Writes the connection and battery events to a named pipe, for shell scripts
+/// which `read` them. Each event is a few `KEY=VALUE` lines ended by an empty
+/// line, `EVENT` first:
+///
+/// ```text
+/// EVENT=connected
+/// ADDRESS=00:11:22:33:44:55
+/// NAME=WH-1000XM4
+/// ```
+///
+/// The events are `connected` and `disconnected`, with `ADDRESS` and `NAME`,
+/// and `battery` with `ADDRESS` and `BATTERY`. They are only written while a
+/// script has the pipe open, the ones before are dropped. The pipe is removed
+/// when dropped.
+pub struct EventFifo {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl EventFifo {
+    pub fn spawn(
+        path: impl Into<PathBuf>, state_rx: watch::Receiver<AppState>,
+    ) -> anyhow::Result<Self> {
+        let path = path.into();
+        create_fifo(&path)?;
+        info!(?path, "write events to fifo");
+        let task = tokio::spawn(follow(path.clone(), state_rx));
+        Ok(Self { path, task })
+    }
+}
+
+impl Drop for EventFifo {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Create the pipe, a pipe left by a killed run is used again. Anything else
+/// at the path, a symlink too, is left alone and fails, as it's removed with
+/// the pipe.
+fn create_fifo(path: &Path) -> anyhow::Result<()> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).context("nul byte in fifo path")?;
+    // SAFETY: a plain syscall on a valid C string, the umask applies to the
+    // mode like for mkfifo(1)
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o666) } == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    if err.kind() != io::ErrorKind::AlreadyExists {
+        return Err(err).with_context(|| format!("create fifo {}", path.display()));
+    }
+
+    let metadata =
+        fs::symlink_metadata(path).with_context(|| format!("read {}", path.display()))?;
+    if !metadata.file_type().is_fifo() {
+        bail!("{} exists and isn't a fifo", path.display());
+    }
+    Ok(())
+}
+
+async fn follow(path: PathBuf, mut state_rx: watch::Receiver<AppState>) {
+    let mut connected = state_rx.borrow_and_update().connected_device().cloned();
+    let mut sender = None;
+
+    while state_rx.changed().await.is_ok() {
+        let current = state_rx.borrow_and_update().connected_device().cloned();
+        let events = events(connected.as_ref(), current.as_ref());
+        connected = current;
+
+        for event in events {
+            write_event(&path, &mut sender, &event).await;
+        }
+    }
+}
+
+/// The events between two states of the connected device.
+fn events(previous: Option<&DeviceInfo>, current: Option<&DeviceInfo>) -> Vec<String> {
+    let device = |event: &str, info: &DeviceInfo| {
+        // a newline in the name would be read as another line
+        let name = info.name.replace('\n', " ");
+        format!("EVENT={event}\nADDRESS={}\nNAME={name}\n\n", info.addr)
+    };
+
+    match (previous, current) {
+        (Some(previous), Some(current)) if previous.addr == current.addr => match current.battery {
+            Some(battery) if previous.battery != current.battery => vec![format!(
+                "EVENT=battery\nADDRESS={}\nBATTERY={battery}\n\n",
+                current.addr
+            )],
+            _ => Vec::new(),
+        },
+        (previous, current) => previous
+            .map(|info| device("disconnected", info))
+            .into_iter()
+            .chain(current.map(|info| device("connected", info)))
+            .collect(),
+    }
+}
+
+/// Write the event to the reader of the pipe, opening it when there's none
+/// yet. Without a reader the event is dropped.
+async fn write_event(path: &Path, sender: &mut Option<pipe::Sender>, event: &str) {
+    if sender.is_none() {
+        match pipe::OpenOptions::new().open_sender(path) {
+            Ok(opened) => *sender = Some(opened),
+            Err(err) => {
+                debug!(?err, "no reader of the event fifo, drop the event");
+                return;
+            }
+        }
+    }
+    let Some(writer) = sender else {
+        return;
+    };
+
+    match timeout(WRITE_TIMEOUT, writer.write_all(event.as_bytes())).await {
+        Ok(Ok(())) => {}
+        // the reader is gone, the next event waits for a new one
+        Ok(Err(err)) => {
+            debug!(?err, "write event fifo failed");
+            *sender = None;
+        }
+        Err(_) => {
+            warn!("reader of the event fifo is stuck, drop the event");
+            *sender = None;
+        }
+    }
+}
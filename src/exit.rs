@@ -0,0 +1,34 @@
+use bluetooth_audio_connector_tg5040::Error;
+use std::process::ExitCode;
+
+/// The exit code of the process, for the shell wrappers of the pak.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitStatus {
+    /// Done, for `--connect` the device is connected.
+    Success = 0,
+    Error = 1,
+    /// bluetoothd has no adapter.
+    NoAdapter = 2,
+    /// `--connect` couldn't connect the device.
+    ConnectFailed = 3,
+    /// Quit by a signal before `--connect` finished.
+    Cancelled = 4,
+    /// `--reconnect-last` has no device to connect, none was connected yet
+    /// and none is preferred.
+    NoDevice = 5,
+}
+
+impl ExitStatus {
+    pub fn of_error(err: &anyhow::Error) -> Self {
+        match err.downcast_ref::<Error>() {
+            Some(Error::AdapterMissing) => Self::NoAdapter,
+            _ => Self::Error,
+        }
+    }
+}
+
+impl From<ExitStatus> for ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// Write to a temporary file next to `path` and rename it over `path`, so a
+/// battery dying in between leaves the old content instead of a cut one.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+
+    let tmp_path = with_suffix(path, ".tmp");
+    let result = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result?;
+
+    // the rename is only on the SD card once the directory is synced too
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+/// Rename a file which can't be parsed to `<name>.broken`, keeping it for the
+/// user while the defaults take its place.
+pub fn set_aside(path: &Path) -> io::Result<PathBuf> {
+    let broken_path = with_suffix(path, ".broken");
+    fs::rename(path, &broken_path)?;
+    Ok(broken_path)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
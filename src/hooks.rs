@@ -0,0 +1,190 @@
+use crate::{
+    config::{Config, ConnectionProfile},
+    data::AppData,
+    paths,
+};
+use bluetooth_audio_connector_tg5040::{AppState, BluetoothScanStatus, DeviceInfo};
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{process, sync::watch, time::timeout};
+use tracing::{info, warn};
+
+const HOOKS_DIR: &str = "hooks";
+
+// a hook still running after this is killed, so a stuck script doesn't pile up
+// processes on each event
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run the hooks of the config directory when the connected device changes
+/// and when a scan finishes. Each event runs the executable `hooks/<event>`
+/// and then the executables of `hooks/<event>.d/` in the order of their names,
+/// like run-parts, so several paks can each add their own.
+///
+/// The events are `on-connect` and `on-disconnect`, with `BT_DEVICE_ADDRESS`
+/// and `BT_DEVICE_NAME` in their environment, and `on-scan-finished` with
+/// `BT_DEVICE_COUNT`. When the device was connected from a connection profile,
+/// `on-connect` also gets `BT_PROFILE_NAME` and `BT_LATENCY_HINT`. Every hook
+/// gets the event as `BT_EVENT`.
+///
+/// The hooks of an event run in a task of their own, so a slow one doesn't
+/// hold back the hooks of later events. Their output goes to the log and a
+/// hook running longer than `HOOK_TIMEOUT` is killed.
+pub fn spawn(mut state_rx: watch::Receiver<AppState>, config_rx: watch::Receiver<Config>) {
+    let dir = Arc::new(paths::config_dir().join(HOOKS_DIR));
+
+    tokio::spawn(async move {
+        let (mut connected, mut scan_status) = {
+            let state = state_rx.borrow_and_update();
+            (state.connected_device().cloned(), state.scan_status)
+        };
+
+        while state_rx.changed().await.is_ok() {
+            let (current, current_scan_status, device_count) = {
+                let state = state_rx.borrow_and_update();
+                (
+                    state.connected_device().cloned(),
+                    state.scan_status,
+                    state.devices.len(),
+                )
+            };
+
+            if scan_status == BluetoothScanStatus::Scanning
+                && current_scan_status == BluetoothScanStatus::Finished
+            {
+                let env = vec![("BT_DEVICE_COUNT", device_count.to_string())];
+                spawn_hooks(&dir, vec![("on-scan-finished", env)]);
+            }
+            scan_status = current_scan_status;
+
+            if current.as_ref().map(|info| info.addr) == connected.as_ref().map(|info| info.addr) {
+                continue;
+            }
+
+            // a switch to another device runs on-disconnect before on-connect
+            let mut events = Vec::new();
+            if let Some(info) = &connected {
+                events.push(("on-disconnect", device_env(info, None)));
+            }
+            if let Some(info) = &current {
+                let profile = active_profile(&config_rx.borrow(), info);
+                events.push(("on-connect", device_env(info, profile.as_ref())));
+            }
+            spawn_hooks(&dir, events);
+            connected = current;
+        }
+    });
+}
+
+/// The profile picked last, if it's the one of the device.
+fn active_profile(config: &Config, info: &DeviceInfo) -> Option<ConnectionProfile> {
+    let name = AppData::load(&AppData::path()).active_profile?;
+    config
+        .profiles
+        .iter()
+        .find(|profile| profile.name == name && profile.addr() == Some(info.addr))
+        .cloned()
+}
+
+fn device_env(
+    info: &DeviceInfo, profile: Option<&ConnectionProfile>,
+) -> Vec<(&'static str, String)> {
+    let mut env = vec![
+        ("BT_DEVICE_ADDRESS", info.addr.to_string()),
+        ("BT_DEVICE_NAME", info.name.clone()),
+    ];
+    if let Some(profile) = profile {
+        env.push(("BT_PROFILE_NAME", profile.name.clone()));
+        env.push(("BT_LATENCY_HINT", profile.latency.as_str().to_string()));
+    }
+    env
+}
+
+/// Run the hooks of the events in order, without waiting for them.
+fn spawn_hooks(dir: &Arc<PathBuf>, events: Vec<(&'static str, Vec<(&'static str, String)>)>) {
+    if events.is_empty() {
+        return;
+    }
+    let dir = dir.clone();
+    tokio::spawn(async move {
+        for (event, env) in events {
+            run_hooks(&dir, event, &env).await;
+        }
+    });
+}
+
+/// Run `<event>` and then `<event>.d/*` of the hooks directory, one after the
+/// other.
+async fn run_hooks(dir: &Path, event: &str, env: &[(&str, String)]) {
+    let single = dir.join(event);
+    let paths = single
+        .exists()
+        .then_some(single)
+        .into_iter()
+        .chain(hook_dir_entries(&dir.join(format!("{event}.d"))));
+    for path in paths {
+        run_hook(&path, event, env).await;
+    }
+}
+
+/// The executables of a hook directory sorted by name. Like run-parts, names
+/// with a dot are left out, so backups like `10-volume.bak` don't run.
+fn hook_dir_entries(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths = entries
+        .flatten()
+        .filter(|entry| !entry.file_name().to_string_lossy().contains('.'))
+        // symlinks are followed, like to a script of a pak
+        .filter(|entry| {
+            fs::metadata(entry.path())
+                .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        })
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+async fn run_hook(path: &Path, event: &str, env: &[(&str, String)]) {
+    info!(?path, "run hook");
+    let mut command = process::Command::new(path);
+    command
+        .env("BT_EVENT", event)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    for (name, value) in env {
+        command.env(name, value);
+    }
+    let output = match timeout(HOOK_TIMEOUT, command.output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => {
+            warn!(?path, ?err, "run hook failed");
+            return;
+        }
+        Err(_) => {
+            warn!(?path, timeout = ?HOOK_TIMEOUT, "hook timed out, killed it");
+            return;
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let (stdout, stderr) = (stdout.trim_end(), stderr.trim_end());
+    if output.status.success() {
+        if !stdout.is_empty() || !stderr.is_empty() {
+            info!(?path, stdout, stderr, "hook output");
+        }
+    } else {
+        warn!(?path, status = ?output.status, stdout, stderr, "hook failed");
+    }
+}
@@ -0,0 +1,357 @@
+use sdl2::{controller::Button, event::Event, keyboard::Keycode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// D-pad directions, in clockwise order.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    pub fn from_index(index: usize) -> Self {
+        match index {
+            0 => Direction::Up,
+            1 => Direction::Right,
+            2 => Direction::Down,
+            _ => Direction::Left,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Action {
+    Quit,
+    PowerOn,
+    PowerOff,
+    Move(Direction),
+    Rescan,
+    Connect,
+    Disconnect,
+    Forget,
+    ReconnectLast,
+    ToggleFavorite,
+    /// Connect the favorite with this index.
+    ConnectFavorite(usize),
+    /// Start the remap wizard.
+    Remap,
+    /// Show the recent log lines.
+    ShowLogs,
+    /// Show the connection profiles of the config.
+    Profiles,
+    /// Show the phones which can share their internet.
+    Network,
+    /// Show the versions of the app and the Bluetooth stack.
+    About,
+    /// Change the volume of the connected device.
+    VolumeUp,
+    VolumeDown,
+    /// Switch between the light and the dark theme.
+    ToggleTheme,
+    /// Switch between Chinese and English.
+    ToggleLanguage,
+    /// Sort the list by the next `SortOrder`.
+    CycleSort,
+    /// A raw press, sent instead of the mapped action while remapping.
+    Bind(Binding),
+    /// The window lost the focus or was hidden (`true`), or came back.
+    Background(bool),
+}
+
+impl Action {
+    pub fn from_event(event: &Event, keymap: &Keymap) -> Option<Self> {
+        // 连接收藏的蓝牙
+        if let Event::KeyUp {
+            keycode: Some(keycode),
+            ..
+        } = event
+        {
+            if let Some(index) = favorite_hotkey(*keycode) {
+                return Some(Action::ConnectFavorite(index));
+            }
+        }
+
+        // 关闭窗口
+        if let Event::Quit { .. } = event {
+            return Some(Action::Quit);
+        }
+
+        keymap.action(Binding::from_event(event)?)
+    }
+
+    /// Every press as `Bind` while remapping, closing the window still quits.
+    pub fn bind_from_event(event: &Event) -> Option<Self> {
+        if let Event::Quit { .. } = event {
+            return Some(Action::Quit);
+        }
+        Binding::from_event(event).map(Action::Bind)
+    }
+}
+
+/// A key of the keyboard or a button of the controller, written as
+/// `key:<SDL key name>` or `button:<SDL button name>` in the config.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Binding {
+    Key(Keycode),
+    Button(Button),
+}
+
+impl Binding {
+    /// Leaves the remap wizard, so it can't be taken by an action there.
+    pub const CANCEL: Binding = Binding::Key(Keycode::Escape);
+
+    /// The binding released by the event, actions fire on release.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::KeyUp {
+                keycode: Some(keycode),
+                ..
+            } => Some(Self::Key(*keycode)),
+            Event::ControllerButtonUp { button, .. } => Some(Self::Button(*button)),
+            _ => None,
+        }
+    }
+
+    pub fn is_key(self) -> bool {
+        matches!(self, Self::Key(_))
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let binding = match value.split_once(':') {
+            Some(("key", name)) => Keycode::from_name(name).map(Self::Key),
+            Some(("button", name)) => Button::from_string(name).map(Self::Button),
+            _ => None,
+        };
+        binding.ok_or_else(|| format!("invalid key or button: {}", value))
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        binding.to_string()
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(keycode) => write!(f, "key:{}", keycode.name()),
+            Self::Button(button) => write!(f, "button:{}", button.string()),
+        }
+    }
+}
+
+/// The keys and buttons of each action, the `[keymap]` section of the config.
+/// The defaults fit the tg5040, whose A/B and X/Y are swapped against the SDL
+/// names.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub quit: Vec<Binding>,
+    pub power_on: Vec<Binding>,
+    pub power_off: Vec<Binding>,
+    pub up: Vec<Binding>,
+    pub right: Vec<Binding>,
+    pub down: Vec<Binding>,
+    pub left: Vec<Binding>,
+    pub rescan: Vec<Binding>,
+    pub connect: Vec<Binding>,
+    pub disconnect: Vec<Binding>,
+    pub forget: Vec<Binding>,
+    pub reconnect_last: Vec<Binding>,
+    pub toggle_favorite: Vec<Binding>,
+    pub show_logs: Vec<Binding>,
+    pub profiles: Vec<Binding>,
+    pub network: Vec<Binding>,
+    pub remap: Vec<Binding>,
+    pub about: Vec<Binding>,
+    pub volume_up: Vec<Binding>,
+    pub volume_down: Vec<Binding>,
+    pub toggle_theme: Vec<Binding>,
+    pub toggle_language: Vec<Binding>,
+    pub cycle_sort: Vec<Binding>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Binding::{Button as B, Key as K};
+
+        Self {
+            // 退出程序
+            quit: vec![
+                K(Keycode::Escape),
+                K(Keycode::B),
+                K(Keycode::Backspace),
+                B(Button::A), /* B of tg5040 */
+            ],
+            // 打开蓝牙
+            power_on: vec![K(Keycode::Y), B(Button::X) /* Y of tg5040 */],
+            // 关闭蓝牙
+            power_off: vec![K(Keycode::X), B(Button::Y) /* X of tg5040 */],
+            // 选择蓝牙
+            up: vec![K(Keycode::Up), B(Button::DPadUp)],
+            right: vec![K(Keycode::Right), B(Button::DPadRight)],
+            down: vec![K(Keycode::Down), B(Button::DPadDown)],
+            left: vec![K(Keycode::Left), B(Button::DPadLeft)],
+            // 重新扫描
+            rescan: vec![K(Keycode::L), K(Keycode::F5), B(Button::LeftShoulder)],
+            // 连接蓝牙
+            connect: vec![
+                K(Keycode::A),
+                K(Keycode::Return),
+                B(Button::B), /* A of tg5040 */
+            ],
+            // 断开蓝牙
+            disconnect: vec![K(Keycode::D), B(Button::Back) /* SELECT of tg5040 */],
+            // 忘记蓝牙
+            forget: vec![K(Keycode::F), B(Button::Start) /* START of tg5040 */],
+            // 重新连接上次设备
+            reconnect_last: vec![K(Keycode::R), B(Button::RightShoulder)],
+            // 收藏蓝牙
+            toggle_favorite: vec![K(Keycode::S), B(Button::Guide) /* MENU of tg5040 */],
+            // 查看日志
+            show_logs: vec![K(Keycode::G), B(Button::RightStick) /* R3 of tg5040 */],
+            // 连接方案
+            profiles: vec![K(Keycode::P), B(Button::LeftStick) /* L3 of tg5040 */],
+            // 网络共享
+            network: vec![K(Keycode::N)],
+            // 重新设置按键
+            remap: vec![K(Keycode::K)],
+            // 关于
+            about: vec![K(Keycode::I)],
+            // 调节音量
+            volume_up: vec![K(Keycode::Equals), K(Keycode::Plus), K(Keycode::KpPlus)],
+            volume_down: vec![K(Keycode::Minus), K(Keycode::KpMinus)],
+            // 切换主题
+            toggle_theme: vec![K(Keycode::T)],
+            // 切换语言
+            toggle_language: vec![K(Keycode::E)],
+            // 切换排序
+            cycle_sort: vec![K(Keycode::O)],
+        }
+    }
+}
+
+impl Keymap {
+    /// The actions walked through by the remap wizard, in order.
+    pub const REMAP_ACTIONS: [Action; 15] = [
+        Action::Quit,
+        Action::PowerOn,
+        Action::PowerOff,
+        Action::Move(Direction::Up),
+        Action::Move(Direction::Right),
+        Action::Move(Direction::Down),
+        Action::Move(Direction::Left),
+        Action::Rescan,
+        Action::Connect,
+        Action::Disconnect,
+        Action::Forget,
+        Action::ReconnectLast,
+        Action::ToggleFavorite,
+        Action::ShowLogs,
+        Action::Profiles,
+    ];
+
+    pub fn action(&self, binding: Binding) -> Option<Action> {
+        Self::REMAP_ACTIONS
+            .into_iter()
+            .chain([
+                Action::Network,
+                Action::Remap,
+                Action::About,
+                Action::VolumeUp,
+                Action::VolumeDown,
+                Action::ToggleTheme,
+                Action::ToggleLanguage,
+                Action::CycleSort,
+            ])
+            .find(|action| self.bindings(*action).contains(&binding))
+    }
+
+    fn bindings(&self, action: Action) -> &[Binding] {
+        match action {
+            Action::Quit => &self.quit,
+            Action::PowerOn => &self.power_on,
+            Action::PowerOff => &self.power_off,
+            Action::Move(Direction::Up) => &self.up,
+            Action::Move(Direction::Right) => &self.right,
+            Action::Move(Direction::Down) => &self.down,
+            Action::Move(Direction::Left) => &self.left,
+            Action::Rescan => &self.rescan,
+            Action::Connect => &self.connect,
+            Action::Disconnect => &self.disconnect,
+            Action::Forget => &self.forget,
+            Action::ReconnectLast => &self.reconnect_last,
+            Action::ToggleFavorite => &self.toggle_favorite,
+            Action::ShowLogs => &self.show_logs,
+            Action::Profiles => &self.profiles,
+            Action::Network => &self.network,
+            Action::Remap => &self.remap,
+            Action::About => &self.about,
+            Action::VolumeUp => &self.volume_up,
+            Action::VolumeDown => &self.volume_down,
+            Action::ToggleTheme => &self.toggle_theme,
+            Action::ToggleLanguage => &self.toggle_language,
+            Action::CycleSort => &self.cycle_sort,
+            Action::ConnectFavorite(_) | Action::Bind(_) | Action::Background(_) => &[],
+        }
+    }
+
+    /// Replace the bindings of the action which are of the same kind as
+    /// `binding`, so remapping the buttons keeps the keyboard working.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let bindings = match action {
+            Action::Quit => &mut self.quit,
+            Action::PowerOn => &mut self.power_on,
+            Action::PowerOff => &mut self.power_off,
+            Action::Move(Direction::Up) => &mut self.up,
+            Action::Move(Direction::Right) => &mut self.right,
+            Action::Move(Direction::Down) => &mut self.down,
+            Action::Move(Direction::Left) => &mut self.left,
+            Action::Rescan => &mut self.rescan,
+            Action::Connect => &mut self.connect,
+            Action::Disconnect => &mut self.disconnect,
+            Action::Forget => &mut self.forget,
+            Action::ReconnectLast => &mut self.reconnect_last,
+            Action::ToggleFavorite => &mut self.toggle_favorite,
+            Action::ShowLogs => &mut self.show_logs,
+            Action::Profiles => &mut self.profiles,
+            Action::Network => &mut self.network,
+            Action::Remap => &mut self.remap,
+            Action::About => &mut self.about,
+            Action::VolumeUp => &mut self.volume_up,
+            Action::VolumeDown => &mut self.volume_down,
+            Action::ToggleTheme => &mut self.toggle_theme,
+            Action::ToggleLanguage => &mut self.toggle_language,
+            Action::CycleSort => &mut self.cycle_sort,
+            Action::ConnectFavorite(_) | Action::Bind(_) | Action::Background(_) => return,
+        };
+        bindings.retain(|old| old.is_key() != binding.is_key());
+        bindings.push(binding);
+    }
+}
+
+/// The number keys 1 to 9, for the first nine favorites.
+fn favorite_hotkey(keycode: Keycode) -> Option<usize> {
+    let index = match keycode {
+        Keycode::Num1 => 0,
+        Keycode::Num2 => 1,
+        Keycode::Num3 => 2,
+        Keycode::Num4 => 3,
+        Keycode::Num5 => 4,
+        Keycode::Num6 => 5,
+        Keycode::Num7 => 6,
+        Keycode::Num8 => 7,
+        Keycode::Num9 => 8,
+        _ => return None,
+    };
+    Some(index)
+}
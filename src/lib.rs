@@ -0,0 +1,29 @@
+//! Bluetooth scanning, connecting and state management of the connector,
+//! independent of any display so it can be reused by other frontends.
+
+pub mod bluetooth;
+pub mod control;
+pub mod dbus;
+pub mod error;
+pub mod resume;
+pub mod state;
+pub mod volume_keys;
+
+pub use crate::bluetooth::{
+    backend::{bluez::BluezBackend, mock::MockBackend, BluetoothBackend},
+    battery::{BudBattery, BudsBattery},
+    connector::Connector,
+    manager::{
+        BluetoothManager, Command, ExitPolicy, ManagerHandle, ManagerOptions, CONNECT_TIMEOUT,
+    },
+    scanner::Scanner,
+    AudioProfile, BluetoothConnectState, BluetoothEvent, BluetoothScanStatus, BluezVersion,
+    ConnectStage, DeviceDetails, DeviceInfo, DeviceSettings, LinkQuality, LinkStats, NowPlaying,
+    PacketStats, PairingAdvertisement, TetherState,
+};
+pub use crate::{
+    control::ControlServer,
+    dbus::DbusService,
+    error::{Error, Result},
+    state::AppState,
+};
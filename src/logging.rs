@@ -0,0 +1,115 @@
+use crate::{config::Config, crash::RecentLogs};
+use std::io;
+use tokio::sync::watch;
+use tracing::{info, warn};
+use tracing_subscriber::{
+    fmt, fmt::writer::BoxMakeWriter, prelude::*, reload, EnvFilter, Registry,
+};
+
+/// What is logged until the config is read, and when it sets nothing.
+pub const DEFAULT_FILTER: &str = "debug";
+
+/// Where the log lines are printed, they're kept in `RecentLogs` anyway.
+#[derive(Clone, Copy, Default)]
+pub enum LogOutput {
+    #[default]
+    Stdout,
+    /// When stdout is the output of a command.
+    Stderr,
+    /// When the terminal is the UI, or the journal has the logs.
+    None,
+}
+
+/// Sends the logs to systemd-journald, next to the logs of bluetoothd.
+#[cfg(feature = "journald")]
+pub type Journald = tracing_journald::Layer;
+#[cfg(not(feature = "journald"))]
+pub type Journald = tracing_subscriber::layer::Identity;
+
+/// The filter of the installed subscriber, `RUST_LOG` wins over the config
+/// when it's set.
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    from_env: bool,
+}
+
+/// The journald layer of a daemon, when the journal can be reached. Runs
+/// before the subscriber exists, so a failure goes to stderr.
+#[cfg(feature = "journald")]
+pub fn journald(daemon: bool) -> Option<Journald> {
+    if !daemon {
+        return None;
+    }
+    match tracing_journald::layer() {
+        Ok(layer) => Some(layer),
+        Err(err) => {
+            eprintln!("connect journald failed, log to stderr: {err}");
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "journald"))]
+pub fn journald(_daemon: bool) -> Option<Journald> {
+    None
+}
+
+/// Install the global subscriber, filtered by `RUST_LOG` or the default until
+/// the config is applied.
+pub fn init(
+    output: LogOutput, recent_logs: RecentLogs, journald: Option<Journald>,
+) -> anyhow::Result<LogFilter> {
+    let env_filter = EnvFilter::try_from_default_env().ok();
+    let from_env = env_filter.is_some();
+    let (filter, handle) =
+        reload::Layer::new(env_filter.unwrap_or_else(|| EnvFilter::new(DEFAULT_FILTER)));
+
+    let output = match output {
+        LogOutput::Stdout => Some(BoxMakeWriter::new(io::stdout)),
+        LogOutput::Stderr => Some(BoxMakeWriter::new(io::stderr)),
+        LogOutput::None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(output.map(|writer| fmt::layer().with_writer(writer)))
+        // plain text, for crash reports and the log screen
+        .with(fmt::layer().with_ansi(false).with_writer(recent_logs))
+        .with(journald)
+        .try_init()?;
+
+    Ok(LogFilter { handle, from_env })
+}
+
+impl LogFilter {
+    /// Switch to the `log_filter` of the config, an invalid one keeps the
+    /// current filter.
+    pub fn apply(&self, config: &Config) {
+        if self.from_env {
+            return;
+        }
+
+        match EnvFilter::try_new(&config.log_filter) {
+            Ok(filter) => {
+                if let Err(err) = self.handle.reload(filter) {
+                    warn!(%err, "change log filter failed");
+                    return;
+                }
+                info!(filter = config.log_filter, "change log filter");
+            }
+            Err(err) => {
+                warn!(filter = config.log_filter, %err, "invalid log filter, keep the current one");
+            }
+        }
+    }
+
+    /// Follow the filter of the config file while it's edited.
+    pub fn spawn_reloader(self, mut config_rx: watch::Receiver<Config>) {
+        tokio::spawn(async move {
+            while config_rx.changed().await.is_ok() {
+                let config = config_rx.borrow_and_update().clone();
+                self.apply(&config);
+            }
+        });
+    }
+}
@@ -0,0 +1,148 @@
+use bluetooth_audio_connector_tg5040::{AppState, BluetoothConnectState, BluetoothScanStatus};
+use std::{
+    fmt::Write,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    time::sleep,
+};
+use tracing::{info, warn};
+
+// an accept failing like on EMFILE fails again at once, until a descriptor is
+// closed
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Default)]
+struct Metrics {
+    scans: u64,
+    connect_successes: u64,
+    connect_failures: u64,
+    // successful connects of a device connected before in this run
+    reconnects: u64,
+    connected: bool,
+}
+
+impl Metrics {
+    fn update(&mut self, last: &AppState, state: &AppState) {
+        if state.scan_status == BluetoothScanStatus::Scanning
+            && last.scan_status != BluetoothScanStatus::Scanning
+        {
+            self.scans += 1;
+        }
+
+        if last.connect_state.is_in_progress() {
+            match state.connect_state {
+                BluetoothConnectState::Connected => {
+                    self.connect_successes += 1;
+                    if last.last_connected.is_some() && last.last_connected == state.last_connected
+                    {
+                        self.reconnects += 1;
+                    }
+                }
+                BluetoothConnectState::Failed { .. } => self.connect_failures += 1,
+                _ => {}
+            }
+        }
+
+        self.connected = state.connected_device().is_some();
+    }
+
+    /// The Prometheus text exposition format.
+    fn render(&self) -> String {
+        let mut text = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: u64| {
+            let _ = writeln!(text, "# HELP {name} {help}");
+            let _ = writeln!(text, "# TYPE {name} {kind}");
+            let _ = writeln!(text, "{name} {value}");
+        };
+        metric(
+            "bt_audio_scans_total",
+            "counter",
+            "Scans started.",
+            self.scans,
+        );
+        metric(
+            "bt_audio_connect_successes_total",
+            "counter",
+            "Connects which succeeded.",
+            self.connect_successes,
+        );
+        metric(
+            "bt_audio_connect_failures_total",
+            "counter",
+            "Connects which failed.",
+            self.connect_failures,
+        );
+        metric(
+            "bt_audio_reconnects_total",
+            "counter",
+            "Connects of a device connected before.",
+            self.reconnects,
+        );
+        metric(
+            "bt_audio_connected",
+            "gauge",
+            "Whether a device is connected.",
+            self.connected as u64,
+        );
+        text
+    }
+}
+
+/// Count what the `BluetoothManager` does and serve it over HTTP, for graphing
+/// the reliability of headsets.
+pub async fn spawn(
+    addr: SocketAddr, mut state_rx: watch::Receiver<AppState>,
+) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "serve metrics");
+
+    let metrics = Arc::new(Mutex::new(Metrics::default()));
+
+    tokio::spawn({
+        let metrics = metrics.clone();
+        async move {
+            let mut last = state_rx.borrow_and_update().clone();
+            while state_rx.changed().await.is_ok() {
+                let state = state_rx.borrow_and_update().clone();
+                metrics.lock().unwrap().update(&last, &state);
+                last = state;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let body = metrics.lock().unwrap().render();
+                    tokio::spawn(respond(stream, body));
+                }
+                Err(err) => {
+                    warn!(?err, "accept metrics client failed");
+                    sleep(ACCEPT_RETRY_DELAY).await;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Answer any request with the metrics, there is only one page.
+async fn respond(mut stream: TcpStream, body: String) {
+    let mut request = [0; 1024];
+    let _ = stream.read(&mut request).await;
+
+    let response = format!(
+        "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+}
@@ -0,0 +1,87 @@
+use std::path::Path;
+use toml::Table;
+use tracing::{info, warn};
+
+/// A step from one version of a file format to the next, on the raw TOML so
+/// renamed or moved keys can be carried over before serde drops them.
+pub type Migration = fn(&mut Table);
+
+/// Bring a parsed file up to `migrations.len()`, the version it's read with.
+/// The step from version `n` is `migrations[n]`, files written before the
+/// versioning have no `version` and are version 0.
+pub fn migrate(path: &Path, table: &mut Table, migrations: &[Migration]) {
+    let current = migrations.len() as i64;
+    let version = table
+        .get("version")
+        .and_then(|version| version.as_integer())
+        .unwrap_or(0);
+
+    if version > current {
+        warn!(
+            ?path,
+            version, current, "file of a newer release, unknown keys are ignored"
+        );
+        return;
+    }
+    if version == current {
+        return;
+    }
+
+    for migration in &migrations[version.max(0) as usize..] {
+        migration(table);
+    }
+    table.insert("version".to_string(), current.into());
+    info!(?path, from = version, to = current, "migrate file format");
+}
+
+/// Files written before the versioning only lack the version.
+pub fn add_version(_table: &mut Table) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_volume(table: &mut Table) {
+        if let Some(volume) = table.remove("default_volume") {
+            table.insert("volume".to_string(), volume);
+        }
+    }
+
+    fn migrated(text: &str) -> Table {
+        let mut table: Table = text.parse().unwrap();
+        migrate(
+            Path::new("test.toml"),
+            &mut table,
+            &[add_version, rename_volume],
+        );
+        table
+    }
+
+    #[test]
+    fn unversioned_file_runs_every_step() {
+        let table = migrated("default_volume = 50");
+        assert_eq!(table["version"].as_integer(), Some(2));
+        assert_eq!(table["volume"].as_integer(), Some(50));
+        assert!(!table.contains_key("default_volume"));
+    }
+
+    #[test]
+    fn only_later_steps_run() {
+        let table = migrated("version = 1\ndefault_volume = 50");
+        assert_eq!(table["version"].as_integer(), Some(2));
+        assert_eq!(table["volume"].as_integer(), Some(50));
+    }
+
+    #[test]
+    fn current_and_newer_files_are_left_alone() {
+        for text in [
+            "version = 2\ndefault_volume = 50",
+            "version = 3\ndefault_volume = 50",
+        ] {
+            let table = migrated(text);
+            assert_eq!(table["default_volume"].as_integer(), Some(50));
+            assert!(!table.contains_key("volume"));
+        }
+        assert_eq!(migrated("version = 3")["version"].as_integer(), Some(3));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{cli::StatusOutput, config::MqttConfig};
+use anyhow::Context;
+use bluetooth_audio_connector_tg5040::AppState;
+use rumqttc::{AsyncClient, Event, LastWill, MqttOptions, Packet, QoS};
+use std::time::Duration;
+use tokio::{sync::watch, time::sleep};
+use tracing::{debug, info, warn};
+
+const DEFAULT_PORT: u16 = 1883;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+// the wait before connecting the broker again after it was lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+// requests queued for the broker, the status is only published on changes
+const REQUEST_CAPACITY: usize = 10;
+
+/// Publish the status to the MQTT broker of the config for home automation:
+/// the JSON of `--status --json` to `<topic>/status` and the battery of the
+/// connected device to `<topic>/battery`, both retained. `<topic>/availability`
+/// is `online` while the app runs and `offline` otherwise, by the last will.
+pub fn spawn(config: &MqttConfig, state_rx: watch::Receiver<AppState>) -> anyhow::Result<()> {
+    let (host, port) = broker_address(&config.broker)?;
+    let topic = config.topic.trim_end_matches('/').to_string();
+
+    let mut options = MqttOptions::new(&config.client_id, host, port);
+    options
+        .set_keep_alive(KEEP_ALIVE)
+        .set_last_will(LastWill::new(
+            format!("{topic}/availability"),
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.as_deref().unwrap_or_default());
+    }
+
+    let (client, mut event_loop) = AsyncClient::new(options, REQUEST_CAPACITY);
+    info!(host, port, topic, "publish status to mqtt");
+
+    tokio::spawn(publish_status(
+        client.clone(),
+        topic.clone(),
+        state_rx.clone(),
+    ));
+    tokio::spawn(async move {
+        loop {
+            match event_loop.poll().await {
+                // the last will made it offline when the broker was lost, and
+                // the status may have changed meanwhile
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!("mqtt broker connected");
+                    let state = state_rx.borrow().clone();
+                    if let Err(err) = publish(&client, &topic, &state, true) {
+                        warn!(?err, "publish mqtt status failed");
+                    }
+                }
+                Ok(event) => debug!(?event, "mqtt event"),
+                Err(err) => {
+                    warn!(?err, "mqtt broker lost, connect again later");
+                    sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The host and port of `host`, `host:port`, an IPv6 address or one in
+/// brackets with a port like `[::1]:1883`.
+fn broker_address(broker: &str) -> anyhow::Result<(&str, u16)> {
+    if let Some(bracketed) = broker.strip_prefix('[') {
+        let (host, rest) = bracketed
+            .split_once(']')
+            .context("missing ] after the broker address")?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse().context("invalid broker port")?,
+            None if rest.is_empty() => DEFAULT_PORT,
+            None => anyhow::bail!("unexpected {rest:?} after the broker address"),
+        };
+        return Ok((host, port));
+    }
+    match broker.split_once(':') {
+        // more colons are an IPv6 address without a port
+        Some((_, rest)) if rest.contains(':') => Ok((broker, DEFAULT_PORT)),
+        Some((host, port)) => Ok((host, port.parse().context("invalid broker port")?)),
+        None => Ok((broker, DEFAULT_PORT)),
+    }
+}
+
+/// Publish each change of the status, while the app runs.
+async fn publish_status(
+    client: AsyncClient, topic: String, mut state_rx: watch::Receiver<AppState>,
+) {
+    let mut published = None;
+    while state_rx.changed().await.is_ok() {
+        let state = state_rx.borrow_and_update().clone();
+        let status = serde_json::to_string(&StatusOutput::from(&state)).ok();
+        // the state changes with every device found, the status rarely
+        if status == published {
+            continue;
+        }
+        if let Err(err) = publish(&client, &topic, &state, false) {
+            warn!(?err, "publish mqtt status failed");
+        }
+        published = status;
+    }
+}
+
+/// Queue the messages without waiting, the event loop calls it too and would
+/// wait for itself on a full queue.
+fn publish(
+    client: &AsyncClient, topic: &str, state: &AppState, online: bool,
+) -> anyhow::Result<()> {
+    if online {
+        client.try_publish(
+            format!("{topic}/availability"),
+            QoS::AtLeastOnce,
+            true,
+            "online",
+        )?;
+    }
+
+    let status = serde_json::to_string(&StatusOutput::from(state))?;
+    client.try_publish(format!("{topic}/status"), QoS::AtLeastOnce, true, status)?;
+
+    // empty while nothing is connected, or the device doesn't tell it
+    let battery = state
+        .connected_device()
+        .and_then(|info| info.battery)
+        .map(|battery| battery.to_string())
+        .unwrap_or_default();
+    client.try_publish(format!("{topic}/battery"), QoS::AtLeastOnce, true, battery)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broker_addresses() {
+        let address = |broker| broker_address(broker).unwrap();
+        assert_eq!(address("broker.local"), ("broker.local", DEFAULT_PORT));
+        assert_eq!(address("broker.local:8883"), ("broker.local", 8883));
+        assert_eq!(address("fd00::1"), ("fd00::1", DEFAULT_PORT));
+        assert_eq!(address("[fd00::1]"), ("fd00::1", DEFAULT_PORT));
+        assert_eq!(address("[fd00::1]:8883"), ("fd00::1", 8883));
+    }
+
+    #[test]
+    fn invalid_broker_addresses() {
+        for broker in [
+            "broker.local:port",
+            "[fd00::1",
+            "[fd00::1]8883",
+            "[fd00::1]:",
+        ] {
+            assert!(broker_address(broker).is_err(), "{broker}");
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    config::Config,
+    data::{AppData, SavedDevice},
+    exit::ExitStatus,
+    signals::QuitSignals,
+    ui::screens::{Screen, SplashProgress, SplashScreen},
+};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{
+    BluetoothBackend, BluetoothConnectState, BluetoothManager, BluetoothScanStatus, Command,
+    DeviceInfo, ExitPolicy, CONNECT_TIMEOUT,
+};
+use std::{pin::pin, sync::Arc, time::Duration};
+use tokio::{
+    sync::{oneshot, watch},
+    time::sleep,
+};
+use tracing::{error, info, warn};
+
+// how long the result stays on the screen before the exit
+const RESULT_TIME: Duration = Duration::from_secs(2);
+
+/// `--toggle`: disconnect the preferred device when it's connected and connect
+/// it otherwise, like the Bluetooth toggle of a phone. Without a preferred
+/// device the one connected last is used. Only the splash screen is shown,
+/// with the result for `RESULT_TIME` at the end. A connect fails after
+/// `CONNECT_TIMEOUT`.
+pub async fn toggle(
+    backend: Arc<dyn BluetoothBackend>, config: &Config, data: &AppData,
+    screen_tx: &watch::Sender<Screen>, mut signals: QuitSignals,
+) -> anyhow::Result<ExitStatus> {
+    let target = config
+        .preferred_device()
+        .or_else(|| data.last_device.as_ref().and_then(SavedDevice::addr));
+    let Some(addr) = target else {
+        warn!("no device to toggle");
+        let splash = SplashScreen {
+            name: None,
+            progress: SplashProgress::NoDevice,
+        };
+        show_result(screen_tx, splash, &mut signals).await;
+        return Ok(ExitStatus::NoDevice);
+    };
+
+    let info = backend.properties(addr).await.ok();
+    let mut splash = SplashScreen {
+        name: Some(device_name(data, addr, info.as_ref())),
+        progress: SplashProgress::Disconnecting,
+    };
+
+    let status = if info.is_some_and(|info| info.connected) {
+        info!(%addr, "toggle off");
+        screen_tx.send_replace(Screen::Splash(splash.clone()));
+        match backend.disconnect(addr).await {
+            Ok(()) => {
+                splash.progress = SplashProgress::Disconnected;
+                ExitStatus::Success
+            }
+            Err(err) => {
+                error!(?err, %addr, "disconnect device failed");
+                splash.progress = SplashProgress::DisconnectFailed(err);
+                ExitStatus::Error
+            }
+        }
+    } else {
+        info!(%addr, "toggle on");
+        tokio::select! {
+            status = connect(backend, config, data, addr, &mut splash, screen_tx) => status,
+            name = signals.recv() => {
+                info!(signal = name, "toggle cancelled by signal");
+                return Ok(ExitStatus::Cancelled);
+            }
+        }
+    };
+
+    show_result(screen_tx, splash, &mut signals).await;
+    Ok(status)
+}
+
+/// `--device`: connect the device without the device list, scanning for it
+/// first when BlueZ doesn't know it. Only the splash screen is shown, with the
+/// result for `RESULT_TIME` at the end. The scan and the connect fail after
+/// `CONNECT_TIMEOUT` together.
+pub async fn connect_device(
+    backend: Arc<dyn BluetoothBackend>, config: &Config, data: &AppData, addr: Address,
+    screen_tx: &watch::Sender<Screen>, mut signals: QuitSignals,
+) -> anyhow::Result<ExitStatus> {
+    let info = backend.properties(addr).await.ok();
+    let mut splash = SplashScreen {
+        name: Some(device_name(data, addr, info.as_ref())),
+        progress: SplashProgress::Connect(BluetoothConnectState::Idle),
+    };
+
+    info!(%addr, "connect device");
+    let status = tokio::select! {
+        status = connect(backend, config, data, addr, &mut splash, screen_tx) => status,
+        name = signals.recv() => {
+            info!(signal = name, "connect cancelled by signal");
+            return Ok(ExitStatus::Cancelled);
+        }
+    };
+
+    show_result(screen_tx, splash, &mut signals).await;
+    Ok(status)
+}
+
+/// The nickname, the name of the device or else its address.
+fn device_name(data: &AppData, addr: Address, info: Option<&DeviceInfo>) -> String {
+    match (data.nickname(addr), info) {
+        (Some(nickname), _) => nickname.to_string(),
+        (None, Some(info)) if !info.name.is_empty() => info.name.clone(),
+        _ => addr.to_string(),
+    }
+}
+
+/// Connect through a manager, the splash follows its stages like the auto
+/// connect screen of the app. Given up after `CONNECT_TIMEOUT`.
+async fn connect(
+    backend: Arc<dyn BluetoothBackend>, config: &Config, data: &AppData, addr: Address,
+    splash: &mut SplashScreen, screen_tx: &watch::Sender<Screen>,
+) -> ExitStatus {
+    let mut options = config.manager_options(data);
+    // connected by the command, which answers how it ended
+    options.auto_connect = None;
+    options.scan_at_start = false;
+    // the point is to keep it connected after the exit
+    options.exit_policy = ExitPolicy::KeepConnected;
+    let mut manager = BluetoothManager::spawn(backend, options);
+
+    let (reply_tx, mut reply_rx) = oneshot::channel();
+    let command = Command::ConnectAndReply {
+        addr,
+        ensure: true,
+        reply_tx,
+    };
+    if manager.command_tx.send(command).await.is_err() {
+        manager.shutdown().await;
+        return ExitStatus::Error;
+    }
+
+    let mut deadline = pin!(sleep(CONNECT_TIMEOUT));
+    let status = loop {
+        let state = manager.state_rx.borrow_and_update().clone();
+        // the device is scanned for before the connect starts
+        splash.progress = match state.connect_state {
+            BluetoothConnectState::Idle if state.scan_status == BluetoothScanStatus::Scanning => {
+                SplashProgress::Searching
+            }
+            connect_state => SplashProgress::Connect(connect_state),
+        };
+        screen_tx.send_replace(Screen::Splash(splash.clone()));
+
+        tokio::select! {
+            reply = &mut reply_rx => break match reply {
+                Ok(Ok(())) => {
+                    splash.progress = SplashProgress::Connect(BluetoothConnectState::Connected);
+                    ExitStatus::Success
+                }
+                Ok(Err(err)) => {
+                    error!(?err, %addr, "connect device failed");
+                    // a failed stage is shown by the state already
+                    let state = manager.state_rx.borrow().connect_state.clone();
+                    splash.progress = match state {
+                        failed @ BluetoothConnectState::Failed { .. } => {
+                            SplashProgress::Connect(failed)
+                        }
+                        _ => SplashProgress::Failed(err),
+                    };
+                    ExitStatus::ConnectFailed
+                }
+                // nothing cancels it, so the manager stopped
+                Err(_) => ExitStatus::Error,
+            },
+            changed = manager.state_rx.changed() => {
+                if changed.is_err() {
+                    break ExitStatus::Error;
+                }
+            }
+            () = &mut deadline => {
+                warn!(%addr, timeout = ?CONNECT_TIMEOUT, "connect took too long, give up");
+                splash.progress = SplashProgress::TimedOut;
+                break ExitStatus::ConnectFailed;
+            }
+        }
+    };
+
+    // stops a connect given up
+    manager.shutdown().await;
+    status
+}
+
+/// Show the result until the time is up, or a signal cuts it short.
+async fn show_result(
+    screen_tx: &watch::Sender<Screen>, splash: SplashScreen, signals: &mut QuitSignals,
+) {
+    screen_tx.send_replace(Screen::Splash(splash));
+    tokio::select! {
+        () = sleep(RESULT_TIME) => {}
+        _ = signals.recv() => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bluetooth_audio_connector_tg5040::MockBackend;
+
+    #[tokio::test(start_paused = true)]
+    async fn toggle_without_a_device() {
+        let backend = Arc::new(MockBackend::new(Vec::new()));
+        let (screen_tx, screen_rx) = watch::channel(Screen::default());
+        let status = toggle(
+            backend,
+            &Config::default(),
+            &AppData::default(),
+            &screen_tx,
+            QuitSignals::new().unwrap(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(status, ExitStatus::NoDevice);
+        let Screen::Splash(splash) = &*screen_rx.borrow() else {
+            panic!("no splash shown");
+        };
+        // SDL_ttf can't render an empty name
+        assert_eq!(splash.name, None);
+        assert!(matches!(splash.progress, SplashProgress::NoDevice));
+    }
+}
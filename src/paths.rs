@@ -0,0 +1,47 @@
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
+
+// subdirectory of the XDG base directories
+const APP_DIR: &str = env!("CARGO_PKG_NAME");
+
+/// Directory of the binary, where the pak keeps its files.
+pub fn exe_dir() -> io::Result<PathBuf> {
+    let exe = env::current_exe()?;
+    exe.parent()
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "binary has no parent directory"))
+}
+
+/// Where `config.toml` and the hooks live.
+pub fn config_dir() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME")
+}
+
+/// Where the app data and crash reports live.
+pub fn data_dir() -> PathBuf {
+    base_dir("XDG_DATA_HOME")
+}
+
+/// The XDG directory when its variable is set, or else the directory of the
+/// binary, which is the layout of a pak on the SD card.
+fn base_dir(var: &str) -> PathBuf {
+    match env::var_os(var) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir).join(APP_DIR),
+        _ => exe_dir().unwrap_or_default(),
+    }
+}
+
+/// Resolve a relative path of the config, such as the font, against the
+/// directory of the binary, keeping the working directory only as a fallback
+/// for `cargo run`.
+pub fn resolve(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    match exe_dir() {
+        Ok(dir) if dir.join(path).exists() => dir.join(path),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fs, path::Path, time::Duration};
+use tokio::{sync::watch, time::interval};
+use tracing::{debug, info};
+
+const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply";
+
+// the level moves slowly, a pairing takes a minute or so
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Below this the app warns that the handheld may die while pairing.
+pub const LOW_BATTERY: u8 = 10;
+
+/// The battery of the handheld itself, not of a Bluetooth device.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct HandheldBattery {
+    /// Percent.
+    pub level: u8,
+    pub charging: bool,
+}
+
+impl HandheldBattery {
+    pub fn is_low(&self) -> bool {
+        self.level < LOW_BATTERY && !self.charging
+    }
+}
+
+/// Read the battery of the handheld now and then, `None` when it has none,
+/// like on a desktop.
+pub fn spawn() -> watch::Receiver<Option<HandheldBattery>> {
+    let (battery_tx, battery_rx) = watch::channel(read());
+
+    tokio::spawn(async move {
+        let mut interval = interval(POLL_INTERVAL);
+        while !battery_tx.is_closed() {
+            interval.tick().await;
+            battery_tx.send_if_modified(|battery| {
+                let current = read();
+                if current == *battery {
+                    return false;
+                }
+                debug!(?current, "handheld battery changed");
+                *battery = current;
+                true
+            });
+        }
+    });
+
+    if let Some(battery) = *battery_rx.borrow() {
+        info!(
+            level = battery.level,
+            charging = battery.charging,
+            "handheld battery"
+        );
+    }
+    battery_rx
+}
+
+/// The first power supply of the `Battery` type.
+fn read() -> Option<HandheldBattery> {
+    fs::read_dir(POWER_SUPPLY_DIR)
+        .ok()?
+        .flatten()
+        .find_map(|entry| read_supply(&entry.path()))
+}
+
+fn read_supply(path: &Path) -> Option<HandheldBattery> {
+    let read = |name: &str| fs::read_to_string(path.join(name)).ok();
+    if read("type")?.trim() != "Battery" {
+        return None;
+    }
+    let level = read("capacity")?.trim().parse::<u8>().ok()?.min(100);
+    let charging =
+        read("status").is_some_and(|status| matches!(status.trim(), "Charging" | "Full"));
+    Some(HandheldBattery { level, charging })
+}
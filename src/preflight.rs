@@ -0,0 +1,136 @@
+use crate::{
+    input::Action,
+    signals::QuitSignals,
+    ui::screens::{PreflightScreen, Screen},
+};
+use bluetooth_audio_connector_tg5040::{BluezBackend, Error};
+use std::{env, fs, path::PathBuf};
+use tokio::sync::{mpsc, watch};
+use tracing::{info, warn};
+use zbus::{fdo::DBusProxy, names::BusName, Connection};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const BLUEALSA_SERVICE: &str = "org.bluealsa";
+
+/// What the connector needs from the system, in the order they're checked.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Component {
+    Bluetoothd,
+    Adapter,
+    /// Bluetooth blocked by rfkill, like by an airplane mode.
+    Rfkill,
+    /// PulseAudio, PipeWire or BlueALSA, which plays the audio of the device.
+    AudioServer,
+}
+
+/// Check the system and connect to bluetoothd, showing what is wrong until
+/// it's fixed. A retries, Y goes on anyway and quitting returns `None`.
+pub async fn until_ready(
+    action_rx: &mut mpsc::UnboundedReceiver<Action>, screen_tx: &watch::Sender<Screen>,
+    signals: &mut QuitSignals,
+) -> Option<BluezBackend> {
+    let mut ignore_problems = false;
+    loop {
+        let mut problems = check().await;
+        if problems.is_empty() || ignore_problems {
+            match BluezBackend::new().await {
+                Ok(backend) => return Some(backend),
+                Err(err) => {
+                    warn!(?err, "connect bluetoothd failed");
+                    problems = vec![match err {
+                        Error::AdapterMissing => Component::Adapter,
+                        _ => Component::Bluetoothd,
+                    }];
+                }
+            }
+        }
+
+        warn!(?problems, "system isn't ready for bluetooth audio");
+        screen_tx.send_replace(Screen::Preflight(PreflightScreen { problems }));
+
+        loop {
+            let action = tokio::select! {
+                action = action_rx.recv() => action?,
+                name = signals.recv() => {
+                    info!(signal = name, "quit by signal");
+                    return None;
+                }
+            };
+            match action {
+                Action::Quit => return None,
+                Action::Connect => break,
+                Action::PowerOn => {
+                    info!("ignore the failed checks");
+                    ignore_problems = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The components which aren't working.
+async fn check() -> Vec<Component> {
+    let connection = Connection::system().await.ok();
+    let mut problems = Vec::new();
+
+    if !has_owner(connection.as_ref(), BLUEZ_SERVICE).await {
+        problems.push(Component::Bluetoothd);
+    }
+    if !has_adapter() {
+        problems.push(Component::Adapter);
+    } else if is_blocked() {
+        problems.push(Component::Rfkill);
+    }
+    if !has_audio_socket() && !has_owner(connection.as_ref(), BLUEALSA_SERVICE).await {
+        problems.push(Component::AudioServer);
+    }
+    problems
+}
+
+async fn has_owner(connection: Option<&Connection>, service: &'static str) -> bool {
+    let Some(connection) = connection else {
+        return false;
+    };
+    let Ok(proxy) = DBusProxy::new(connection).await else {
+        return false;
+    };
+    proxy
+        .name_has_owner(BusName::from_static_str_unchecked(service))
+        .await
+        .unwrap_or(false)
+}
+
+fn has_adapter() -> bool {
+    fs::read_dir("/sys/class/bluetooth")
+        .map(|entries| {
+            entries
+                .flatten()
+                .any(|entry| entry.file_name().to_string_lossy().starts_with("hci"))
+        })
+        .unwrap_or(false)
+}
+
+/// Whether a Bluetooth rfkill switch is on, in software or hardware.
+fn is_blocked() -> bool {
+    let Ok(entries) = fs::read_dir("/sys/class/rfkill") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        let read = |name: &str| fs::read_to_string(path.join(name)).unwrap_or_default();
+        read("type").trim() == "bluetooth"
+            && (read("soft").trim() == "1" || read("hard").trim() == "1")
+    })
+}
+
+/// The socket of PulseAudio or PipeWire, of the user or of a system instance.
+fn has_audio_socket() -> bool {
+    let mut dirs = vec![PathBuf::from("/run"), PathBuf::from("/var/run")];
+    if let Some(runtime_dir) = env::var_os("XDG_RUNTIME_DIR") {
+        dirs.insert(0, PathBuf::from(runtime_dir));
+    }
+    dirs.iter()
+        .any(|dir| dir.join("pulse/native").exists() || dir.join("pipewire-0").exists())
+}
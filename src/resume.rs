@@ -0,0 +1,109 @@
+use crate::bluetooth::manager::Command;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::{sync::mpsc, time::interval};
+use tokio_stream::StreamExt;
+use tracing::{debug, info, warn};
+use zbus::{dbus_proxy, fdo::DBusProxy, names::BusName, Connection};
+
+const LOGIND_SERVICE: &str = "org.freedesktop.login1";
+
+// how often the clocks are compared without logind
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// the wall clock running ahead of the monotonic one by more than this means
+// the system slept, smaller jumps are NTP corrections
+const CLOCK_JUMP: Duration = Duration::from_secs(15);
+
+#[dbus_proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    /// Sent with `true` before the system sleeps and `false` after it wakes.
+    #[dbus_proxy(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+/// Send `Command::Revalidate` each time the system wakes up, told by logind
+/// when it runs and guessed from the clocks otherwise, like on the stock
+/// firmware of the tg5040.
+pub fn spawn(command_tx: mpsc::Sender<Command>) {
+    tokio::spawn(async move {
+        match sleep_signals().await {
+            Ok(Some(signals)) => {
+                info!("detect resume by logind");
+                logind_resumes(signals, &command_tx).await;
+            }
+            Ok(None) => {
+                debug!("logind isn't running, detect resume by the clocks");
+                clock_resumes(&command_tx).await;
+            }
+            Err(err) => {
+                debug!(?err, "connect logind failed, detect resume by the clocks");
+                clock_resumes(&command_tx).await;
+            }
+        }
+    });
+}
+
+/// The sleep signals of logind, `None` when it isn't running.
+async fn sleep_signals() -> zbus::Result<Option<PrepareForSleepStream<'static>>> {
+    let connection = Connection::system().await?;
+    let service = BusName::try_from(LOGIND_SERVICE)?;
+    if !DBusProxy::new(&connection)
+        .await?
+        .name_has_owner(service)
+        .await?
+    {
+        return Ok(None);
+    }
+
+    let manager = LoginManagerProxy::new(&connection).await?;
+    Ok(Some(manager.receive_prepare_for_sleep().await?))
+}
+
+async fn logind_resumes(
+    mut signals: PrepareForSleepStream<'static>, command_tx: &mpsc::Sender<Command>,
+) {
+    while let Some(signal) = signals.next().await {
+        let start = match signal.args() {
+            Ok(args) => args.start,
+            Err(err) => {
+                warn!(?err, "invalid PrepareForSleep signal");
+                continue;
+            }
+        };
+        if start {
+            info!("system is going to sleep");
+            continue;
+        }
+        info!("system resumed");
+        if command_tx.send(Command::Revalidate).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// The monotonic clock stops while the system sleeps and the wall clock
+/// doesn't, so the wall clock jumping ahead of it means a resume.
+async fn clock_resumes(command_tx: &mpsc::Sender<Command>) {
+    let mut interval = interval(CLOCK_CHECK_INTERVAL);
+    let mut last = (Instant::now(), SystemTime::now());
+
+    loop {
+        interval.tick().await;
+
+        let now = (Instant::now(), SystemTime::now());
+        let monotonic = now.0.duration_since(last.0);
+        let wall = now.1.duration_since(last.1).unwrap_or_default();
+        last = now;
+
+        if wall.saturating_sub(monotonic) > CLOCK_JUMP {
+            info!(slept = ?wall - monotonic, "system resumed");
+            if command_tx.send(Command::Revalidate).await.is_err() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use crate::{
+    config::{Config, Language},
+    input::Action,
+    paths,
+    tone::TonePlayer,
+    ui::{
+        screens::{self, Screen},
+        text_drawer::TextDrawer,
+        texts,
+        theme::Palette,
+        widgets::DeviceList,
+        SCREEN_HEIGHT, SCREEN_WIDTH,
+    },
+};
+use sdl2::{
+    controller::GameController,
+    event::{Event, WindowEvent},
+    image::InitFlag,
+    render::BlendMode,
+    rwops::RWops,
+    ttf::{Font, Sdl2TtfContext},
+    GameControllerSubsystem,
+};
+use std::{
+    collections::HashMap,
+    env,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info, warn};
+
+// controller mapping database in the SDL format, read from the pak and from
+// the config directory
+const CONTROLLER_DB_FILE: &str = "gamecontrollerdb.txt";
+
+// drawn when the font of the config can't be loaded, it has no Chinese so
+// the texts are English then
+const FALLBACK_FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono.ttf");
+
+const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+// in the background only the events are read, twice a second
+const BACKGROUND_FRAME_TIME: Duration = Duration::from_millis(500);
+
+/// Run SDL on its own thread, the window and the event pump must stay on the
+/// thread that created them.
+///
+/// Input is forwarded to `action_tx` as `Action`s, and the latest `Screen` is
+/// drawn at 60 FPS until the sender of `screen_rx` is dropped. Theme, language
+/// and keymap follow `config_rx`, the rest of the config is only read at start.
+pub fn spawn(
+    config_rx: watch::Receiver<Config>, action_tx: mpsc::UnboundedSender<Action>,
+    screen_rx: watch::Receiver<Screen>,
+) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+    let handle = thread::Builder::new()
+        .name("sdl".to_string())
+        .spawn(move || run(config_rx, &action_tx, screen_rx))?;
+    Ok(handle)
+}
+
+fn run(
+    mut config_rx: watch::Receiver<Config>, action_tx: &mpsc::UnboundedSender<Action>,
+    mut screen_rx: watch::Receiver<Screen>,
+) -> anyhow::Result<()> {
+    let config = config_rx.borrow_and_update().clone();
+
+    // SIGTERM and SIGINT are handled by the app, not turned into SDL quit events
+    sdl2::hint::set("SDL_NO_SIGNAL_HANDLERS", "1");
+
+    let sdl_context = sdl2::init().map_err(anyhow::Error::msg)?;
+
+    let video_subsystem = sdl_context.video().map_err(anyhow::Error::msg)?;
+
+    let window = video_subsystem
+        .window(env!("CARGO_CRATE_NAME"), SCREEN_WIDTH, SCREEN_HEIGHT)
+        .position_centered()
+        .build()?;
+
+    let game_controller_subsystem = sdl_context.game_controller().map_err(anyhow::Error::msg)?;
+    load_controller_mappings(&game_controller_subsystem, &config);
+    // every controller sends its buttons, like a gamepad paired next to the
+    // built-in one. Enumerated late on some devices, or plugged in later, the
+    // added event opens it then
+    let mut game_controllers = open_controllers(&game_controller_subsystem);
+    if game_controllers.is_empty() {
+        warn!("no game controller found");
+    }
+
+    let ttf_context = sdl2::ttf::init()?;
+    let (font, fallback_font) = load_font(&ttf_context, &config)?;
+    let language = |language| {
+        if fallback_font {
+            Language::En
+        } else {
+            language
+        }
+    };
+
+    let _image_context = sdl2::image::init(InitFlag::PNG).map_err(anyhow::Error::msg)?;
+
+    let mut canvas = window.into_canvas().build()?;
+    let texture_creator = canvas.texture_creator();
+
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Palette::of(config.theme).background);
+    canvas.clear();
+    canvas.present();
+
+    let mut event_pump = sdl_context.event_pump().map_err(anyhow::Error::msg)?;
+    let mut tone_player = TonePlayer::new(&sdl_context);
+
+    let mut text_drawer = TextDrawer::new(canvas, &texture_creator, font, &config)?;
+    let mut texts = texts::texts(language(config.language));
+
+    let mut keymap = config.keymap.clone();
+    let mut device_list = DeviceList::default();
+    let mut next_frame = Instant::now();
+    let mut backgrounded = false;
+
+    // the app drops the sender once it has quit
+    while screen_rx.has_changed().is_ok() {
+        let remapping = matches!(*screen_rx.borrow(), Screen::Remap(_));
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Window { win_event, .. } => {
+                    let background = match win_event {
+                        WindowEvent::FocusLost | WindowEvent::Hidden | WindowEvent::Minimized => {
+                            true
+                        }
+                        WindowEvent::FocusGained | WindowEvent::Shown | WindowEvent::Restored => {
+                            false
+                        }
+                        _ => backgrounded,
+                    };
+                    if background != backgrounded {
+                        backgrounded = background;
+                        if backgrounded {
+                            text_drawer.release_textures();
+                        } else {
+                            text_drawer.restore_textures(&config_rx.borrow());
+                        }
+                        let _ = action_tx.send(Action::Background(backgrounded));
+                    }
+                }
+                // also sent at start for the ones opened already, opening
+                // again gives the same instance
+                Event::ControllerDeviceAdded { which, .. } => {
+                    if let Some(controller) = open_controller(&game_controller_subsystem, which) {
+                        game_controllers.insert(controller.instance_id(), controller);
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if game_controllers.remove(&which).is_some() {
+                        info!(which, "game controller removed");
+                    }
+                }
+                _ => {}
+            }
+
+            let action = if remapping {
+                Action::bind_from_event(&event)
+            } else {
+                Action::from_event(&event, &keymap)
+            };
+            if let Some(action) = action {
+                let _ = action_tx.send(action);
+            }
+        }
+
+        if config_rx.has_changed().unwrap_or(false) {
+            let config = config_rx.borrow_and_update();
+            text_drawer.set_theme(config.theme);
+            texts = texts::texts(language(config.language));
+            keymap = config.keymap.clone();
+        }
+
+        let mut screen = screen_rx.borrow_and_update().clone();
+        if let Screen::Main(main_screen) = &mut screen {
+            main_screen.no_controller = game_controllers.is_empty();
+        }
+
+        // a game running over the app has the audio
+        tone_player.play(screen.test_tone().filter(|_| !backgrounded));
+        if let Screen::AudioTest(audio_test_screen) = &mut screen {
+            audio_test_screen.channel = tone_player.channel();
+        }
+
+        // nothing is seen of the window, it's drawn again once it's back
+        if !backgrounded {
+            text_drawer.clear()?;
+            screens::draw(&mut text_drawer, &mut device_list, texts, &screen)?;
+            text_drawer.present()?;
+        }
+
+        // sleep only what is left of the frame, skip ahead if drawing was slow
+        next_frame += if backgrounded {
+            BACKGROUND_FRAME_TIME
+        } else {
+            FRAME_TIME
+        };
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => thread::sleep(wait),
+            None => next_frame = Instant::now(),
+        }
+    }
+
+    Ok(())
+}
+
+/// The font of the config, or the embedded one when it's missing so the UI
+/// still shows up. Returns whether the embedded one is used.
+fn load_font<'ttf>(
+    ttf_context: &'ttf Sdl2TtfContext, config: &Config,
+) -> anyhow::Result<(Font<'ttf, 'static>, bool)> {
+    let path = paths::resolve(&config.font);
+    match ttf_context.load_font(&path, config.font_size()) {
+        Ok(font) => Ok((font, false)),
+        Err(err) => {
+            warn!(?path, %err, "load font failed, use the embedded one");
+            let rwops = RWops::from_bytes(FALLBACK_FONT).map_err(anyhow::Error::msg)?;
+            let font = ttf_context
+                .load_font_from_rwops(rwops, config.font_size())
+                .map_err(anyhow::Error::msg)?;
+            Ok((font, true))
+        }
+    }
+}
+
+/// The joysticks SDL has a mapping for, by their instance id.
+fn open_controllers(subsystem: &GameControllerSubsystem) -> HashMap<u32, GameController> {
+    let count = subsystem.num_joysticks().unwrap_or(0);
+    (0..count)
+        .filter(|index| subsystem.is_game_controller(*index))
+        .filter_map(|index| open_controller(subsystem, index))
+        .map(|controller| (controller.instance_id(), controller))
+        .collect()
+}
+
+fn open_controller(subsystem: &GameControllerSubsystem, index: u32) -> Option<GameController> {
+    match subsystem.open(index) {
+        Ok(controller) => {
+            info!(name = controller.name(), "open game controller");
+            debug!(mapping = controller.mapping(), "controller mapping");
+            Some(controller)
+        }
+        Err(err) => {
+            warn!(index, %err, "open game controller failed");
+            None
+        }
+    }
+}
+
+/// Add the controller mappings, each source overriding the ones before it: the
+/// database of the pak, the one in the config directory, the mappings of the
+/// config and `SDL_GAMECONTROLLERCONFIG`. SDL reads the variable itself at
+/// init, it's applied again so it wins over the files.
+fn load_controller_mappings(subsystem: &GameControllerSubsystem, config: &Config) {
+    let mut files = paths::exe_dir()
+        .ok()
+        .into_iter()
+        .chain([paths::config_dir()])
+        .map(|dir| dir.join(CONTROLLER_DB_FILE))
+        .filter(|path| path.exists())
+        .collect::<Vec<_>>();
+    // both are the directory of the binary without XDG variables
+    files.dedup();
+    for path in files {
+        match subsystem.load_mappings(&path) {
+            Ok(count) => info!(?path, count, "load controller mappings"),
+            Err(err) => warn!(?path, %err, "load controller mappings failed"),
+        }
+    }
+
+    let from_env = env::var("SDL_GAMECONTROLLERCONFIG").unwrap_or_default();
+    let mappings = config
+        .controller_mappings
+        .iter()
+        .map(String::as_str)
+        .chain(from_env.lines());
+    for mapping in mappings {
+        let mapping = mapping.trim();
+        if mapping.is_empty() || mapping.starts_with('#') {
+            continue;
+        }
+        if let Err(err) = subsystem.add_mapping(mapping) {
+            warn!(mapping, %err, "add controller mapping failed");
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::io;
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+/// SIGTERM from the launcher and SIGINT from a terminal, both quit the app.
+pub struct QuitSignals {
+    terminate: Signal,
+    interrupt: Signal,
+}
+
+impl QuitSignals {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            terminate: signal(SignalKind::terminate())?,
+            interrupt: signal(SignalKind::interrupt())?,
+        })
+    }
+
+    /// Wait for the next signal and return its name.
+    pub async fn recv(&mut self) -> &'static str {
+        tokio::select! {
+            _ = self.terminate.recv() => "SIGTERM",
+            _ = self.interrupt.recv() => "SIGINT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SignalAction {
+    Rescan,
+    /// Reconnect the device connected last, or else the preferred one.
+    Reconnect,
+}
+
+/// SIGUSR1 and SIGUSR2, for launcher scripts poking a running instance
+/// without any IPC library.
+pub struct ActionSignals {
+    rescan: Signal,
+    reconnect: Signal,
+}
+
+impl ActionSignals {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            rescan: signal(SignalKind::user_defined1())?,
+            reconnect: signal(SignalKind::user_defined2())?,
+        })
+    }
+
+    pub async fn recv(&mut self) -> SignalAction {
+        tokio::select! {
+            _ = self.rescan.recv() => SignalAction::Rescan,
+            _ = self.reconnect.recv() => SignalAction::Reconnect,
+        }
+    }
+}
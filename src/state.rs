@@ -0,0 +1,76 @@
+use crate::bluetooth::{
+    BluetoothConnectState, BluetoothScanStatus, BluezVersion, DeviceDetails, DeviceInfo,
+    LinkQuality, LinkStats, NowPlaying, TetherState,
+};
+use bluer::Address;
+
+/// Everything a frontend needs to render, published by the `BluetoothManager`
+/// as a whole so a frame never sees half of an update.
+#[derive(Debug, Clone)]
+pub struct AppState {
+    /// Whether there is a Bluetooth adapter, `false` while a USB dongle is
+    /// unplugged.
+    pub adapter_present: bool,
+    pub powered: bool,
+    pub scan_status: BluetoothScanStatus,
+    pub devices: Vec<DeviceInfo>,
+    pub connect_state: BluetoothConnectState,
+    /// The device connected successfully last during this run.
+    pub last_connected: Option<Address>,
+    /// The PIN a legacy device accepted last, for the frontend to remember.
+    pub paired_pin: Option<(Address, String)>,
+    /// The connect waits for BlueZ to finish another operation, like a
+    /// connect made by another program.
+    pub bluez_busy: bool,
+    /// The version of bluetoothd, when the adapter tells it.
+    pub bluez_version: Option<BluezVersion>,
+    /// The track of the player of the connected device, when it has one.
+    pub now_playing: Option<NowPlaying>,
+    /// The connected device dropped its audio but stays connected, like a
+    /// multipoint headset which switched to a phone.
+    pub audio_switched_away: bool,
+    /// The link of the connected device read last, oldest first.
+    pub link_history: Vec<LinkStats>,
+    /// The internet shared by a phone.
+    pub tether: TetherState,
+    /// What the device read last tells about itself, for its settings.
+    pub details: Option<DeviceDetails>,
+}
+
+impl Default for AppState {
+    fn default() -> Self {
+        Self {
+            adapter_present: true,
+            powered: false,
+            scan_status: BluetoothScanStatus::Disable,
+            devices: Vec::new(),
+            connect_state: BluetoothConnectState::Idle,
+            last_connected: None,
+            paired_pin: None,
+            bluez_busy: false,
+            bluez_version: None,
+            now_playing: None,
+            audio_switched_away: false,
+            link_history: Vec::new(),
+            tether: TetherState::Off,
+            details: None,
+        }
+    }
+}
+
+impl AppState {
+    pub fn connected_device(&self) -> Option<&DeviceInfo> {
+        self.devices.iter().find(|info| info.connected)
+    }
+
+    /// How well the audio of the connected device gets through, from the last
+    /// read of its connection, or the signal of the last scan when the
+    /// controller doesn't tell it.
+    pub fn link_quality(&self) -> Option<LinkQuality> {
+        let connected = self.connected_device()?;
+        self.link_history
+            .last()
+            .and_then(LinkStats::link_quality)
+            .or_else(|| connected.link_quality())
+    }
+}
@@ -0,0 +1,55 @@
+use crate::{cli::StatusOutput, files};
+use bluetooth_audio_connector_tg5040::AppState;
+use std::{fs, path::PathBuf};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, warn};
+
+/// Where the status is kept unless `status_file` of the config says otherwise.
+pub const STATUS_PATH: &str = "/tmp/bt-audio-connector.status";
+
+/// Keeps a file with the power state and the connected device, the JSON of
+/// `--status --json`, for launcher overlays and other paks which show the
+/// Bluetooth status without asking BlueZ. It's rewritten when the status
+/// changes, like the battery of the device, and removed when dropped.
+pub struct StatusFile {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl StatusFile {
+    pub fn spawn(path: impl Into<PathBuf>, state_rx: watch::Receiver<AppState>) -> Self {
+        let path = path.into();
+        let task = tokio::spawn(follow(path.clone(), state_rx));
+        Self { path, task }
+    }
+}
+
+impl Drop for StatusFile {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+async fn follow(path: PathBuf, mut state_rx: watch::Receiver<AppState>) {
+    let mut written = None;
+    loop {
+        let status = serde_json::to_string(&StatusOutput::from(&*state_rx.borrow_and_update()));
+        match status {
+            // the state changes with every device found, the status rarely
+            Ok(status) if written.as_ref() != Some(&status) => {
+                debug!(status, "write status file");
+                if let Err(err) = files::write_atomic(&path, &status) {
+                    warn!(?err, ?path, "write status file failed");
+                }
+                written = Some(status);
+            }
+            Ok(_) => {}
+            Err(err) => warn!(?err, "serialize status failed"),
+        }
+
+        if state_rx.changed().await.is_err() {
+            break;
+        }
+    }
+}
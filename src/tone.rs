@@ -0,0 +1,302 @@
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    AudioSubsystem, Sdl,
+};
+use std::f32::consts::TAU;
+use tracing::{info, warn};
+
+// what the audio servers and most headsets run at
+const SAMPLE_RATE: i32 = 48_000;
+
+// loud enough to hear on any volume, far from clipping
+const AMPLITUDE: f32 = 0.25;
+
+// the sine sweep goes from the lowest to the highest frequency one can hear,
+// in this many seconds, then starts over
+const SWEEP_START: f32 = 20.;
+const SWEEP_END: f32 = 20_000.;
+const SWEEP_TIME: f32 = 10.;
+
+// brings the sum of the pink noise filters back to about -1 to 1
+const PINK_NOISE_GAIN: f32 = 0.11;
+
+// the channel test plays a tone this long in one channel, then is silent a
+// while before the other, so the switch is heard
+const CHANNEL_TONE: f32 = 440.;
+const CHANNEL_TONE_TIME: f32 = 1.;
+const CHANNEL_TIME: f32 = 1.5;
+
+/// A sound of the audio test.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TestTone {
+    /// A sine rising exponentially over the audible range, a part of it
+    /// missing shows where the device cuts off.
+    Sweep,
+    /// Equal energy in each octave, sounds even on a working device.
+    PinkNoise,
+    /// A tone only in the left channel, then only in the right, in turn. Heard
+    /// in both or the wrong one, the device plays mono or swaps them.
+    Channels,
+}
+
+impl TestTone {
+    pub const ALL: [TestTone; 3] = [TestTone::Sweep, TestTone::PinkNoise, TestTone::Channels];
+}
+
+/// A side of a stereo output.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Channel {
+    Left,
+    Right,
+}
+
+/// Plays the test tone of the screen through the default output of SDL, which
+/// the audio server sends to the connected device.
+pub struct TonePlayer {
+    /// `None` when SDL has no audio driver, nothing is played then.
+    audio: Option<AudioSubsystem>,
+    playing: Option<(TestTone, AudioDevice<ToneGenerator>)>,
+}
+
+impl TonePlayer {
+    pub fn new(sdl_context: &Sdl) -> Self {
+        let audio = match sdl_context.audio() {
+            Ok(audio) => Some(audio),
+            Err(err) => {
+                warn!(%err, "open sdl audio failed, the test tones are silent");
+                None
+            }
+        };
+        Self {
+            audio,
+            playing: None,
+        }
+    }
+
+    /// Play the tone until another one is passed, or stop with `None`. Called
+    /// each frame, a tone already playing goes on.
+    pub fn play(&mut self, tone: Option<TestTone>) {
+        if self.playing.as_ref().map(|(playing, _)| *playing) == tone {
+            return;
+        }
+        // dropping the device closes it
+        if let Some((playing, _)) = self.playing.take() {
+            info!(?playing, "stop test tone");
+        }
+        let (Some(tone), Some(audio)) = (tone, &self.audio) else {
+            return;
+        };
+
+        let desired = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(2),
+            samples: None,
+        };
+        match audio.open_playback(None, &desired, |spec| {
+            ToneGenerator::new(tone, spec.freq as f32, spec.channels)
+        }) {
+            Ok(device) => {
+                info!(?tone, "play test tone");
+                device.resume();
+                self.playing = Some((tone, device));
+            }
+            Err(err) => warn!(%err, ?tone, "open audio device failed"),
+        }
+    }
+
+    /// The channel the channel test plays in now, `None` while another tone
+    /// or nothing plays.
+    pub fn channel(&mut self) -> Option<Channel> {
+        let (tone, device) = self.playing.as_mut()?;
+        (*tone == TestTone::Channels).then(|| device.lock().channel())
+    }
+}
+
+/// Synthesizes the tone in the audio thread of SDL.
+struct ToneGenerator {
+    tone: TestTone,
+    sample_rate: f32,
+    channels: usize,
+    /// Samples of each channel made so far.
+    position: u64,
+    phase: f32,
+    /// The state of the xorshift generator of the white noise.
+    noise: u32,
+    /// The filters of the pink noise, by Paul Kellet.
+    pink: [f32; 7],
+}
+
+impl ToneGenerator {
+    fn new(tone: TestTone, sample_rate: f32, channels: u8) -> Self {
+        Self {
+            tone,
+            sample_rate,
+            channels: usize::from(channels.max(1)),
+            position: 0,
+            phase: 0.,
+            noise: 0x2545_f491,
+            pink: [0.; 7],
+        }
+    }
+
+    fn next_sample(&mut self) -> f32 {
+        let sample = match self.tone {
+            TestTone::Sweep => {
+                let time = self.time_in(SWEEP_TIME);
+                let frequency = SWEEP_START * (SWEEP_END / SWEEP_START).powf(time / SWEEP_TIME);
+                self.sine(frequency)
+            }
+            TestTone::PinkNoise => self.pink_noise(),
+            TestTone::Channels if self.time_in(CHANNEL_TIME) < CHANNEL_TONE_TIME => {
+                self.sine(CHANNEL_TONE)
+            }
+            TestTone::Channels => 0.,
+        };
+        self.position += 1;
+        sample * AMPLITUDE
+    }
+
+    /// Seconds since the last start of a period, counted in samples as a float
+    /// would lose the fractions after a few minutes.
+    fn time_in(&self, period: f32) -> f32 {
+        (self.position % self.samples(period)) as f32 / self.sample_rate
+    }
+
+    fn samples(&self, seconds: f32) -> u64 {
+        (seconds * self.sample_rate) as u64
+    }
+
+    /// The channel of the channel test, from the samples made so far.
+    fn channel(&self) -> Channel {
+        if self.position / self.samples(CHANNEL_TIME) % 2 == 0 {
+            Channel::Left
+        } else {
+            Channel::Right
+        }
+    }
+
+    fn sine(&mut self, frequency: f32) -> f32 {
+        self.phase = (self.phase + TAU * frequency / self.sample_rate) % TAU;
+        self.phase.sin()
+    }
+
+    fn white_noise(&mut self) -> f32 {
+        self.noise ^= self.noise << 13;
+        self.noise ^= self.noise >> 17;
+        self.noise ^= self.noise << 5;
+        self.noise as f32 / u32::MAX as f32 * 2. - 1.
+    }
+
+    fn pink_noise(&mut self) -> f32 {
+        let white = self.white_noise();
+        let pink = &mut self.pink;
+        pink[0] = 0.99886 * pink[0] + white * 0.0555179;
+        pink[1] = 0.99332 * pink[1] + white * 0.0750759;
+        pink[2] = 0.96900 * pink[2] + white * 0.1538520;
+        pink[3] = 0.86650 * pink[3] + white * 0.3104856;
+        pink[4] = 0.55000 * pink[4] + white * 0.5329522;
+        pink[5] = -0.7616 * pink[5] - white * 0.0168980;
+        let sum = pink.iter().sum::<f32>() + white * 0.5362;
+        pink[6] = white * 0.115926;
+        sum * PINK_NOISE_GAIN
+    }
+}
+
+impl AudioCallback for ToneGenerator {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for frame in out.chunks_mut(self.channels) {
+            // the channel of the sample, before it moves on
+            let only = (self.tone == TestTone::Channels).then(|| self.channel());
+            let sample = self.next_sample();
+            frame.fill(0.);
+            match only {
+                Some(Channel::Left) => frame[0] = sample,
+                Some(Channel::Right) if frame.len() > 1 => frame[1] = sample,
+                Some(Channel::Right) => {}
+                None => frame.fill(sample),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: f32 = SAMPLE_RATE as f32;
+
+    fn samples(tone: TestTone, seconds: f32) -> Vec<f32> {
+        let mut generator = ToneGenerator::new(tone, RATE, 1);
+        (0..(seconds * RATE) as usize)
+            .map(|_| generator.next_sample())
+            .collect()
+    }
+
+    /// The frequency of a sine, from how often it crosses zero upwards.
+    fn frequency(samples: &[f32]) -> f32 {
+        let rising = samples
+            .windows(2)
+            .filter(|pair| pair[0] < 0. && pair[1] >= 0.)
+            .count();
+        rising as f32 * RATE / samples.len() as f32
+    }
+
+    #[test]
+    fn samples_stay_within_the_amplitude() {
+        for tone in TestTone::ALL {
+            let samples = samples(tone, SWEEP_TIME);
+            let peak = samples
+                .iter()
+                .fold(0f32, |peak, sample| peak.max(sample.abs()));
+            assert!(peak <= AMPLITUDE, "{tone:?} peaks at {peak}");
+            assert!(peak > AMPLITUDE / 2., "{tone:?} peaks at {peak}");
+        }
+    }
+
+    #[test]
+    fn sweep_rises_and_starts_over() {
+        let samples = samples(TestTone::Sweep, SWEEP_TIME + 1.);
+        let second = |start: f32| {
+            frequency(&samples[(start * RATE) as usize..((start + 1.) * RATE) as usize])
+        };
+
+        let start = second(0.);
+        assert!(start > SWEEP_START && start < 2. * SWEEP_START, "{start}");
+        // 20 Hz times 1000 to the power of 0.5 to 0.6
+        let middle = second(SWEEP_TIME / 2.);
+        assert!(middle > 800. && middle < 1000., "{middle}");
+        let again = second(SWEEP_TIME);
+        assert!((again - start).abs() < 2., "{again}");
+    }
+
+    #[test]
+    fn channel_tone_and_pause() {
+        let samples = samples(TestTone::Channels, CHANNEL_TIME * 2.);
+        let tone_end = (CHANNEL_TONE_TIME * RATE) as usize;
+        let period = (CHANNEL_TIME * RATE) as usize;
+
+        let tone = frequency(&samples[..tone_end]);
+        assert!((tone - CHANNEL_TONE).abs() < 2., "{tone}");
+        assert!(samples[tone_end..period].iter().all(|sample| *sample == 0.));
+        assert!(samples[period..period + tone_end]
+            .iter()
+            .any(|sample| *sample != 0.));
+    }
+
+    #[test]
+    fn channel_test_alternates_sides() {
+        let mut generator = ToneGenerator::new(TestTone::Channels, RATE, 2);
+        let mut out = vec![0.; 2 * (CHANNEL_TIME * RATE) as usize];
+
+        generator.callback(&mut out);
+        assert!(out.chunks(2).all(|frame| frame[1] == 0.));
+        assert!(out.chunks(2).any(|frame| frame[0] != 0.));
+
+        assert_eq!(generator.channel(), Channel::Right);
+        generator.callback(&mut out);
+        assert!(out.chunks(2).all(|frame| frame[0] == 0.));
+        assert!(out.chunks(2).any(|frame| frame[1] != 0.));
+    }
+}
@@ -0,0 +1,629 @@
+use crate::{
+    config::Config,
+    input::{Action, Direction},
+    tone::{TestTone, TonePlayer},
+    ui::{
+        errors,
+        screens::{
+            now_playing_text, AboutScreen, AudioTestScreen, AutoConnectScreen,
+            DeviceSettingsScreen, HiddenDevicesScreen, LogsScreen, MainScreen, NetworkScreen,
+            PinEntryScreen, PreflightScreen, ProfilesScreen, RemapScreen, Screen, SplashScreen,
+        },
+        texts::{self, Texts},
+    },
+};
+use bluetooth_audio_connector_tg5040::{BluetoothConnectState, BluetoothScanStatus, LinkQuality};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{
+    prelude::*,
+    widgets::{List, ListItem, ListState, Paragraph, Wrap},
+};
+use std::{
+    io::{self, Stdout},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use tokio::sync::{mpsc, watch};
+
+// how long to wait for a key before drawing again
+const POLL_TIME: Duration = Duration::from_millis(100);
+
+// the bars of the link history, from the lowest
+const SPARKLINE_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draw the screens in the terminal instead of SDL, for SSH sessions and
+/// devices without a working video driver. Same contract as `sdl::spawn`.
+pub fn spawn(
+    config_rx: watch::Receiver<Config>, action_tx: mpsc::UnboundedSender<Action>,
+    screen_rx: watch::Receiver<Screen>,
+) -> anyhow::Result<JoinHandle<anyhow::Result<()>>> {
+    let handle = thread::Builder::new()
+        .name("tui".to_string())
+        .spawn(move || run(config_rx, &action_tx, screen_rx))?;
+    Ok(handle)
+}
+
+fn run(
+    config_rx: watch::Receiver<Config>, action_tx: &mpsc::UnboundedSender<Action>,
+    screen_rx: watch::Receiver<Screen>,
+) -> anyhow::Result<()> {
+    terminal::enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+
+    let result = Terminal::new(CrosstermBackend::new(io::stdout()))
+        .map_err(anyhow::Error::from)
+        .and_then(|mut terminal| draw_loop(&mut terminal, config_rx, action_tx, screen_rx));
+
+    // give the terminal back even when drawing failed
+    let _ = execute!(io::stdout(), LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+
+    result
+}
+
+fn draw_loop(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>, mut config_rx: watch::Receiver<Config>,
+    action_tx: &mpsc::UnboundedSender<Action>, mut screen_rx: watch::Receiver<Screen>,
+) -> anyhow::Result<()> {
+    let mut texts = texts::texts(config_rx.borrow_and_update().language);
+    let mut list_state = ListState::default();
+
+    // only the audio of SDL, for the test tones
+    sdl2::hint::set("SDL_NO_SIGNAL_HANDLERS", "1");
+    let sdl_context = sdl2::init().map_err(anyhow::Error::msg)?;
+    let mut tone_player = TonePlayer::new(&sdl_context);
+
+    // the app drops the sender once it has quit
+    while screen_rx.has_changed().is_ok() {
+        if event::poll(POLL_TIME)? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    if let Some(action) = action_from_key(key.code) {
+                        let _ = action_tx.send(action);
+                    }
+                }
+            }
+        }
+
+        if config_rx.has_changed().unwrap_or(false) {
+            texts = texts::texts(config_rx.borrow_and_update().language);
+        }
+
+        let mut screen = screen_rx.borrow_and_update().clone();
+        tone_player.play(screen.test_tone());
+        if let Screen::AudioTest(audio_test_screen) = &mut screen {
+            audio_test_screen.channel = tone_player.channel();
+        }
+        terminal.draw(|frame| match &screen {
+            Screen::Main(main_screen) => draw_main(frame, texts, main_screen, &mut list_state),
+            Screen::AutoConnect(auto_connect_screen) => {
+                draw_auto_connect(frame, texts, auto_connect_screen)
+            }
+            Screen::DeviceSettings(settings_screen) => {
+                draw_device_settings(frame, texts, settings_screen)
+            }
+            Screen::HiddenDevices(hidden_screen) => {
+                draw_hidden_devices(frame, texts, hidden_screen, &mut list_state)
+            }
+            Screen::Remap(remap_screen) => draw_remap(frame, texts, remap_screen),
+            Screen::Logs(logs_screen) => draw_logs(frame, texts, logs_screen),
+            Screen::About(about_screen) => draw_about(frame, texts, about_screen),
+            Screen::PinEntry(pin_screen) => draw_pin_entry(frame, texts, pin_screen),
+            Screen::Preflight(preflight_screen) => draw_preflight(frame, texts, preflight_screen),
+            Screen::Splash(splash_screen) => draw_splash(frame, texts, splash_screen),
+            Screen::Profiles(profiles_screen) => {
+                draw_profiles(frame, texts, profiles_screen, &mut list_state)
+            }
+            Screen::Network(network_screen) => {
+                draw_network(frame, texts, network_screen, &mut list_state)
+            }
+            Screen::AudioTest(audio_test_screen) => {
+                draw_audio_test(frame, texts, audio_test_screen, &mut list_state)
+            }
+            Screen::Quitting => {
+                frame.render_widget(Paragraph::new(texts.quitting).red(), frame.size())
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+/// The same letters as the keyboard of the SDL frontend.
+fn action_from_key(code: KeyCode) -> Option<Action> {
+    let action = match code {
+        KeyCode::Esc | KeyCode::Char('b') | KeyCode::Backspace => Action::Quit,
+        KeyCode::Char('y') => Action::PowerOn,
+        KeyCode::Char('x') => Action::PowerOff,
+        KeyCode::Up => Action::Move(Direction::Up),
+        KeyCode::Right => Action::Move(Direction::Right),
+        KeyCode::Down => Action::Move(Direction::Down),
+        KeyCode::Left => Action::Move(Direction::Left),
+        KeyCode::Char('l') | KeyCode::F(5) => Action::Rescan,
+        KeyCode::Char('a') | KeyCode::Enter => Action::Connect,
+        KeyCode::Char('d') => Action::Disconnect,
+        KeyCode::Char('f') => Action::Forget,
+        KeyCode::Char('r') => Action::ReconnectLast,
+        KeyCode::Char('s') => Action::ToggleFavorite,
+        KeyCode::Char('g') => Action::ShowLogs,
+        KeyCode::Char('p') => Action::Profiles,
+        KeyCode::Char('n') => Action::Network,
+        KeyCode::Char('k') => Action::Remap,
+        KeyCode::Char('i') => Action::About,
+        KeyCode::Char('+' | '=') => Action::VolumeUp,
+        KeyCode::Char('-') => Action::VolumeDown,
+        KeyCode::Char('t') => Action::ToggleTheme,
+        KeyCode::Char('e') => Action::ToggleLanguage,
+        KeyCode::Char('o') => Action::CycleSort,
+        KeyCode::Char(digit @ '1'..='9') => Action::ConnectFavorite(digit as usize - '1' as usize),
+        _ => return None,
+    };
+    Some(action)
+}
+
+fn draw_auto_connect(frame: &mut Frame, texts: &Texts, screen: &AutoConnectScreen) {
+    let status = match &screen.connect_state {
+        BluetoothConnectState::Idle => Line::from(texts.auto_connect_waiting.cyan()),
+        state => connect_status(texts, state),
+    };
+    let lines = vec![
+        Line::from(format!("{}{}", texts.auto_connect_title, screen.name)),
+        status,
+        Line::from(texts.auto_connect_hint.dark_gray()),
+    ];
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.size(),
+    );
+}
+
+fn draw_splash(frame: &mut Frame, texts: &Texts, screen: &SplashScreen) {
+    let (status, failed) = screen.status(texts);
+    let status = if failed { status.red() } else { status.cyan() };
+    let lines = vec![
+        Line::from(screen.name.as_deref().unwrap_or_default()),
+        Line::from(status),
+    ];
+    let area = frame.size();
+    let top = area.height.saturating_sub(lines.len() as u16) / 2;
+    frame.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: false }),
+        Rect {
+            y: area.y + top,
+            height: area.height - top,
+            ..area
+        },
+    );
+}
+
+fn draw_device_settings(frame: &mut Frame, texts: &Texts, screen: &DeviceSettingsScreen) {
+    let mut lines = vec![Line::from(screen.title(texts))];
+    for line in screen.details(texts) {
+        lines.push(Line::from(line.dark_gray()));
+    }
+    for line in screen.link_stats(texts) {
+        lines.push(Line::from(line.cyan()));
+    }
+    let signals = screen.link_signals();
+    if !signals.is_empty() {
+        lines.push(Line::from(
+            signals
+                .into_iter()
+                .map(|(level, quality)| {
+                    let bar = SPARKLINE_BARS[usize::from(level) * SPARKLINE_BARS.len() / 256];
+                    if quality == LinkQuality::Weak {
+                        bar.to_string().red()
+                    } else {
+                        bar.to_string().cyan()
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ));
+    }
+    for (index, (label, value)) in screen.rows(texts).into_iter().enumerate() {
+        let style = if index == screen.selected_row {
+            Style::new().reversed()
+        } else {
+            Style::new()
+        };
+        lines.push(Line::from(Span::styled(
+            format!("{}{}", label, value),
+            style,
+        )));
+    }
+    lines.push(Line::from(texts.settings_hint.dark_gray()));
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.size(),
+    );
+}
+
+/// Only the SDL frontend reads raw presses, so here the wizard can only be
+/// cancelled with Esc.
+fn draw_remap(frame: &mut Frame, texts: &Texts, screen: &RemapScreen) {
+    let mut lines = vec![
+        Line::from((texts.remap_title)(
+            screen.step + 1,
+            texts.remap_actions.len(),
+        )),
+        Line::from(texts.remap_actions[screen.step].cyan()),
+    ];
+    if screen.rejected {
+        lines.push(Line::from(texts.remap_taken.red()));
+    }
+    lines.push(Line::from(texts.remap_hint.dark_gray()));
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.size(),
+    );
+}
+
+fn draw_preflight(frame: &mut Frame, texts: &Texts, screen: &PreflightScreen) {
+    let mut lines = vec![Line::from(texts.preflight_title)];
+    for problem in &screen.problems {
+        lines.push(Line::from(
+            texts.preflight_problems[*problem as usize].red(),
+        ));
+        lines.push(Line::from(
+            texts.preflight_fixes[*problem as usize].dark_gray(),
+        ));
+    }
+    lines.push(Line::from(texts.preflight_hint.cyan()));
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.size(),
+    );
+}
+
+fn draw_logs(frame: &mut Frame, texts: &Texts, screen: &LogsScreen) {
+    let [title_area, logs_area] = *Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(frame.size())
+    else {
+        return;
+    };
+
+    frame.render_widget(Paragraph::new(texts.logs_title), title_area);
+    let lines = screen
+        .visible(logs_area.height as usize)
+        .iter()
+        .map(|line| Line::from(line.as_str().dark_gray()))
+        .collect::<Vec<_>>();
+    frame.render_widget(Paragraph::new(lines), logs_area);
+}
+
+fn draw_about(frame: &mut Frame, texts: &Texts, screen: &AboutScreen) {
+    let mut lines = vec![Line::from(texts.about_title)];
+    for (label, value) in screen.rows(texts) {
+        lines.push(Line::from(format!("{}{}", label, value).cyan()));
+    }
+    if let Some(warning) = screen.warning(texts) {
+        lines.push(Line::from(warning.red()));
+    }
+    lines.push(Line::from(texts.about_keys));
+    lines.push(Line::from(texts.about_hint.dark_gray()));
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.size(),
+    );
+}
+
+fn draw_pin_entry(frame: &mut Frame, texts: &Texts, screen: &PinEntryScreen) {
+    let keys = (0..PinEntryScreen::KEY_COUNT)
+        .map(|index| {
+            let label = format!(" {} ", PinEntryScreen::key_label(index, texts));
+            if index == screen.cursor {
+                Span::styled(label, Style::new().reversed())
+            } else {
+                Span::raw(label)
+            }
+        })
+        .collect::<Vec<_>>();
+    let lines = vec![
+        Line::from(format!("{}{}", texts.pin_title, screen.name)),
+        Line::from(screen.digits.as_str().cyan()),
+        Line::from(keys),
+        Line::from(texts.pin_hint.dark_gray()),
+    ];
+    frame.render_widget(
+        Paragraph::new(lines).wrap(Wrap { trim: false }),
+        frame.size(),
+    );
+}
+
+fn draw_profiles(
+    frame: &mut Frame, texts: &Texts, screen: &ProfilesScreen, list_state: &mut ListState,
+) {
+    let [title_area, list_area] = *Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(frame.size())
+    else {
+        return;
+    };
+
+    frame.render_widget(Paragraph::new(texts.profiles_title), title_area);
+    if screen.profiles.is_empty() {
+        frame.render_widget(Paragraph::new(texts.profiles_empty.dark_gray()), list_area);
+        return;
+    }
+
+    let items = screen
+        .profiles
+        .iter()
+        .map(|label| ListItem::new(label.as_str()))
+        .collect::<Vec<_>>();
+    list_state.select(Some(screen.selected_index));
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::new().reversed()),
+        list_area,
+        list_state,
+    );
+}
+
+fn draw_network(
+    frame: &mut Frame, texts: &Texts, screen: &NetworkScreen, list_state: &mut ListState,
+) {
+    let status = screen.status(texts);
+    let [title_area, status_area, list_area] = *Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(u16::from(status.is_some())),
+            Constraint::Min(1),
+        ])
+        .split(frame.size())
+    else {
+        return;
+    };
+
+    frame.render_widget(Paragraph::new(texts.network_title), title_area);
+    if let Some(status) = status {
+        frame.render_widget(Paragraph::new(status.cyan()), status_area);
+    }
+    if screen.phones.is_empty() {
+        frame.render_widget(Paragraph::new(texts.network_empty.dark_gray()), list_area);
+        return;
+    }
+
+    let items = screen
+        .rows(texts)
+        .into_iter()
+        .map(ListItem::new)
+        .collect::<Vec<_>>();
+    list_state.select(Some(screen.selected_index));
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::new().reversed()),
+        list_area,
+        list_state,
+    );
+}
+
+fn draw_audio_test(
+    frame: &mut Frame, texts: &Texts, screen: &AudioTestScreen, list_state: &mut ListState,
+) {
+    let channel_test = screen.playing == Some(TestTone::Channels);
+    let [title_area, warning_area, list_area, channels_area, hint_area] = *Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(u16::from(!screen.connected)),
+            Constraint::Min(1),
+            Constraint::Length(2 * u16::from(channel_test)),
+            Constraint::Length(1),
+        ])
+        .split(frame.size())
+    else {
+        return;
+    };
+
+    frame.render_widget(
+        Paragraph::new(format!("{}{}", texts.audio_test_title, screen.name)),
+        title_area,
+    );
+    if !screen.connected {
+        frame.render_widget(
+            Paragraph::new(texts.audio_test_not_connected.red()),
+            warning_area,
+        );
+    }
+    let items = screen
+        .rows(texts)
+        .into_iter()
+        .map(ListItem::new)
+        .collect::<Vec<_>>();
+    list_state.select(Some(screen.selected_index));
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::new().reversed()),
+        list_area,
+        list_state,
+    );
+    if channel_test {
+        let channels = texts
+            .channel_names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let name = format!(" {} ", name);
+                if screen
+                    .channel
+                    .is_some_and(|channel| channel as usize == index)
+                {
+                    name.reversed()
+                } else {
+                    name.dark_gray()
+                }
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(channels),
+                Line::from(texts.channel_test_hint.cyan()),
+            ]),
+            channels_area,
+        );
+    }
+    frame.render_widget(Paragraph::new(texts.audio_test_hint.dark_gray()), hint_area);
+}
+
+fn draw_hidden_devices(
+    frame: &mut Frame, texts: &Texts, screen: &HiddenDevicesScreen, list_state: &mut ListState,
+) {
+    let [title_area, list_area] = *Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(1)])
+        .split(frame.size())
+    else {
+        return;
+    };
+
+    frame.render_widget(Paragraph::new(texts.hidden_title), title_area);
+
+    let mut items = screen
+        .rows(texts)
+        .into_iter()
+        .map(ListItem::new)
+        .collect::<Vec<_>>();
+    if screen.devices.is_empty() {
+        items.push(ListItem::new(texts.hidden_empty.dark_gray()));
+    }
+    list_state.select(Some(screen.selected_index));
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::new().reversed()),
+        list_area,
+        list_state,
+    );
+}
+
+fn draw_main(frame: &mut Frame, texts: &Texts, screen: &MainScreen, list_state: &mut ListState) {
+    let state = &screen.state;
+
+    let mut header = Vec::new();
+    if let Some(notice) = &screen.notice {
+        header.push(Line::from(notice.as_str().red()));
+    }
+    header.push(Line::from(texts.quit_hint));
+    if !state.adapter_present {
+        header.push(Line::from(texts.adapter_removed.red()));
+        header.push(Line::from(texts.adapter_removed_hint.dark_gray()));
+        frame.render_widget(
+            Paragraph::new(header).wrap(Wrap { trim: false }),
+            frame.size(),
+        );
+        return;
+    }
+    let mut power = vec![
+        Span::raw(texts.power_hint),
+        if state.powered {
+            texts.power_on.green()
+        } else {
+            texts.power_off.red()
+        },
+    ];
+    if let Some(battery) = screen.handheld_battery {
+        let text = format!(
+            "  {}",
+            (texts.handheld_battery)(battery.level, battery.charging)
+        );
+        power.push(if battery.is_low() {
+            text.red()
+        } else {
+            text.dark_gray()
+        });
+    }
+    header.push(Line::from(power));
+    if let Some(name) = &screen.reconnect_last {
+        header.push(Line::from(format!("{}{}", texts.reconnect_last_hint, name)));
+    }
+    header.push(match state.scan_status {
+        BluetoothScanStatus::Disable => Line::default(),
+        BluetoothScanStatus::Scanning => Line::from(screen.scanning_text(texts).cyan()),
+        BluetoothScanStatus::Finished => {
+            let connected = match state.connected_device() {
+                Some(info) => format!("{}{}", texts.connected_to, info.name),
+                None => texts.not_connected.to_string(),
+            };
+            Line::from(vec![texts.scan_finished.green(), connected.dark_gray()])
+        }
+        BluetoothScanStatus::Failed => {
+            Line::from(vec![texts.scan_failed.red(), texts.rescan_hint.dark_gray()])
+        }
+    });
+    if let Some(now_playing) = &state.now_playing {
+        header.push(Line::from(now_playing_text(now_playing, texts).cyan()));
+    }
+    if state.scan_status == BluetoothScanStatus::Finished {
+        header.push(Line::from((texts.list_hint)(screen.found_count())));
+        header.push(Line::from(texts.favorite_hint.dark_gray()));
+    }
+
+    let [header_area, list_area, status_area] = *Layout::default()
+        .direction(ratatui::layout::Direction::Vertical)
+        .constraints([
+            Constraint::Length(header.len() as u16),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .split(frame.size())
+    else {
+        return;
+    };
+
+    frame.render_widget(
+        Paragraph::new(header).wrap(Wrap { trim: false }),
+        header_area,
+    );
+
+    if state.scan_status != BluetoothScanStatus::Finished {
+        return;
+    }
+
+    let items = screen
+        .list_rows(texts)
+        .into_iter()
+        .map(ListItem::new)
+        .collect::<Vec<_>>();
+    list_state.select((!items.is_empty()).then_some(screen.selected_device_index));
+    frame.render_stateful_widget(
+        List::new(items).highlight_style(Style::new().reversed()),
+        list_area,
+        list_state,
+    );
+
+    let mut status = connect_status(texts, &state.connect_state);
+    if state.connect_state == BluetoothConnectState::Connected {
+        if let Some(quality) = state.link_quality() {
+            let label = texts.link_quality[quality as usize];
+            status.spans.push(match quality {
+                LinkQuality::Weak => label.red(),
+                _ => label.dark_gray(),
+            });
+        }
+    }
+    frame.render_widget(Paragraph::new(status), status_area);
+}
+
+fn connect_status<'a>(texts: &'a Texts, connect_state: &BluetoothConnectState) -> Line<'a> {
+    match connect_state {
+        BluetoothConnectState::Idle => Line::default(),
+        BluetoothConnectState::InProgress(stage) => {
+            Line::from(texts.connect_in_progress[*stage as usize].cyan())
+        }
+        BluetoothConnectState::Connected => Line::from(texts.connect_succeeded.green()),
+        BluetoothConnectState::Failed { stage, error } => Line::from(
+            format!(
+                "{}{}",
+                texts.connect_failed[*stage as usize],
+                errors::describe(error, texts)
+            )
+            .red(),
+        ),
+    }
+}
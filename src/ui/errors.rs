@@ -0,0 +1,36 @@
+use crate::ui::texts::Texts;
+use bluetooth_audio_connector_tg5040::Error;
+
+/// What the user can do about an error, in their language.
+pub fn describe(err: &Error, texts: &Texts) -> String {
+    let errors = &texts.errors;
+    match err {
+        Error::AdapterMissing => errors.adapter_missing.to_string(),
+        Error::BluezDown(_) => errors.bluez_down.to_string(),
+        Error::PoweredOff => errors.powered_off.to_string(),
+        Error::PairingRejected(_) => errors.pairing_rejected.to_string(),
+        Error::PairingInterrupted(_) => errors.pairing_interrupted.to_string(),
+        Error::WrongPin => errors.wrong_pin.to_string(),
+        Error::PinRequired(_) => errors.pin_required.to_string(),
+        Error::PageTimeout => errors.page_timeout.to_string(),
+        Error::InProgress => errors.in_progress.to_string(),
+        Error::AudioRoutingFailed => errors.audio_routing_failed.to_string(),
+        Error::DeviceNotFound(_) => errors.device_not_found.to_string(),
+        Error::NoNetworkAddress => errors.no_network_address.to_string(),
+        Error::NetworkSetup(_) => errors.network_setup.to_string(),
+        Error::Bluez(message) => explain_bluez(message, texts)
+            .map(str::to_string)
+            .unwrap_or_else(|| message.clone()),
+    }
+}
+
+/// The hint of a known BlueZ error name in the message, which may be wrapped
+/// in other text.
+fn explain_bluez(message: &str, texts: &Texts) -> Option<&'static str> {
+    texts
+        .errors
+        .bluez
+        .iter()
+        .find(|(name, _)| message.contains(name))
+        .map(|(_, hint)| *hint)
+}
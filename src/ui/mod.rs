@@ -0,0 +1,38 @@
+// handle the annoying Rect i32
+macro_rules! rect(
+    ($x:expr, $y:expr, $w:expr, $h:expr) => (
+        Rect::new($x as i32, $y as i32, $w as u32, $h as u32)
+    )
+);
+
+pub mod errors;
+pub mod screens;
+pub mod text_drawer;
+pub mod texts;
+pub mod theme;
+pub mod widgets;
+
+pub const SCREEN_WIDTH: u32 = 1280;
+pub const SCREEN_HEIGHT: u32 = 720;
+
+pub const PADDING: u32 = 32;
+
+pub const FONT_SIZE: u16 = 30;
+pub const LARGE_FONT_SIZE: u16 = 44;
+
+// default alpha of the layer drawn over the background image
+pub const BACKGROUND_FADE: u8 = 180;
+
+// fraction of the remaining distance an animation moves each frame
+const ANIMATION_SPEED: f32 = 0.3;
+
+/// Move `current` a part of the way to `target`, called once per frame this
+/// gives an ease-out animation.
+fn ease_out(current: f32, target: f32) -> f32 {
+    let next = current + (target - current) * ANIMATION_SPEED;
+    if (target - next).abs() < 0.01 {
+        target
+    } else {
+        next
+    }
+}
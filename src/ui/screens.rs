@@ -0,0 +1,1134 @@
+use crate::{
+    power_supply::HandheldBattery,
+    preflight::Component,
+    tone::{Channel, TestTone},
+    ui::{
+        errors,
+        text_drawer::TextDrawer,
+        texts::Texts,
+        widgets::{device_label, DeviceList},
+        PADDING,
+    },
+};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{
+    AppState, BluetoothConnectState, BluetoothScanStatus, BluezVersion, DeviceDetails,
+    DeviceSettings, Error, LinkQuality, LinkStats, NowPlaying, TetherState,
+};
+use sdl2::pixels::Color;
+use std::time::Duration;
+
+/// The RSSI of a connection drawn as the lowest bar, the bar is full inside
+/// the range the controller wants.
+const SPARKLINE_MIN_RSSI: i8 = -30;
+
+// the width of a bar of the link history, a bar for each read
+const SPARKLINE_BAR_WIDTH: u32 = 8;
+
+/// What the SDL thread draws, published by the `App` whenever it changes.
+#[derive(Clone)]
+pub enum Screen {
+    Main(MainScreen),
+    /// Connecting the preferred device at start, until any input.
+    AutoConnect(AutoConnectScreen),
+    DeviceSettings(DeviceSettingsScreen),
+    HiddenDevices(HiddenDevicesScreen),
+    /// Asking for the key or button of each action.
+    Remap(RemapScreen),
+    /// The recent log lines, newest at the bottom.
+    Logs(LogsScreen),
+    /// The versions of the app and the Bluetooth stack.
+    About(AboutScreen),
+    /// The connection profiles of the config.
+    Profiles(ProfilesScreen),
+    /// The paired phones which can share their internet.
+    Network(NetworkScreen),
+    /// The test tones, played while shown.
+    AudioTest(AudioTestScreen),
+    /// Asking for the PIN of a legacy device.
+    PinEntry(PinEntryScreen),
+    /// What keeps Bluetooth from working, before the app starts.
+    Preflight(PreflightScreen),
+    /// The progress and result of a one-shot mode like `--toggle`.
+    Splash(SplashScreen),
+    Quitting,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::Main(MainScreen::default())
+    }
+}
+
+impl Screen {
+    /// The tone the frontend plays, only on the audio test.
+    pub fn test_tone(&self) -> Option<TestTone> {
+        match self {
+            Screen::AudioTest(audio_test_screen) => audio_test_screen.playing,
+            _ => None,
+        }
+    }
+}
+
+pub fn draw(
+    drawer: &mut TextDrawer, device_list: &mut DeviceList, texts: &Texts, screen: &Screen,
+) -> anyhow::Result<()> {
+    match screen {
+        Screen::Main(main_screen) => draw_main(drawer, device_list, texts, main_screen),
+        Screen::AutoConnect(auto_connect_screen) => {
+            draw_auto_connect(drawer, texts, auto_connect_screen)
+        }
+        Screen::DeviceSettings(settings_screen) => {
+            draw_device_settings(drawer, texts, settings_screen)
+        }
+        Screen::HiddenDevices(hidden_screen) => draw_hidden_devices(drawer, texts, hidden_screen),
+        Screen::Remap(remap_screen) => draw_remap(drawer, texts, remap_screen),
+        Screen::Logs(logs_screen) => draw_logs(drawer, texts, logs_screen),
+        Screen::About(about_screen) => draw_about(drawer, texts, about_screen),
+        Screen::Profiles(profiles_screen) => draw_profiles(drawer, texts, profiles_screen),
+        Screen::Network(network_screen) => draw_network(drawer, texts, network_screen),
+        Screen::AudioTest(audio_test_screen) => draw_audio_test(drawer, texts, audio_test_screen),
+        Screen::PinEntry(pin_screen) => draw_pin_entry(drawer, texts, pin_screen),
+        Screen::Preflight(preflight_screen) => draw_preflight(drawer, texts, preflight_screen),
+        Screen::Splash(splash_screen) => draw_splash(drawer, texts, splash_screen),
+        Screen::Quitting => draw_quitting(drawer, texts),
+    }
+}
+
+pub fn draw_quitting(drawer: &mut TextDrawer, texts: &Texts) -> anyhow::Result<()> {
+    drawer.draw(texts.quitting, Color::RGB(255, 0, 0), PADDING, PADDING)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct PreflightScreen {
+    pub problems: Vec<Component>,
+}
+
+pub fn draw_preflight(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &PreflightScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, mut last_height) = drawer.draw_wrapped(texts.preflight_title, palette.text, 0, 0)?;
+    for problem in &screen.problems {
+        last_height = drawer
+            .draw_wrapped(
+                texts.preflight_problems[*problem as usize],
+                Color::RGB(255, 0, 0),
+                0,
+                last_height,
+            )?
+            .1;
+        last_height = drawer
+            .draw_wrapped(
+                texts.preflight_fixes[*problem as usize],
+                palette.hint,
+                0,
+                last_height,
+            )?
+            .1;
+    }
+    drawer.draw_wrapped(texts.preflight_hint, palette.info, 0, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct AutoConnectScreen {
+    pub name: String,
+    pub connect_state: BluetoothConnectState,
+}
+
+pub fn draw_auto_connect(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &AutoConnectScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(
+        &format!("{}{}", texts.auto_connect_title, screen.name),
+        palette.text,
+        0,
+        0,
+    )?;
+
+    let (_, last_height) = match &screen.connect_state {
+        BluetoothConnectState::Idle => {
+            drawer.draw(texts.auto_connect_waiting, palette.info, 0, last_height)?
+        }
+        BluetoothConnectState::InProgress(stage) => drawer.draw(
+            texts.connect_in_progress[*stage as usize],
+            palette.info,
+            0,
+            last_height,
+        )?,
+        BluetoothConnectState::Connected => drawer.draw(
+            texts.connect_succeeded,
+            Color::RGB(0, 255, 0),
+            0,
+            last_height,
+        )?,
+        BluetoothConnectState::Failed { stage, error } => drawer.draw_wrapped(
+            &format!(
+                "{}{}",
+                texts.connect_failed[*stage as usize],
+                errors::describe(error, texts)
+            ),
+            Color::RGB(255, 0, 0),
+            0,
+            last_height,
+        )?,
+    };
+
+    drawer.draw_wrapped(texts.auto_connect_hint, palette.hint, 0, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct SplashScreen {
+    /// Name of the device, `None` without one.
+    pub name: Option<String>,
+    pub progress: SplashProgress,
+}
+
+#[derive(Clone)]
+pub enum SplashProgress {
+    /// Scanning for a device BlueZ doesn't know yet.
+    Searching,
+    Connect(BluetoothConnectState),
+    Disconnecting,
+    Disconnected,
+    DisconnectFailed(Error),
+    /// Nothing was connected before and no device is preferred.
+    NoDevice,
+    /// The connect failed before it started, like Bluetooth not powering on.
+    Failed(Error),
+    /// The connect was given up after too long.
+    TimedOut,
+}
+
+impl SplashScreen {
+    /// The text of the progress, and whether it's a failure.
+    pub fn status(&self, texts: &Texts) -> (String, bool) {
+        match &self.progress {
+            SplashProgress::Searching => (texts.splash_searching.to_string(), false),
+            SplashProgress::Connect(BluetoothConnectState::Idle) => {
+                (texts.auto_connect_waiting.to_string(), false)
+            }
+            SplashProgress::Connect(BluetoothConnectState::InProgress(stage)) => (
+                texts.connect_in_progress[*stage as usize].to_string(),
+                false,
+            ),
+            SplashProgress::Connect(BluetoothConnectState::Connected) => {
+                (texts.connect_succeeded.to_string(), false)
+            }
+            SplashProgress::Connect(BluetoothConnectState::Failed { stage, error }) => (
+                format!(
+                    "{}{}",
+                    texts.connect_failed[*stage as usize],
+                    errors::describe(error, texts)
+                ),
+                true,
+            ),
+            SplashProgress::Disconnecting => (texts.splash_disconnecting.to_string(), false),
+            SplashProgress::Disconnected => (texts.splash_disconnected.to_string(), false),
+            SplashProgress::DisconnectFailed(error) => (
+                format!(
+                    "{}{}",
+                    texts.splash_disconnect_failed,
+                    errors::describe(error, texts)
+                ),
+                true,
+            ),
+            SplashProgress::NoDevice => (texts.splash_no_device.to_string(), true),
+            SplashProgress::Failed(error) => (errors::describe(error, texts), true),
+            SplashProgress::TimedOut => (texts.splash_timed_out.to_string(), true),
+        }
+    }
+}
+
+/// The name and the status in the middle of the screen, nothing else.
+pub fn draw_splash(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &SplashScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let mut last_height = (drawer.height / 2).saturating_sub(PADDING + drawer.line_height());
+    if let Some(name) = &screen.name {
+        (_, last_height) = drawer.draw_wrapped(name, palette.text, 0, last_height)?;
+    }
+    let (status, failed) = screen.status(texts);
+    let color = if failed {
+        Color::RGB(255, 0, 0)
+    } else {
+        palette.info
+    };
+    drawer.draw_wrapped(&status, color, 0, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct LogsScreen {
+    pub lines: Vec<String>,
+    /// Lines hidden below the screen, 0 follows the newest line.
+    pub scroll: usize,
+}
+
+impl LogsScreen {
+    /// The lines shown in `rows` rows, the scroll stops at the oldest line.
+    pub fn visible(&self, rows: usize) -> &[String] {
+        let end = self
+            .lines
+            .len()
+            .saturating_sub(self.scroll)
+            .max(rows.min(self.lines.len()));
+        &self.lines[end.saturating_sub(rows)..end]
+    }
+}
+
+pub fn draw_logs(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &LogsScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(texts.logs_title, palette.text, 0, 0)?;
+
+    let line_height = drawer.line_height();
+    let rows = (drawer.height.saturating_sub(PADDING * 2 + last_height) / line_height) as usize;
+    let mut y = last_height;
+    for line in screen.visible(rows) {
+        // SDL_ttf can't render an empty text
+        if !line.is_empty() {
+            drawer.draw_truncated(line, palette.hint, 0, y, drawer.remaining_width(0))?;
+        }
+        y += line_height;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct AboutScreen {
+    pub app_version: &'static str,
+    pub bluez_version: Option<BluezVersion>,
+    /// The release of the running kernel.
+    pub kernel_version: Option<String>,
+}
+
+impl AboutScreen {
+    /// The label and value of each version, in the order shown.
+    pub fn rows(&self, texts: &Texts) -> [(&'static str, String); 3] {
+        let unknown = || texts.about_unknown.to_string();
+        [
+            (texts.about_app, self.app_version.to_string()),
+            (
+                texts.about_bluez,
+                self.bluez_version
+                    .map_or_else(unknown, |version| version.to_string()),
+            ),
+            (
+                texts.about_kernel,
+                self.kernel_version.clone().unwrap_or_else(unknown),
+            ),
+        ]
+    }
+
+    /// The warning about an outdated BlueZ, if it is.
+    pub fn warning(&self, texts: &Texts) -> Option<String> {
+        let version = self.bluez_version.filter(|version| version.is_outdated())?;
+        Some((texts.bluez_outdated)(
+            &version.to_string(),
+            &BluezVersion::MINIMUM.to_string(),
+        ))
+    }
+}
+
+pub fn draw_about(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &AboutScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, mut last_height) = drawer.draw(texts.about_title, palette.text, 0, 0)?;
+    for (label, value) in screen.rows(texts) {
+        last_height = drawer
+            .draw_wrapped(&format!("{}{}", label, value), palette.info, 0, last_height)?
+            .1;
+    }
+    if let Some(warning) = screen.warning(texts) {
+        last_height = drawer
+            .draw_wrapped(&warning, Color::RGB(255, 0, 0), 0, last_height)?
+            .1;
+    }
+    let (_, last_height) = drawer.draw_wrapped(texts.about_keys, palette.text, 0, last_height)?;
+    drawer.draw_wrapped(texts.about_hint, palette.hint, 0, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct RemapScreen {
+    /// Index into `Keymap::REMAP_ACTIONS` and `Texts::remap_actions`.
+    pub step: usize,
+    /// The last press was taken by an earlier action.
+    pub rejected: bool,
+}
+
+pub fn draw_remap(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &RemapScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(
+        &(texts.remap_title)(screen.step + 1, texts.remap_actions.len()),
+        palette.text,
+        0,
+        0,
+    )?;
+    let (_, last_height) = drawer.draw(
+        texts.remap_actions[screen.step],
+        palette.info,
+        0,
+        last_height,
+    )?;
+    let last_height = if screen.rejected {
+        drawer
+            .draw_wrapped(texts.remap_taken, Color::RGB(255, 0, 0), 0, last_height)?
+            .1
+    } else {
+        last_height
+    };
+    drawer.draw_wrapped(texts.remap_hint, palette.hint, 0, last_height)?;
+    Ok(())
+}
+
+/// Rows of the device settings screen, in the order shown.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SettingsRow {
+    AutoConnect,
+    Trust,
+    Profile,
+    Volume,
+    /// Not a value, opens the audio test.
+    Test,
+    /// Not a value, hides the device from the scans.
+    Hide,
+}
+
+impl SettingsRow {
+    pub const ALL: [SettingsRow; 6] = [
+        SettingsRow::AutoConnect,
+        SettingsRow::Trust,
+        SettingsRow::Profile,
+        SettingsRow::Volume,
+        SettingsRow::Test,
+        SettingsRow::Hide,
+    ];
+}
+
+#[derive(Clone)]
+pub struct DeviceSettingsScreen {
+    pub name: String,
+    pub address: Address,
+    pub settings: DeviceSettings,
+    /// Read when the screen opens, `None` until then.
+    pub details: Option<DeviceDetails>,
+    /// The link history while the device is connected, oldest first.
+    pub link_history: Vec<LinkStats>,
+    /// Index into `SettingsRow::ALL`.
+    pub selected_row: usize,
+}
+
+impl DeviceSettingsScreen {
+    /// The name with the address, which is always shown here.
+    pub fn title(&self, texts: &Texts) -> String {
+        let address = self.address.to_string();
+        if self.name == address {
+            format!("{}{}", texts.settings_title, address)
+        } else {
+            format!("{}{} ({})", texts.settings_title, self.name, address)
+        }
+    }
+
+    /// A line for each thing the device tells about itself.
+    pub fn details(&self, texts: &Texts) -> Vec<String> {
+        let Some(details) = &self.details else {
+            return Vec::new();
+        };
+        [
+            &details.manufacturer,
+            &details.model,
+            &details.firmware,
+            &details.hardware,
+        ]
+        .into_iter()
+        .zip(texts.details_labels)
+        .filter_map(|(value, label)| Some(format!("{}{}", label, value.as_ref()?)))
+        .collect()
+    }
+
+    /// A line for each part of the last link read the controller told.
+    pub fn link_stats(&self, texts: &Texts) -> Vec<String> {
+        let Some(stats) = self.link_history.last() else {
+            return Vec::new();
+        };
+        [
+            stats.rssi.map(|rssi| format!("{} dB", rssi)),
+            stats.tx_power.map(|tx_power| format!("{} dBm", tx_power)),
+            stats.packets.as_ref().map(texts.packet_stats),
+        ]
+        .into_iter()
+        .zip(texts.link_stats_labels)
+        .filter_map(|(value, label)| Some(format!("{}{}", label, value?)))
+        .collect()
+    }
+
+    /// The signal of each read of the history from 0 to 255, with how well
+    /// the audio got through, for its sparkline.
+    pub fn link_signals(&self) -> Vec<(u8, LinkQuality)> {
+        self.link_history
+            .iter()
+            .filter_map(|stats| {
+                let rssi = stats.rssi?.clamp(SPARKLINE_MIN_RSSI, 0) - SPARKLINE_MIN_RSSI;
+                let level = i16::from(rssi) * 255 / -i16::from(SPARKLINE_MIN_RSSI);
+                Some((level as u8, stats.link_quality()?))
+            })
+            .collect()
+    }
+
+    /// The label and the current value of each row.
+    pub fn rows(&self, texts: &Texts) -> Vec<(&'static str, String)> {
+        let on_off = |on: bool| if on { texts.power_on } else { texts.power_off };
+        SettingsRow::ALL
+            .iter()
+            .map(|row| {
+                let value = match row {
+                    SettingsRow::AutoConnect => on_off(self.settings.auto_connect).to_string(),
+                    SettingsRow::Trust => on_off(self.settings.trust).to_string(),
+                    SettingsRow::Profile => {
+                        texts.profile_names[self.settings.profile as usize].to_string()
+                    }
+                    SettingsRow::Volume => match self.settings.volume {
+                        Some(volume) => format!("{}%", volume),
+                        None => texts.volume_unchanged.to_string(),
+                    },
+                    SettingsRow::Test => texts.audio_test_action.to_string(),
+                    SettingsRow::Hide => texts.hide_action.to_string(),
+                };
+                (texts.settings_labels[*row as usize], value)
+            })
+            .collect()
+    }
+}
+
+pub fn draw_device_settings(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &DeviceSettingsScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, mut last_height) = drawer.draw_wrapped(&screen.title(texts), palette.text, 0, 0)?;
+    for line in screen.details(texts) {
+        last_height = drawer.draw_wrapped(&line, palette.hint, 0, last_height)?.1;
+    }
+    for line in screen.link_stats(texts) {
+        last_height = drawer.draw_wrapped(&line, palette.info, 0, last_height)?.1;
+    }
+    let signals = screen.link_signals();
+    if !signals.is_empty() {
+        last_height = draw_sparkline(drawer, &signals, last_height)?;
+    }
+
+    let rows = screen
+        .rows(texts)
+        .into_iter()
+        .map(|(label, value)| format!("{}{}", label, value))
+        .collect::<Vec<_>>();
+    let last_height = draw_rows(drawer, &rows, screen.selected_row, last_height)?;
+
+    drawer.draw_wrapped(texts.settings_hint, palette.hint, 0, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct HiddenDevicesScreen {
+    /// Whether the devices without a name are hidden, toggled by the first
+    /// row.
+    pub unnamed_hidden: bool,
+    /// Labels of the hidden devices, in the order of the config.
+    pub devices: Vec<String>,
+    /// Index into `rows`.
+    pub selected_index: usize,
+}
+
+impl HiddenDevicesScreen {
+    /// The row of the unnamed devices, then one per hidden device.
+    pub fn rows(&self, texts: &Texts) -> Vec<String> {
+        let unnamed = format!(
+            "{}{}",
+            texts.hidden_unnamed, texts.hidden_unnamed_values[self.unnamed_hidden as usize]
+        );
+        [unnamed]
+            .into_iter()
+            .chain(self.devices.iter().cloned())
+            .collect()
+    }
+}
+
+pub fn draw_hidden_devices(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &HiddenDevicesScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(texts.hidden_title, palette.text, 0, 0)?;
+    let last_height = draw_rows(
+        drawer,
+        &screen.rows(texts),
+        screen.selected_index,
+        last_height,
+    )?;
+    if screen.devices.is_empty() {
+        drawer.draw(texts.hidden_empty, palette.hint, 0, last_height)?;
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct ProfilesScreen {
+    /// Labels of the profiles, in the order of the config.
+    pub profiles: Vec<String>,
+    pub selected_index: usize,
+}
+
+pub fn draw_profiles(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &ProfilesScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(texts.profiles_title, palette.text, 0, 0)?;
+    if screen.profiles.is_empty() {
+        drawer.draw_wrapped(texts.profiles_empty, palette.hint, 0, last_height)?;
+        return Ok(());
+    }
+    draw_rows(drawer, &screen.profiles, screen.selected_index, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct NetworkScreen {
+    /// The paired phones offering tethering, with their names.
+    pub phones: Vec<(Address, String)>,
+    pub tether: TetherState,
+    pub selected_index: usize,
+}
+
+impl NetworkScreen {
+    /// The names of the phones, the tethered one marked.
+    pub fn rows(&self, texts: &Texts) -> Vec<String> {
+        self.phones
+            .iter()
+            .map(|(addr, name)| match &self.tether {
+                TetherState::Connected { addr: tethered, .. } if tethered == addr => {
+                    format!("{}{}", name, texts.tethered_mark)
+                }
+                _ => name.clone(),
+            })
+            .collect()
+    }
+
+    /// What the tethering is doing, `None` while it's off.
+    pub fn status(&self, texts: &Texts) -> Option<String> {
+        match &self.tether {
+            TetherState::Off => None,
+            TetherState::Connecting(_) => Some(texts.tether_connecting.to_string()),
+            TetherState::Connected { interface, .. } => Some((texts.tether_connected)(interface)),
+            TetherState::Failed { error, .. } => Some(format!(
+                "{}{}",
+                texts.tether_failed,
+                errors::describe(error, texts)
+            )),
+        }
+    }
+}
+
+pub fn draw_network(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &NetworkScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(texts.network_title, palette.text, 0, 0)?;
+    let last_height = match screen.status(texts) {
+        Some(status) => {
+            drawer
+                .draw_wrapped(&status, palette.info, 0, last_height)?
+                .1
+        }
+        None => last_height,
+    };
+    if screen.phones.is_empty() {
+        drawer.draw_wrapped(texts.network_empty, palette.hint, 0, last_height)?;
+        return Ok(());
+    }
+    draw_rows(
+        drawer,
+        &screen.rows(texts),
+        screen.selected_index,
+        last_height,
+    )?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct AudioTestScreen {
+    pub name: String,
+    /// The tones play on the speaker of the handheld otherwise.
+    pub connected: bool,
+    pub playing: Option<TestTone>,
+    /// The channel the channel test plays in, set by the frontend playing it.
+    pub channel: Option<Channel>,
+    /// Index into `TestTone::ALL`.
+    pub selected_index: usize,
+}
+
+impl AudioTestScreen {
+    /// The names of the tones, the playing one marked.
+    pub fn rows(&self, texts: &Texts) -> Vec<String> {
+        TestTone::ALL
+            .iter()
+            .map(|tone| {
+                let name = texts.test_tone_names[*tone as usize];
+                if self.playing == Some(*tone) {
+                    format!("{}{}", name, texts.test_tone_playing)
+                } else {
+                    name.to_string()
+                }
+            })
+            .collect()
+    }
+}
+
+pub fn draw_audio_test(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &AudioTestScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let title = format!("{}{}", texts.audio_test_title, screen.name);
+    let (_, mut last_height) = drawer.draw_wrapped(&title, palette.text, 0, 0)?;
+    if !screen.connected {
+        last_height = drawer
+            .draw_wrapped(
+                texts.audio_test_not_connected,
+                Color::RGB(255, 0, 0),
+                0,
+                last_height,
+            )?
+            .1;
+    }
+    let mut last_height = draw_rows(
+        drawer,
+        &screen.rows(texts),
+        screen.selected_index,
+        last_height,
+    )?;
+
+    if screen.playing == Some(TestTone::Channels) {
+        let line_height = drawer.line_height();
+        let width = drawer.remaining_width(0) / 2;
+        for (index, name) in texts.channel_names.iter().enumerate() {
+            let x = index as u32 * width;
+            let color = if screen
+                .channel
+                .is_some_and(|channel| channel as usize == index)
+            {
+                drawer.fill_rect(palette.highlight, x, last_height, width, line_height)?;
+                palette.highlight_text
+            } else {
+                palette.hint
+            };
+            drawer.draw_truncated(name, color, x, last_height, width)?;
+        }
+        last_height += line_height;
+        last_height = drawer
+            .draw_wrapped(texts.channel_test_hint, palette.info, 0, last_height)?
+            .1;
+    }
+
+    drawer.draw_wrapped(texts.audio_test_hint, palette.hint, 0, last_height)?;
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct PinEntryScreen {
+    pub name: String,
+    pub digits: String,
+    /// Index of the key under the cursor.
+    pub cursor: usize,
+}
+
+impl PinEntryScreen {
+    /// The keys are these digits, then delete and done.
+    pub const DIGITS: [char; 10] = ['1', '2', '3', '4', '5', '6', '7', '8', '9', '0'];
+    pub const DELETE: usize = 10;
+    pub const DONE: usize = 11;
+    pub const KEY_COUNT: usize = 12;
+
+    pub fn key_label(index: usize, texts: &Texts) -> String {
+        match index {
+            Self::DELETE => texts.pin_delete.to_string(),
+            Self::DONE => texts.pin_done.to_string(),
+            digit => Self::DIGITS[digit].to_string(),
+        }
+    }
+}
+
+pub fn draw_pin_entry(
+    drawer: &mut TextDrawer, texts: &Texts, screen: &PinEntryScreen,
+) -> anyhow::Result<()> {
+    let palette = drawer.palette;
+
+    let (_, last_height) = drawer.draw_wrapped(
+        &format!("{}{}", texts.pin_title, screen.name),
+        palette.text,
+        0,
+        0,
+    )?;
+    let digits = if screen.digits.is_empty() {
+        "_"
+    } else {
+        &screen.digits
+    };
+    let (_, y) = drawer.draw(digits, palette.info, 0, last_height)?;
+
+    let line_height = drawer.line_height();
+    let key_width = drawer.remaining_width(0) / PinEntryScreen::KEY_COUNT as u32;
+    for index in 0..PinEntryScreen::KEY_COUNT {
+        let x = index as u32 * key_width;
+        let color = if index == screen.cursor {
+            drawer.fill_rect(palette.highlight, x, y, key_width, line_height)?;
+            palette.highlight_text
+        } else {
+            palette.hint
+        };
+        let label = PinEntryScreen::key_label(index, texts);
+        drawer.draw_truncated(&label, color, x, y, key_width)?;
+    }
+
+    drawer.draw_wrapped(texts.pin_hint, palette.hint, 0, y + line_height)?;
+    Ok(())
+}
+
+/// A bar for each value of 0 to 255, a line high, returns where it ends.
+fn draw_sparkline(
+    drawer: &mut TextDrawer, values: &[(u8, LinkQuality)], y: u32,
+) -> anyhow::Result<u32> {
+    let palette = drawer.palette;
+    let line_height = drawer.line_height();
+
+    for (index, (value, quality)) in values.iter().enumerate() {
+        let height = (u32::from(*value) * line_height / 255).max(1);
+        let color = if *quality == LinkQuality::Weak {
+            Color::RGB(255, 0, 0)
+        } else {
+            palette.info
+        };
+        drawer.fill_rect(
+            color,
+            index as u32 * SPARKLINE_BAR_WIDTH,
+            y + line_height - height,
+            SPARKLINE_BAR_WIDTH - 1,
+            height,
+        )?;
+    }
+    Ok(y + line_height)
+}
+
+/// One line per row with the selected one highlighted, returns where the rows
+/// end.
+fn draw_rows(
+    drawer: &mut TextDrawer, rows: &[String], selected_index: usize, mut y: u32,
+) -> anyhow::Result<u32> {
+    let palette = drawer.palette;
+    let line_height = drawer.line_height();
+
+    for (index, row) in rows.iter().enumerate() {
+        let color = if index == selected_index {
+            drawer.fill_rect(
+                palette.highlight,
+                0,
+                y,
+                drawer.remaining_width(0),
+                line_height,
+            )?;
+            palette.highlight_text
+        } else {
+            palette.hint
+        };
+        drawer.draw_truncated(row, color, 0, y, drawer.remaining_width(0))?;
+        y += line_height;
+    }
+    Ok(y)
+}
+
+#[derive(Default, Clone)]
+pub struct MainScreen {
+    pub state: AppState,
+    pub selected_device_index: usize,
+    pub notice: Option<String>,
+    /// Name of the last connected device, while it isn't connected.
+    pub reconnect_last: Option<String>,
+    pub favorites: Vec<Address>,
+    /// Put the address after the name of each device.
+    pub show_addresses: bool,
+    /// Whole seconds until the running scan ends.
+    pub scan_seconds_left: Option<u64>,
+    /// Devices found but not in `state.devices`, shown as a last row which
+    /// lists them.
+    pub more_devices: usize,
+    /// No game controller is open, set by the SDL frontend.
+    pub no_controller: bool,
+    /// The battery of the handheld, when it has one.
+    pub handheld_battery: Option<HandheldBattery>,
+}
+
+impl MainScreen {
+    /// A row for each device, and the row of the devices not listed.
+    pub fn list_rows(&self, texts: &Texts) -> Vec<String> {
+        let mut rows = self
+            .state
+            .devices
+            .iter()
+            .map(|device| device_label(device, &self.favorites, self.show_addresses, texts))
+            .collect::<Vec<_>>();
+        if self.more_devices > 0 {
+            rows.push((texts.list_more)(self.more_devices));
+        }
+        rows
+    }
+
+    /// Every device found, listed or not.
+    pub fn found_count(&self) -> usize {
+        self.state.devices.len() + self.more_devices
+    }
+
+    /// The scanning status with the countdown.
+    pub fn scanning_text(&self, texts: &Texts) -> String {
+        match self.scan_seconds_left {
+            Some(seconds) => format!("{} {}s", texts.scanning, seconds),
+            None => texts.scanning.to_string(),
+        }
+    }
+}
+
+/// Like "Playing: Title - Artist 1:23 / 4:56", the artist and the duration
+/// are left out when unknown.
+pub fn now_playing_text(now_playing: &NowPlaying, texts: &Texts) -> String {
+    let minutes = |duration: Duration| {
+        let secs = duration.as_secs();
+        format!("{}:{:02}", secs / 60, secs % 60)
+    };
+
+    let mut text = if now_playing.playing {
+        texts.now_playing.to_string()
+    } else {
+        texts.now_paused.to_string()
+    };
+    text.push_str(&now_playing.title);
+    if !now_playing.artist.is_empty() {
+        text.push_str(&format!(" - {}", now_playing.artist));
+    }
+    text.push_str(&format!(" {}", minutes(now_playing.position)));
+    if let Some(duration) = now_playing.duration {
+        text.push_str(&format!(" / {}", minutes(duration)));
+    }
+    text
+}
+
+pub fn draw_main(
+    drawer: &mut TextDrawer, device_list: &mut DeviceList, texts: &Texts, screen: &MainScreen,
+) -> anyhow::Result<()> {
+    let state = &screen.state;
+    let palette = drawer.palette;
+
+    let top = match &screen.notice {
+        Some(notice) => drawer.draw_wrapped(notice, Color::RGB(255, 0, 0), 0, 0)?.1,
+        None => 0,
+    };
+
+    let (_, b_height) = drawer.draw_wrapped(texts.quit_hint, palette.text, 0, top)?;
+    let b_height = if screen.no_controller {
+        drawer
+            .draw_wrapped(texts.no_controller, palette.hint, 0, b_height)?
+            .1
+    } else {
+        b_height
+    };
+
+    if !state.adapter_present {
+        let (_, last_height) =
+            drawer.draw_wrapped(texts.adapter_removed, Color::RGB(255, 0, 0), 0, b_height)?;
+        drawer.draw_wrapped(texts.adapter_removed_hint, palette.hint, 0, last_height)?;
+        return Ok(());
+    }
+
+    let (last_width, last_height) =
+        drawer.draw_wrapped(texts.power_hint, palette.text, 0, b_height)?;
+
+    // the hint may be wrapped, so the status goes behind its last line
+    let status_y = drawer.last_line_y(last_height);
+    let (power_width, _) = if state.powered {
+        drawer.draw(texts.power_on, Color::RGB(0, 255, 0), last_width, status_y)?
+    } else {
+        drawer.draw(texts.power_off, Color::RGB(255, 0, 0), last_width, status_y)?
+    };
+    if let Some(battery) = screen.handheld_battery {
+        let color = if battery.is_low() {
+            Color::RGB(255, 0, 0)
+        } else {
+            palette.hint
+        };
+        // behind the power status, `draw` returns the right edge with the
+        // padding, which leaves a gap
+        drawer.draw_truncated(
+            &(texts.handheld_battery)(battery.level, battery.charging),
+            color,
+            power_width,
+            status_y,
+            drawer.remaining_width(power_width),
+        )?;
+    }
+
+    let last_height = match &screen.reconnect_last {
+        Some(name) => {
+            drawer
+                .draw_wrapped(
+                    &format!("{}{}", texts.reconnect_last_hint, name),
+                    palette.info,
+                    0,
+                    last_height,
+                )?
+                .1
+        }
+        None => last_height,
+    };
+
+    let (_, last_height) = match state.scan_status {
+        BluetoothScanStatus::Disable => drawer.draw(" ", palette.text, 0, last_height)?,
+        BluetoothScanStatus::Scanning => {
+            drawer.draw(&screen.scanning_text(texts), palette.info, 0, last_height)?
+        }
+        BluetoothScanStatus::Finished => {
+            let (success_width, success_height) =
+                drawer.draw(texts.scan_finished, Color::RGB(0, 255, 0), 0, last_height)?;
+
+            if let Some(info) = state.connected_device() {
+                drawer.draw_truncated(
+                    &format!("{}{}", texts.connected_to, &info.name),
+                    palette.hint,
+                    success_width,
+                    last_height,
+                    drawer.remaining_width(success_width),
+                )?;
+            } else {
+                drawer.draw(
+                    texts.not_connected,
+                    palette.hint,
+                    success_width,
+                    last_height,
+                )?;
+            }
+
+            (success_width, success_height)
+        }
+        BluetoothScanStatus::Failed => {
+            let (failed_width, failed_height) =
+                drawer.draw(texts.scan_failed, Color::RGB(255, 0, 0), 0, last_height)?;
+            drawer.draw(texts.rescan_hint, palette.hint, failed_width, last_height)?;
+            (failed_width, failed_height)
+        }
+    };
+
+    let last_height = match &state.now_playing {
+        Some(now_playing) => {
+            drawer
+                .draw_truncated(
+                    &now_playing_text(now_playing, texts),
+                    palette.info,
+                    0,
+                    last_height,
+                    drawer.remaining_width(0),
+                )?
+                .1
+        }
+        None => last_height,
+    };
+
+    if state.scan_status != BluetoothScanStatus::Finished {
+        return Ok(());
+    }
+
+    let (_, last_height) = drawer.draw_wrapped(
+        &(texts.list_hint)(screen.found_count()),
+        palette.text,
+        0,
+        last_height,
+    )?;
+    let (_, last_height) =
+        drawer.draw_wrapped(texts.favorite_hint, palette.hint, 0, last_height)?;
+
+    // keep a line below the list for the connect status
+    let list_bottom = drawer
+        .height
+        .saturating_sub(PADDING * 3 + drawer.line_height());
+    let last_height = device_list.draw(
+        drawer,
+        &screen.list_rows(texts),
+        screen.selected_device_index,
+        last_height,
+        list_bottom,
+    )?;
+
+    match &state.connect_state {
+        BluetoothConnectState::Idle => {
+            drawer.draw(" ", palette.text, 0, last_height)?;
+        }
+        BluetoothConnectState::InProgress(stage) => {
+            drawer.draw(
+                texts.connect_in_progress[*stage as usize],
+                palette.info,
+                0,
+                last_height,
+            )?;
+        }
+        BluetoothConnectState::Connected => {
+            let (width, _) = drawer.draw(
+                texts.connect_succeeded,
+                Color::RGB(0, 255, 0),
+                0,
+                last_height,
+            )?;
+            if let Some(quality) = state.link_quality() {
+                let color = match quality {
+                    LinkQuality::Weak => Color::RGB(255, 0, 0),
+                    _ => palette.hint,
+                };
+                drawer.draw_truncated(
+                    texts.link_quality[quality as usize],
+                    color,
+                    width,
+                    last_height,
+                    drawer.remaining_width(width),
+                )?;
+            }
+        }
+        BluetoothConnectState::Failed { stage, error } => {
+            drawer.draw_truncated(
+                &format!(
+                    "{}{}",
+                    texts.connect_failed[*stage as usize],
+                    errors::describe(error, texts)
+                ),
+                Color::RGB(255, 0, 0),
+                0,
+                last_height,
+                drawer.remaining_width(0),
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,305 @@
+use crate::{
+    config::{Config, Rotation, Theme},
+    paths,
+    ui::{theme::Palette, PADDING, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+use anyhow::anyhow;
+use sdl2::{
+    image::LoadTexture,
+    pixels::Color,
+    rect::Rect,
+    render::{Texture, TextureCreator, TextureQuery, WindowCanvas},
+    ttf::Font,
+    video::WindowContext,
+};
+use std::collections::HashMap;
+use tracing::warn;
+
+// the texts cut by `draw_truncated` are kept, as the same ones are drawn each
+// frame, and forgotten all at once past this many, like when a scan lists
+// other devices
+const TRUNCATED_CACHE_SIZE: usize = 256;
+
+pub struct TextDrawer<'texture, 'ttf_module, 'rwops> {
+    canvas: WindowCanvas,
+    texture_creator: &'texture TextureCreator<WindowContext>,
+    font: Font<'ttf_module, 'rwops>,
+    /// Size of the UI before rotating.
+    width: u32,
+    pub height: u32,
+    /// When rotated, the frame is drawn here and then copied rotated onto the window.
+    target: Option<Texture<'texture>>,
+    /// The one at start, changing it needs a restart.
+    rotation: Rotation,
+    angle: f64,
+    background: Option<Texture<'texture>>,
+    background_fade: u8,
+    pub palette: Palette,
+    /// What `draw_truncated` draws for a text and a width.
+    truncated: HashMap<(String, u32), String>,
+}
+
+impl<'texture, 'ttf_module, 'rwops> TextDrawer<'texture, 'ttf_module, 'rwops> {
+    pub fn new(
+        mut canvas: WindowCanvas, texture_creator: &'texture TextureCreator<WindowContext>,
+        font: Font<'ttf_module, 'rwops>, config: &Config,
+    ) -> anyhow::Result<Self> {
+        let rotation = config.rotation;
+        let (width, height) = rotation.logical_size();
+        let target = create_target(texture_creator, rotation)?;
+        let background = load_background(texture_creator, config);
+
+        let palette = Palette::of(config.theme);
+        canvas.set_draw_color(palette.background);
+
+        Ok(Self {
+            canvas,
+            texture_creator,
+            font,
+            width,
+            height,
+            target,
+            rotation,
+            angle: rotation.angle(),
+            background,
+            background_fade: config.background_fade,
+            palette,
+            truncated: HashMap::new(),
+        })
+    }
+
+    /// Free the textures kept between frames while nothing is drawn, like
+    /// while the window is in the background. `restore_textures` brings them
+    /// back before drawing again.
+    pub fn release_textures(&mut self) {
+        self.target = None;
+        self.background = None;
+    }
+
+    /// The background is the one of the current config, which may have
+    /// changed in the meantime. A texture which can't be made is left out,
+    /// the frame is drawn without it.
+    pub fn restore_textures(&mut self, config: &Config) {
+        match create_target(self.texture_creator, self.rotation) {
+            Ok(target) => self.target = target,
+            Err(err) => warn!(?err, "create rotated frame failed, draw unrotated"),
+        }
+        self.background = load_background(self.texture_creator, config);
+        self.background_fade = config.background_fade;
+    }
+
+    /// Switch the colors, from the next frame on.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.palette = Palette::of(theme);
+        self.canvas.set_draw_color(self.palette.background);
+    }
+
+    pub fn draw(&mut self, text: &str, color: Color, x: u32, y: u32) -> anyhow::Result<(u32, u32)> {
+        let surface = self.font.render(text).blended(color)?;
+        let texture = self.texture_creator.create_texture_from_surface(&surface)?;
+        let TextureQuery { width, height, .. } = texture.query();
+        let target = rect!(PADDING + x, PADDING + y, width, height);
+        if let Err(err) = self.canvas.copy(&texture, None, Some(target)) {
+            return Err(anyhow!("{}", err));
+        }
+        Ok((PADDING + x + width, PADDING + y + height))
+    }
+
+    /// Like `draw`, but breaks the text into several lines when it would
+    /// overflow the right edge of the screen.
+    pub fn draw_wrapped(
+        &mut self, text: &str, color: Color, x: u32, y: u32,
+    ) -> anyhow::Result<(u32, u32)> {
+        let max_width = self.remaining_width(x);
+
+        let mut result = (x, y);
+        let mut line_y = y;
+        for line in self.wrap(text, max_width)? {
+            result = self.draw(line, color, x, line_y)?;
+            line_y = result.1 - PADDING;
+        }
+        Ok(result)
+    }
+
+    /// Like `draw`, but cuts the text and appends "…" when it is wider than
+    /// `max_width`.
+    pub fn draw_truncated(
+        &mut self, text: &str, color: Color, x: u32, y: u32, max_width: u32,
+    ) -> anyhow::Result<(u32, u32)> {
+        let key = (text.to_string(), max_width);
+        let truncated = match self.truncated.get(&key) {
+            Some(truncated) => truncated.clone(),
+            None => {
+                let truncated = self.truncate(text, max_width)?;
+                if self.truncated.len() >= TRUNCATED_CACHE_SIZE {
+                    self.truncated.clear();
+                }
+                self.truncated.insert(key, truncated.clone());
+                truncated
+            }
+        };
+        self.draw(&truncated, color, x, y)
+    }
+
+    /// The longest start of the text which fits with "…", found by a binary
+    /// search over its characters.
+    fn truncate(&self, text: &str, max_width: u32) -> anyhow::Result<String> {
+        if self.font.size_of(text)?.0 <= max_width {
+            return Ok(text.to_string());
+        }
+
+        let ends = text
+            .char_indices()
+            .skip(1)
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        // the first `fitting` ends fit, the ones from `too_wide` on don't
+        let (mut fitting, mut too_wide) = (0, ends.len());
+        while fitting < too_wide {
+            let middle = (fitting + too_wide) / 2;
+            if self.font.size_of(&format!("{}…", &text[..ends[middle]]))?.0 <= max_width {
+                fitting = middle + 1;
+            } else {
+                too_wide = middle;
+            }
+        }
+        Ok(match fitting {
+            0 => "…".to_string(),
+            fitting => format!("{}…", &text[..ends[fitting - 1]]),
+        })
+    }
+
+    /// Fill a rectangle, placed the same way as the text of `draw`.
+    pub fn fill_rect(
+        &mut self, color: Color, x: u32, y: u32, width: u32, height: u32,
+    ) -> anyhow::Result<()> {
+        self.canvas.set_draw_color(color);
+        let result = self
+            .canvas
+            .fill_rect(rect!(PADDING + x, PADDING + y, width, height));
+        self.canvas.set_draw_color(self.palette.background);
+        result.map_err(anyhow::Error::msg)
+    }
+
+    pub fn set_clip_rect(&mut self, x: u32, y: u32, width: u32, height: u32) {
+        self.canvas
+            .set_clip_rect(rect!(PADDING + x, PADDING + y, width, height));
+    }
+
+    pub fn reset_clip_rect(&mut self) {
+        self.canvas.set_clip_rect(None);
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.font.height() as u32
+    }
+
+    /// Width left from `x` to the right padding of the screen.
+    pub fn remaining_width(&self, x: u32) -> u32 {
+        self.width.saturating_sub(PADDING * 2 + x)
+    }
+
+    fn wrap<'a>(&self, text: &'a str, max_width: u32) -> anyhow::Result<Vec<&'a str>> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for (index, ch) in text.char_indices() {
+            let (width, _) = self.font.size_of(&text[start..index + ch.len_utf8()])?;
+            if width > max_width && index > start {
+                lines.push(&text[start..index]);
+                start = index;
+            }
+        }
+        lines.push(&text[start..]);
+        Ok(lines)
+    }
+
+    /// The `y` to pass to `draw` for continuing on the last line of a text
+    /// whose returned bottom is `bottom`.
+    pub fn last_line_y(&self, bottom: u32) -> u32 {
+        bottom.saturating_sub(PADDING + self.line_height())
+    }
+
+    pub fn clear(&mut self) -> anyhow::Result<()> {
+        if let Some(target) = &self.target {
+            self.set_render_target(target.raw())?;
+        }
+        self.canvas.clear();
+
+        if let Some(background) = &self.background {
+            self.canvas
+                .copy(background, None, None)
+                .map_err(anyhow::Error::msg)?;
+
+            // fade the image so that the text on it stays readable
+            let background = self.palette.background;
+            self.canvas.set_draw_color(Color::RGBA(
+                background.r,
+                background.g,
+                background.b,
+                self.background_fade,
+            ));
+            self.canvas.fill_rect(None).map_err(anyhow::Error::msg)?;
+            self.canvas.set_draw_color(background);
+        }
+
+        Ok(())
+    }
+
+    pub fn present(&mut self) -> anyhow::Result<()> {
+        if let Some(target) = &self.target {
+            self.set_render_target(std::ptr::null_mut())?;
+            self.canvas.clear();
+
+            // rotate around the window center
+            let TextureQuery { width, height, .. } = target.query();
+            let dst = Rect::new(
+                (SCREEN_WIDTH as i32 - width as i32) / 2,
+                (SCREEN_HEIGHT as i32 - height as i32) / 2,
+                width,
+                height,
+            );
+            self.canvas
+                .copy_ex(target, None, Some(dst), self.angle, None, false, false)
+                .map_err(anyhow::Error::msg)?;
+        }
+        self.canvas.present();
+        Ok(())
+    }
+
+    // `Canvas::with_texture_canvas` only takes a closure, which doesn't fit a
+    // frame drawn across awaits, so switch the target by hand.
+    fn set_render_target(&self, texture: *mut sdl2::sys::SDL_Texture) -> anyhow::Result<()> {
+        // SAFETY: the renderer and the texture are both alive, and the texture
+        // was created by this renderer with the `Target` access.
+        if unsafe { sdl2::sys::SDL_SetRenderTarget(self.canvas.raw(), texture) } != 0 {
+            return Err(anyhow!("{}", sdl2::get_error()));
+        }
+        Ok(())
+    }
+}
+
+/// The texture a rotated frame is drawn to, none without rotation.
+fn create_target(
+    texture_creator: &TextureCreator<WindowContext>, rotation: Rotation,
+) -> anyhow::Result<Option<Texture<'_>>> {
+    if rotation == Rotation::Deg0 {
+        return Ok(None);
+    }
+    let (width, height) = rotation.logical_size();
+    Ok(Some(
+        texture_creator.create_texture_target(None, width, height)?,
+    ))
+}
+
+fn load_background<'texture>(
+    texture_creator: &'texture TextureCreator<WindowContext>, config: &Config,
+) -> Option<Texture<'texture>> {
+    let path = config.background.as_ref()?;
+    match texture_creator.load_texture(paths::resolve(path)) {
+        Ok(background) => Some(background),
+        Err(err) => {
+            warn!(?path, %err, "load background failed");
+            None
+        }
+    }
+}
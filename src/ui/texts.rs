@@ -0,0 +1,716 @@
+use crate::config::Language;
+use bluetooth_audio_connector_tg5040::PacketStats;
+
+/// Every text shown by the UI, in one language.
+pub struct Texts {
+    pub quitting: &'static str,
+    pub quit_hint: &'static str,
+    /// Under the quit hint while no game controller is open.
+    pub no_controller: &'static str,
+    pub power_hint: &'static str,
+    pub power_on: &'static str,
+    pub power_off: &'static str,
+    pub scanning: &'static str,
+    pub scan_finished: &'static str,
+    pub connected_to: &'static str,
+    pub not_connected: &'static str,
+    pub scan_failed: &'static str,
+    pub rescan_hint: &'static str,
+    pub list_hint: fn(usize) -> String,
+    /// The last row of a long list, takes the number of devices not listed.
+    pub list_more: fn(usize) -> String,
+    /// Indexed by `ConnectStage`.
+    pub connect_in_progress: [&'static str; 5],
+    /// Indexed by `ConnectStage`, followed by the error.
+    pub connect_failed: [&'static str; 5],
+    pub connect_succeeded: &'static str,
+    /// Indexed by `LinkQuality`, after the connect status.
+    pub link_quality: [&'static str; 3],
+    /// Appended to the name of the connected device in the list.
+    pub connected_mark: &'static str,
+    /// Appended to the name of a device advertising it's ready to pair.
+    pub pairing_mode_mark: &'static str,
+    /// Before the battery of the left and the right bud and of the case.
+    pub buds_battery_labels: [&'static str; 3],
+    pub crash_notice: fn(&str) -> String,
+    /// Followed by the name of the last connected device.
+    pub reconnect_last_hint: &'static str,
+    pub favorite_hint: &'static str,
+    /// Followed by the name of the preferred device.
+    pub auto_connect_title: &'static str,
+    pub auto_connect_waiting: &'static str,
+    pub auto_connect_hint: &'static str,
+    pub splash_searching: &'static str,
+    pub splash_disconnecting: &'static str,
+    pub splash_disconnected: &'static str,
+    /// Followed by the reason.
+    pub splash_disconnect_failed: &'static str,
+    pub splash_no_device: &'static str,
+    /// The connect took too long and was given up.
+    pub splash_timed_out: &'static str,
+    /// Followed by the name of the device.
+    pub settings_title: &'static str,
+    /// Indexed by `SettingsRow`, followed by the value.
+    pub settings_labels: [&'static str; 6],
+    /// The maker, model, firmware and hardware of the device, followed by
+    /// what it tells.
+    pub details_labels: [&'static str; 4],
+    /// The signal and transmit power of the connected device and the packets
+    /// of the adapter, followed by the last read.
+    pub link_stats_labels: [&'static str; 3],
+    pub packet_stats: fn(&PacketStats) -> String,
+    /// Indexed by `AudioProfile`.
+    pub profile_names: [&'static str; 2],
+    pub volume_unchanged: &'static str,
+    pub settings_hint: &'static str,
+    /// The value of the hide row.
+    pub hide_action: &'static str,
+    /// The value of the audio test row.
+    pub audio_test_action: &'static str,
+    /// Followed by the name of the device.
+    pub audio_test_title: &'static str,
+    pub audio_test_not_connected: &'static str,
+    pub audio_test_hint: &'static str,
+    /// Indexed by `TestTone`.
+    pub test_tone_names: [&'static str; 3],
+    /// After the name of the tone playing.
+    pub test_tone_playing: &'static str,
+    /// Indexed by `Channel`, the one playing highlighted.
+    pub channel_names: [&'static str; 2],
+    pub channel_test_hint: &'static str,
+    pub hidden_title: &'static str,
+    pub hidden_empty: &'static str,
+    /// The first row of the hidden devices, followed by the value.
+    pub hidden_unnamed: &'static str,
+    /// Shown and hidden.
+    pub hidden_unnamed_values: [&'static str; 2],
+    /// Takes the step and the number of steps.
+    pub remap_title: fn(usize, usize) -> String,
+    /// Indexed like `Keymap::REMAP_ACTIONS`.
+    pub remap_actions: [&'static str; 15],
+    pub remap_taken: &'static str,
+    pub remap_hint: &'static str,
+    pub logs_title: &'static str,
+    /// Followed by the sort order picked.
+    pub sort_title: &'static str,
+    /// Indexed by `SortOrder`.
+    pub sort_names: [&'static str; 3],
+    pub about_title: &'static str,
+    /// Each followed by the version.
+    pub about_app: &'static str,
+    pub about_bluez: &'static str,
+    pub about_kernel: &'static str,
+    /// In place of a version which can't be read.
+    pub about_unknown: &'static str,
+    /// Takes the version of BlueZ and the minimum.
+    pub bluez_outdated: fn(&str, &str) -> String,
+    /// The keys of the default keymap, for using it without a controller.
+    pub about_keys: &'static str,
+    pub about_hint: &'static str,
+    pub profiles_title: &'static str,
+    pub profiles_empty: &'static str,
+    pub network_title: &'static str,
+    pub network_empty: &'static str,
+    /// Appended to the name of the tethered phone.
+    pub tethered_mark: &'static str,
+    pub tether_connecting: &'static str,
+    /// Takes the interface of the phone.
+    pub tether_connected: fn(&str) -> String,
+    /// Followed by the error.
+    pub tether_failed: &'static str,
+    pub connect_busy: &'static str,
+    /// The manager has too many commands waiting to take the press.
+    pub manager_busy: &'static str,
+    /// The battery of the handheld in percent, and whether it's charging.
+    pub handheld_battery: fn(u8, bool) -> String,
+    pub low_battery: &'static str,
+    /// Before the track of the connected device, playing or paused.
+    pub now_playing: &'static str,
+    pub now_paused: &'static str,
+    /// The connected device dropped its audio, and got it back.
+    pub audio_switched_away: &'static str,
+    pub audio_returned: &'static str,
+    /// Asked before connecting a device which isn't an audio device.
+    pub not_audio_confirm: &'static str,
+    /// While a connect waits for another operation of BlueZ.
+    pub bluez_busy: &'static str,
+    /// Followed by the name of the device.
+    pub pin_title: &'static str,
+    pub pin_delete: &'static str,
+    pub pin_done: &'static str,
+    pub pin_hint: &'static str,
+    pub adapter_removed: &'static str,
+    pub adapter_removed_hint: &'static str,
+    pub preflight_title: &'static str,
+    /// Indexed by `Component`.
+    pub preflight_problems: [&'static str; 4],
+    /// Indexed by `Component`.
+    pub preflight_fixes: [&'static str; 4],
+    pub preflight_hint: &'static str,
+    pub errors: ErrorTexts,
+}
+
+pub struct ErrorTexts {
+    pub adapter_missing: &'static str,
+    pub bluez_down: &'static str,
+    pub powered_off: &'static str,
+    pub pairing_rejected: &'static str,
+    pub pairing_interrupted: &'static str,
+    pub wrong_pin: &'static str,
+    pub pin_required: &'static str,
+    pub page_timeout: &'static str,
+    pub in_progress: &'static str,
+    pub audio_routing_failed: &'static str,
+    pub device_not_found: &'static str,
+    pub no_network_address: &'static str,
+    pub network_setup: &'static str,
+    /// Hints for the error names BlueZ reports on connect failures.
+    pub bluez: &'static [(&'static str, &'static str)],
+}
+
+pub fn texts(language: Language) -> &'static Texts {
+    match language {
+        Language::Zh => &ZH,
+        Language::En => &EN,
+    }
+}
+
+static ZH: Texts = Texts {
+    quitting: "退出中……",
+    quit_hint: "按B退出程序，按L3选择连接方案，按R3查看日志。",
+    no_controller: "未检测到手柄，只能使用键盘。",
+    power_hint: "按Y打开蓝牙，按X关闭蓝牙。当前蓝牙状态：",
+    power_on: "开",
+    power_off: "关",
+    scanning: "扫描中……",
+    scan_finished: "扫描成功",
+    connected_to: "已连接：",
+    not_connected: "未连接蓝牙",
+    scan_failed: "扫描失败",
+    rescan_hint: "按L重新扫描",
+    list_hint: zh_list_hint,
+    list_more: zh_list_more,
+    connect_in_progress: [
+        "断开中……",
+        "配对中……",
+        "连接中……",
+        "配对已失效，重新配对中……",
+        "检查音频中……",
+    ],
+    connect_failed: [
+        "断开失败：",
+        "配对失败：",
+        "连接失败：",
+        "重新配对失败：",
+        "检查音频失败：",
+    ],
+    connect_succeeded: "连接成功",
+    link_quality: ["，信号强", "，信号一般", "，信号弱，声音可能断续"],
+    connected_mark: "（已连接）",
+    pairing_mode_mark: "（配对模式）",
+    buds_battery_labels: ["左", "右", "盒"],
+    crash_notice: zh_crash_notice,
+    reconnect_last_hint: "按R重新连接上次设备：",
+    favorite_hint: "按MENU收藏，收藏的设备置顶，可按数字键直接连接",
+    auto_connect_title: "自动连接：",
+    auto_connect_waiting: "正在打开蓝牙……",
+    auto_connect_hint: "按B退出程序，按其他键查看设备列表。",
+    splash_searching: "正在查找设备……",
+    splash_disconnecting: "正在断开……",
+    splash_disconnected: "已断开",
+    splash_disconnect_failed: "断开失败：",
+    splash_no_device: "没有可连接的设备，请先在设备列表中连接一次或设置首选设备",
+    splash_timed_out: "连接超时，请确认设备已开启并在附近",
+    settings_title: "设备设置：",
+    settings_labels: [
+        "开机自动连接：",
+        "配对后信任设备：",
+        "音频模式：",
+        "连接后音量：",
+        "音频测试：",
+        "扫描时隐藏：",
+    ],
+    details_labels: ["厂商：", "型号：", "固件：", "硬件："],
+    link_stats_labels: ["信号：", "发射功率：", "适配器数据包："],
+    packet_stats: zh_packet_stats,
+    profile_names: ["高音质（A2DP）", "通话（HFP）"],
+    volume_unchanged: "不调整",
+    settings_hint: "使用 ↑↓ 选择，←→ 修改，按B返回。",
+    hide_action: "按A隐藏",
+    audio_test_action: "按A打开",
+    audio_test_title: "音频测试：",
+    audio_test_not_connected: "设备未连接，声音会从本机扬声器播放",
+    audio_test_hint: "使用 ↑↓ 选择，按A播放或停止，按B返回。",
+    test_tone_names: ["扫频正弦波（20 Hz 至 20 kHz）", "粉红噪声", "左右声道"],
+    test_tone_playing: "（播放中）",
+    channel_names: ["左", "右"],
+    channel_test_hint: "声音应只从亮起的一侧传出，两侧都响是单声道，方向相反是左右接反了",
+    hidden_title: "已隐藏的设备，按A切换或取消隐藏，按B返回：",
+    hidden_empty: "没有隐藏的设备",
+    hidden_unnamed: "没有名称的设备：",
+    hidden_unnamed_values: ["显示", "隐藏（已配对的除外）"],
+    remap_title: zh_remap_title,
+    remap_actions: [
+        "退出程序 / 返回",
+        "打开蓝牙",
+        "关闭蓝牙",
+        "上",
+        "右",
+        "下",
+        "左",
+        "重新扫描",
+        "连接蓝牙 / 确认",
+        "断开蓝牙",
+        "忘记蓝牙",
+        "重新连接上次设备",
+        "收藏蓝牙",
+        "查看日志",
+        "连接方案",
+    ],
+    remap_taken: "这个按键已经用过了，请换一个",
+    remap_hint: "按键盘的Esc取消，完成后自动保存到配置文件。",
+    logs_title: "最近的日志，↑↓滚动，←→翻页，按B返回：",
+    sort_title: "排序：",
+    sort_names: ["发现顺序", "名称", "信号强度"],
+    about_title: "关于",
+    about_app: "程序版本：",
+    about_bluez: "BlueZ版本：",
+    about_kernel: "内核版本：",
+    about_unknown: "未知",
+    bluez_outdated: zh_bluez_outdated,
+    about_keys: "键盘：方向键选择，Enter连接，Backspace返回，F5重新扫描，+/-调节音量，D断开，F忘记，S收藏，P连接方案，N网络共享，G日志，K设置按键，T切换主题，E切换语言，O切换排序。",
+    about_hint: "按B返回。",
+    profiles_title: "连接方案，按A连接并应用设置，按B返回：",
+    profiles_empty: "没有连接方案，请在配置文件中添加 [[profiles]]",
+    network_title: "蓝牙网络共享，按A连接或断开手机的网络，按B返回：",
+    network_empty: "没有可共享网络的已配对手机，请先在手机上打开蓝牙网络共享，再配对并扫描",
+    tethered_mark: "（共享中）",
+    tether_connecting: "正在连接手机的网络……",
+    tether_connected: zh_tether_connected,
+    tether_failed: "连接手机的网络失败：",
+    connect_busy: "正在连接，请稍候，按SELECT取消",
+    manager_busy: "正在处理，请稍候",
+    handheld_battery: zh_handheld_battery,
+    low_battery: "本机电量不足10%，请先充电，配对中途断电会导致配对失败",
+    now_playing: "正在播放：",
+    now_paused: "已暂停：",
+    audio_switched_away: "设备的声音已切换到其他设备（如手机），仍保持连接",
+    audio_returned: "设备的声音已切换回本机",
+    not_audio_confirm: "此设备似乎不是音频设备，仍要连接？再按A连接",
+    bluez_busy: "蓝牙正忙于其他操作，等待完成后继续连接……",
+    pin_title: "请输入PIN码：",
+    pin_delete: "删除",
+    pin_done: "确定",
+    pin_hint: "使用 ←→ 选择，按A输入，按B取消。常见的PIN码在设备说明书上。",
+    adapter_removed: "适配器已移除",
+    adapter_removed_hint: "重新插入蓝牙适配器后自动恢复。",
+    preflight_title: "蓝牙环境检查未通过：",
+    preflight_problems: [
+        "蓝牙服务（bluetoothd）未运行",
+        "未找到蓝牙适配器",
+        "蓝牙被 rfkill 禁用",
+        "未找到音频服务",
+    ],
+    preflight_fixes: [
+        "请重启设备，或在终端运行 bluetoothd",
+        "请确认设备带有蓝牙，或插入蓝牙适配器",
+        "请在终端运行 rfkill unblock bluetooth",
+        "请启动 PulseAudio、PipeWire 或 BlueALSA，否则连接后没有声音",
+    ],
+    preflight_hint: "按A重新检查，按Y忽略并继续，按B退出程序。",
+    errors: ErrorTexts {
+        adapter_missing: "未找到蓝牙适配器",
+        bluez_down: "蓝牙服务未运行，请重启设备后重试",
+        powered_off: "蓝牙未打开，请按Y打开蓝牙",
+        pairing_rejected: "配对被拒绝，请让设备进入配对模式后重试",
+        pairing_interrupted: "配对中断，请让设备保持在配对模式后重试",
+        wrong_pin: "PIN码错误，请重新输入",
+        pin_required: "设备需要PIN码，请查看设备说明书",
+        page_timeout: "设备未开机或不在配对模式",
+        in_progress: "蓝牙正忙，请稍后重试",
+        audio_routing_failed: "已连接但音频不可用，请重新连接",
+        device_not_found: "设备已不在附近，请按L重新扫描",
+        no_network_address: "手机没有分配网络地址，请在手机上打开蓝牙网络共享",
+        network_setup: "无法设置网络地址，请查看日志",
+        bluez: &[
+            ("br-connection-already-connected", "设备已经连接"),
+            ("br-connection-page-timeout", "设备未开机或不在配对模式"),
+            ("br-connection-profile-unavailable", "设备不支持音频连接"),
+            ("br-connection-sdp-search", "读取设备服务失败，请重试"),
+            ("br-connection-create-socket", "无法建立连接，请重试"),
+            (
+                "br-connection-adapter-not-powered",
+                "蓝牙未打开，请按Y打开蓝牙",
+            ),
+            ("br-connection-not-supported", "设备不支持此连接方式"),
+            ("br-connection-busy", "蓝牙正忙，请稍后重试"),
+            (
+                "br-connection-concurrent-connection-limit",
+                "连接的设备太多，请先断开其他设备",
+            ),
+            ("br-connection-timeout", "连接超时，请靠近设备后重试"),
+            (
+                "br-connection-refused",
+                "设备拒绝了连接，请让设备进入配对模式",
+            ),
+            ("br-connection-aborted-by-remote", "设备主动断开了连接"),
+            ("br-connection-aborted-by-local", "连接被本机中止，请重试"),
+            ("br-connection-lmp-protocol-error", "蓝牙协议出错，请重试"),
+            ("br-connection-canceled", "连接已取消"),
+            (
+                "br-connection-key-missing",
+                "配对信息已失效，请按START忘记设备后重新配对",
+            ),
+            ("le-connection-abort-by-local", "连接被本机中止，请重试"),
+            ("le-connection-abort-by-remote", "设备主动断开了连接"),
+            ("le-connection-timeout", "连接超时，请靠近设备后重试"),
+            (
+                "le-connection-key-missing",
+                "配对信息已失效，请按START忘记设备后重新配对",
+            ),
+        ],
+    },
+};
+
+fn zh_list_hint(count: usize) -> String {
+    format!(
+        "使用 ↑↓ 选择蓝牙设备，按A连接，按→设置，按←查看隐藏的设备，按SELECT断开，按START忘记，按L重新扫描（共{}个）：",
+        count
+    )
+}
+
+fn zh_list_more(count: usize) -> String {
+    format!("还有{}个信号较弱的设备，按A显示全部", count)
+}
+
+fn zh_tether_connected(interface: &str) -> String {
+    format!("已通过手机上网（{}）", interface)
+}
+
+fn zh_packet_stats(packets: &PacketStats) -> String {
+    format!(
+        "收{}，发{}，出错{}",
+        packets.received, packets.sent, packets.errors
+    )
+}
+
+fn zh_handheld_battery(level: u8, charging: bool) -> String {
+    if charging {
+        format!("本机电量：{}%（充电中）", level)
+    } else {
+        format!("本机电量：{}%", level)
+    }
+}
+
+fn zh_remap_title(step: usize, count: usize) -> String {
+    format!(
+        "重新设置按键（{}/{}），请按下用于以下操作的按键：",
+        step, count
+    )
+}
+
+fn zh_bluez_outdated(version: &str, minimum: &str) -> String {
+    format!(
+        "BlueZ {} 版本过旧，耳机电量等功能可能不可用，请升级到 {} 以上",
+        version, minimum
+    )
+}
+
+fn zh_crash_notice(name: &str) -> String {
+    format!("上次运行时程序崩溃，报告已保存到 {}", name)
+}
+
+static EN: Texts = Texts {
+    quitting: "Quitting…",
+    quit_hint: "Press B to quit, L3 for the connection profiles, R3 for the logs.",
+    no_controller: "No game controller found, only the keyboard works.",
+    power_hint: "Press Y to turn Bluetooth on, X to turn it off. Bluetooth: ",
+    power_on: "on",
+    power_off: "off",
+    scanning: "Scanning…",
+    scan_finished: "Scan finished. ",
+    connected_to: "Connected: ",
+    not_connected: "Not connected",
+    scan_failed: "Scan failed. ",
+    rescan_hint: "Press L to rescan",
+    list_hint: en_list_hint,
+    list_more: en_list_more,
+    connect_in_progress: [
+        "Disconnecting…",
+        "Pairing…",
+        "Connecting…",
+        "Pairing lost, pairing again…",
+        "Checking audio…",
+    ],
+    connect_failed: [
+        "Disconnect failed: ",
+        "Pairing failed: ",
+        "Connect failed: ",
+        "Pairing again failed: ",
+        "Audio check failed: ",
+    ],
+    connect_succeeded: "Connected",
+    link_quality: [
+        ", good signal",
+        ", fair signal",
+        ", weak signal, audio may drop out",
+    ],
+    connected_mark: " (connected)",
+    pairing_mode_mark: " (pairing mode)",
+    buds_battery_labels: ["L", "R", "Case "],
+    crash_notice: en_crash_notice,
+    reconnect_last_hint: "Press R to reconnect ",
+    favorite_hint: "MENU to favorite, favorites stay on top and connect with their number key",
+    auto_connect_title: "Auto connect: ",
+    auto_connect_waiting: "Turning Bluetooth on…",
+    auto_connect_hint: "Press B to quit, any other key for the device list.",
+    splash_searching: "Looking for the device…",
+    splash_disconnecting: "Disconnecting…",
+    splash_disconnected: "Disconnected",
+    splash_disconnect_failed: "Disconnect failed: ",
+    splash_no_device: "No device to connect, connect one from the device list or set a preferred device first",
+    splash_timed_out: "The connect took too long, check the device is on and nearby",
+    settings_title: "Device settings: ",
+    settings_labels: [
+        "Connect at start: ",
+        "Trust after pairing: ",
+        "Audio profile: ",
+        "Volume after connecting: ",
+        "Audio test: ",
+        "Hide from scans: ",
+    ],
+    details_labels: ["Manufacturer: ", "Model: ", "Firmware: ", "Hardware: "],
+    link_stats_labels: ["Signal: ", "Transmit power: ", "Adapter packets: "],
+    packet_stats: en_packet_stats,
+    profile_names: ["High quality (A2DP)", "Calls (HFP)"],
+    volume_unchanged: "unchanged",
+    settings_hint: "Use ↑↓ to select, ←→ to change, B to go back.",
+    hide_action: "press A",
+    audio_test_action: "press A",
+    audio_test_title: "Audio test: ",
+    audio_test_not_connected: "The device isn't connected, the sound plays on the speaker",
+    audio_test_hint: "Use ↑↓ to select, A to play or stop, B to go back.",
+    test_tone_names: ["Sine sweep (20 Hz to 20 kHz)", "Pink noise", "Left and right"],
+    test_tone_playing: " (playing)",
+    channel_names: ["Left", "Right"],
+    channel_test_hint: "The sound comes only from the lit side, from both it plays mono, from the \
+                        other one the channels are swapped",
+    hidden_title: "Hidden devices, A to toggle or unhide, B to go back:",
+    hidden_empty: "No hidden devices",
+    hidden_unnamed: "Devices without a name: ",
+    hidden_unnamed_values: ["shown", "hidden unless paired"],
+    remap_title: en_remap_title,
+    remap_actions: [
+        "Quit / Back",
+        "Turn Bluetooth on",
+        "Turn Bluetooth off",
+        "Up",
+        "Right",
+        "Down",
+        "Left",
+        "Rescan",
+        "Connect / Confirm",
+        "Disconnect",
+        "Forget",
+        "Reconnect the last device",
+        "Favorite",
+        "Show the logs",
+        "Connection profiles",
+    ],
+    remap_taken: "That one is taken already, press another",
+    remap_hint: "Press Esc on a keyboard to cancel, the keymap is saved to the config when done.",
+    logs_title: "Recent logs, ↑↓ to scroll, ←→ for pages, B to go back:",
+    sort_title: "Sorted by ",
+    sort_names: ["order found", "name", "signal"],
+    about_title: "About",
+    about_app: "App version: ",
+    about_bluez: "BlueZ version: ",
+    about_kernel: "Kernel version: ",
+    about_unknown: "unknown",
+    bluez_outdated: en_bluez_outdated,
+    about_keys: "Keyboard: arrows select, Enter connects, Backspace goes back, F5 rescans, +/- change the volume, D disconnects, F forgets, S favorites, P profiles, N tethering, G logs, K remaps, T switches the theme, E the language, O the sort order.",
+    about_hint: "Press B to go back.",
+    profiles_title: "Connection profiles, A to connect with the settings, B to go back:",
+    profiles_empty: "No connection profiles, add [[profiles]] to the config file",
+    network_title: "Bluetooth tethering, A to connect or disconnect the network of a phone, B to \
+                    go back:",
+    network_empty: "No paired phone shares its internet, turn on Bluetooth tethering on the \
+                    phone, then pair it and rescan",
+    tethered_mark: " (tethered)",
+    tether_connecting: "Connecting the network of the phone…",
+    tether_connected: en_tether_connected,
+    tether_failed: "Connecting the network of the phone failed: ",
+    connect_busy: "Connecting, please wait, SELECT cancels",
+    manager_busy: "Busy, please wait",
+    handheld_battery: en_handheld_battery,
+    low_battery: "Handheld battery below 10%, charge it first, pairing fails if it dies",
+    now_playing: "Playing: ",
+    now_paused: "Paused: ",
+    audio_switched_away: "The device switched its audio to another one, like a phone, and stays \
+                          connected",
+    audio_returned: "The device switched its audio back to the handheld",
+    not_audio_confirm: "This doesn't look like an audio device, connect anyway? Press A again",
+    bluez_busy: "Bluetooth is busy with another operation, connecting once it's done…",
+    pin_title: "Enter the PIN of ",
+    pin_delete: "Del",
+    pin_done: "OK",
+    pin_hint: "Use ←→ to select, A to type, B to cancel. The PIN is usually in the manual of the \
+               device.",
+    adapter_removed: "Bluetooth adapter removed",
+    adapter_removed_hint: "Plug the adapter in again to go on.",
+    preflight_title: "Bluetooth isn't ready:",
+    preflight_problems: [
+        "The Bluetooth service (bluetoothd) isn't running",
+        "No Bluetooth adapter found",
+        "Bluetooth is blocked by rfkill",
+        "No audio server found",
+    ],
+    preflight_fixes: [
+        "Restart the device, or run bluetoothd in a terminal",
+        "Check the device has Bluetooth, or plug in an adapter",
+        "Run rfkill unblock bluetooth in a terminal",
+        "Start PulseAudio, PipeWire or BlueALSA, or there is no sound once connected",
+    ],
+    preflight_hint: "Press A to check again, Y to go on anyway, B to quit.",
+    errors: ErrorTexts {
+        adapter_missing: "No Bluetooth adapter found",
+        bluez_down: "Bluetooth service is not running, restart the device and retry",
+        powered_off: "Bluetooth is off, press Y to turn it on",
+        pairing_rejected: "Pairing rejected, put the device in pairing mode and retry",
+        pairing_interrupted: "Pairing interrupted, keep the device in pairing mode and retry",
+        wrong_pin: "Wrong PIN, enter it again",
+        pin_required: "The device needs a PIN, see its manual",
+        page_timeout: "The device is off or not in pairing mode",
+        in_progress: "Bluetooth is busy, retry in a moment",
+        audio_routing_failed: "Connected but audio is unavailable, connect again",
+        device_not_found: "The device is gone, press L to rescan",
+        no_network_address: "The phone gave no network address, turn on Bluetooth tethering on it",
+        network_setup: "Setting up the network address failed, see the log",
+        bluez: &[
+            (
+                "br-connection-already-connected",
+                "The device is already connected",
+            ),
+            (
+                "br-connection-page-timeout",
+                "The device is off or not in pairing mode",
+            ),
+            (
+                "br-connection-profile-unavailable",
+                "The device doesn't support audio",
+            ),
+            (
+                "br-connection-sdp-search",
+                "Reading the device services failed, retry",
+            ),
+            (
+                "br-connection-create-socket",
+                "Can't open the connection, retry",
+            ),
+            (
+                "br-connection-adapter-not-powered",
+                "Bluetooth is off, press Y to turn it on",
+            ),
+            (
+                "br-connection-not-supported",
+                "The device doesn't support this connection",
+            ),
+            ("br-connection-busy", "Bluetooth is busy, retry later"),
+            (
+                "br-connection-concurrent-connection-limit",
+                "Too many devices connected, disconnect another one first",
+            ),
+            (
+                "br-connection-timeout",
+                "Timed out, move closer to the device and retry",
+            ),
+            (
+                "br-connection-refused",
+                "The device refused, put it in pairing mode",
+            ),
+            (
+                "br-connection-aborted-by-remote",
+                "The device closed the connection",
+            ),
+            (
+                "br-connection-aborted-by-local",
+                "The connection was aborted, retry",
+            ),
+            (
+                "br-connection-lmp-protocol-error",
+                "Bluetooth protocol error, retry",
+            ),
+            ("br-connection-canceled", "The connection was canceled"),
+            (
+                "br-connection-key-missing",
+                "The pairing is outdated, press START to forget the device and pair again",
+            ),
+            (
+                "le-connection-abort-by-local",
+                "The connection was aborted, retry",
+            ),
+            (
+                "le-connection-abort-by-remote",
+                "The device closed the connection",
+            ),
+            (
+                "le-connection-timeout",
+                "Timed out, move closer to the device and retry",
+            ),
+            (
+                "le-connection-key-missing",
+                "The pairing is outdated, press START to forget the device and pair again",
+            ),
+        ],
+    },
+};
+
+fn en_list_hint(count: usize) -> String {
+    format!(
+        "Use ↑↓ to select a device, A to connect, → for settings, ← for hidden devices, SELECT to \
+         disconnect, START to forget, L to rescan ({} found):",
+        count
+    )
+}
+
+fn en_list_more(count: usize) -> String {
+    format!("+{} more (filtered by signal), press A to show all", count)
+}
+
+fn en_tether_connected(interface: &str) -> String {
+    format!("Online through the phone ({})", interface)
+}
+
+fn en_packet_stats(packets: &PacketStats) -> String {
+    format!(
+        "{} in, {} out, {} failed",
+        packets.received, packets.sent, packets.errors
+    )
+}
+
+fn en_handheld_battery(level: u8, charging: bool) -> String {
+    if charging {
+        format!("Battery: {}% (charging)", level)
+    } else {
+        format!("Battery: {}%", level)
+    }
+}
+
+fn en_remap_title(step: usize, count: usize) -> String {
+    format!(
+        "Remap keys ({}/{}), press the key or button for:",
+        step, count
+    )
+}
+
+fn en_bluez_outdated(version: &str, minimum: &str) -> String {
+    format!(
+        "BlueZ {} is outdated, features like the headset battery may not work, {} or newer is \
+         needed",
+        version, minimum
+    )
+}
+
+fn en_crash_notice(name: &str) -> String {
+    format!("The app crashed last time, the report is saved to {}", name)
+}
@@ -0,0 +1,38 @@
+use crate::config::Theme;
+use sdl2::pixels::Color;
+
+/// Colors of a theme, the status colors (green, red) are shared by all themes.
+#[derive(Clone, Copy)]
+pub struct Palette {
+    pub background: Color,
+    pub text: Color,
+    /// Secondary text, like the unselected devices.
+    pub hint: Color,
+    /// Progress, like scanning and connecting.
+    pub info: Color,
+    pub highlight: Color,
+    pub highlight_text: Color,
+}
+
+impl Palette {
+    pub fn of(theme: Theme) -> Self {
+        match theme {
+            Theme::Light => Self {
+                background: Color::RGB(255, 255, 255),
+                text: Color::RGB(0, 0, 0),
+                hint: Color::RGB(100, 100, 100),
+                info: Color::RGB(0, 0, 255),
+                highlight: Color::RGB(0, 120, 215),
+                highlight_text: Color::RGB(255, 255, 255),
+            },
+            Theme::Dark => Self {
+                background: Color::RGB(24, 24, 24),
+                text: Color::RGB(230, 230, 230),
+                hint: Color::RGB(150, 150, 150),
+                info: Color::RGB(90, 160, 255),
+                highlight: Color::RGB(0, 120, 215),
+                highlight_text: Color::RGB(255, 255, 255),
+            },
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::ui::{ease_out, text_drawer::TextDrawer, texts::Texts};
+use bluer::Address;
+use bluetooth_audio_connector_tg5040::{BudsBattery, DeviceInfo};
+
+/// The text of a device in the list, favorites get a star and their hotkey.
+/// `show_address` puts the address after the name, to tell apart devices
+/// with the same name.
+pub fn device_label(
+    device: &DeviceInfo, favorites: &[Address], show_address: bool, texts: &Texts,
+) -> String {
+    let mut label = match favorites.iter().position(|addr| *addr == device.addr) {
+        Some(index) if index < 9 => format!("★{} ", index + 1),
+        Some(_) => "★ ".to_string(),
+        None => String::new(),
+    };
+    if device.name.is_empty() {
+        label.push_str(&device.addr.to_string());
+    } else {
+        label.push_str(&device.name);
+        if show_address {
+            label.push_str(&format!(" ({})", device.addr));
+        }
+    }
+    if device.pairing_advertisement.is_some() && !device.paired {
+        label.push_str(texts.pairing_mode_mark);
+    }
+    if device.connected {
+        label.push_str(texts.connected_mark);
+        if let Some((_, battery)) = &device.buds_battery {
+            label.push_str(&buds_battery_text(battery, texts));
+        }
+    }
+    label
+}
+
+/// Like " L70% R60%↑", the parts the earbuds don't tell are left out.
+fn buds_battery_text(battery: &BudsBattery, texts: &Texts) -> String {
+    [battery.left, battery.right, battery.case]
+        .into_iter()
+        .zip(texts.buds_battery_labels)
+        .filter_map(|(part, label)| {
+            let part = part?;
+            let charging = if part.charging { "↑" } else { "" };
+            Some(format!(" {}{}%{}", label, part.level, charging))
+        })
+        .collect()
+}
+
+/// Scrollable device list with an animated selection highlight.
+#[derive(Default)]
+pub struct DeviceList {
+    // animated positions, counted in rows
+    scroll: f32,
+    highlight_row: f32,
+}
+
+impl DeviceList {
+    /// Draw the rows from `y` down to `bottom`, returns where the list ends.
+    pub fn draw(
+        &mut self, drawer: &mut TextDrawer, labels: &[String], selected_index: usize, y: u32,
+        bottom: u32,
+    ) -> anyhow::Result<u32> {
+        let line_height = drawer.line_height();
+        let rows = (bottom.saturating_sub(y) / line_height).max(1) as usize;
+        let first_row = (selected_index + 1).saturating_sub(rows);
+
+        self.scroll = ease_out(self.scroll, first_row as f32);
+        self.highlight_row = ease_out(self.highlight_row, selected_index as f32);
+
+        let scroll = self.scroll;
+        let row_to_y = |row: f32| (y as f32 + (row - scroll) * line_height as f32).max(0.) as u32;
+
+        // rows scrolling in and out are cut at the list edges
+        drawer.set_clip_rect(0, y, drawer.remaining_width(0), rows as u32 * line_height);
+
+        if !labels.is_empty() {
+            drawer.fill_rect(
+                drawer.palette.highlight,
+                0,
+                row_to_y(self.highlight_row),
+                drawer.remaining_width(0),
+                line_height,
+            )?;
+        }
+
+        for (index, label) in labels
+            .iter()
+            .enumerate()
+            .skip(scroll as usize)
+            .take(rows + 1)
+        {
+            let color = if index == selected_index {
+                drawer.palette.highlight_text
+            } else {
+                drawer.palette.hint
+            };
+
+            drawer.draw_truncated(
+                label,
+                color,
+                0,
+                row_to_y(index as f32),
+                drawer.remaining_width(0),
+            )?;
+        }
+
+        drawer.reset_clip_rect();
+
+        Ok(y + rows as u32 * line_height)
+    }
+}
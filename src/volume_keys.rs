@@ -0,0 +1,122 @@
+use crate::{bluetooth::manager::Command, state::AppState};
+use std::{fs, mem, path::Path};
+use tokio::{
+    fs::File,
+    io::AsyncReadExt,
+    sync::{mpsc, watch},
+};
+use tracing::{debug, info, warn};
+
+const INPUT_CLASS_DIR: &str = "/sys/class/input";
+
+// the codes of `linux/input-event-codes.h`
+const EV_KEY: u16 = 0x01;
+const KEY_VOLUMEDOWN: u16 = 114;
+const KEY_VOLUMEUP: u16 = 115;
+// held keys repeat with 2, only the presses count
+const KEY_PRESSED: i32 = 1;
+
+/// Percent changed by each press of a volume key.
+pub const VOLUME_KEY_STEP: i8 = 5;
+
+// `struct input_event`, a `timeval` of two longs then type, code and value
+const TIMEVAL_SIZE: usize = 2 * mem::size_of::<isize>();
+const EVENT_SIZE: usize = TIMEVAL_SIZE + 8;
+
+/// Read the volume keys of the handheld from evdev and change the volume of
+/// the connected Bluetooth device with them. The firmware only applies them to
+/// the speaker, whose volume they still change, the keys aren't grabbed.
+pub fn spawn(command_tx: mpsc::Sender<Command>, state_rx: watch::Receiver<AppState>) {
+    let devices = volume_key_devices();
+    if devices.is_empty() {
+        debug!("no input device with volume keys");
+        return;
+    }
+
+    for path in devices {
+        info!(path, "forward volume keys");
+        tokio::spawn(forward(path, command_tx.clone(), state_rx.clone()));
+    }
+}
+
+/// The event devices which have both volume keys, like `gpio-keys`.
+fn volume_key_devices() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(INPUT_CLASS_DIR) else {
+        return Vec::new();
+    };
+    let mut devices = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter(|entry| has_volume_keys(&entry.path()))
+        .map(|entry| format!("/dev/input/{}", entry.file_name().to_string_lossy()))
+        .collect::<Vec<_>>();
+    devices.sort();
+    devices
+}
+
+/// The key bitmap of the device is hex words of a long each, the highest
+/// first.
+fn has_volume_keys(path: &Path) -> bool {
+    let Ok(bitmap) = fs::read_to_string(path.join("device/capabilities/key")) else {
+        return false;
+    };
+    let words = bitmap
+        .split_whitespace()
+        .rev()
+        .map(|word| u64::from_str_radix(word, 16).unwrap_or(0))
+        .collect::<Vec<_>>();
+    let word_bits = mem::size_of::<isize>() * 8;
+    let has_key = |code: u16| {
+        let code = code as usize;
+        words
+            .get(code / word_bits)
+            .is_some_and(|word| word & (1 << (code % word_bits)) != 0)
+    };
+    has_key(KEY_VOLUMEUP) && has_key(KEY_VOLUMEDOWN)
+}
+
+async fn forward(
+    path: String, command_tx: mpsc::Sender<Command>, state_rx: watch::Receiver<AppState>,
+) {
+    let mut file = match File::open(&path).await {
+        Ok(file) => file,
+        Err(err) => {
+            warn!(?err, path, "open input device failed");
+            return;
+        }
+    };
+
+    let mut event = [0; EVENT_SIZE];
+    loop {
+        if let Err(err) = file.read_exact(&mut event).await {
+            warn!(?err, path, "read input device failed");
+            return;
+        }
+
+        let kind = u16::from_ne_bytes([event[TIMEVAL_SIZE], event[TIMEVAL_SIZE + 1]]);
+        let code = u16::from_ne_bytes([event[TIMEVAL_SIZE + 2], event[TIMEVAL_SIZE + 3]]);
+        let value = i32::from_ne_bytes([
+            event[TIMEVAL_SIZE + 4],
+            event[TIMEVAL_SIZE + 5],
+            event[TIMEVAL_SIZE + 6],
+            event[TIMEVAL_SIZE + 7],
+        ]);
+        if kind != EV_KEY || value != KEY_PRESSED {
+            continue;
+        }
+        let step = match code {
+            KEY_VOLUMEUP => VOLUME_KEY_STEP,
+            KEY_VOLUMEDOWN => -VOLUME_KEY_STEP,
+            _ => continue,
+        };
+
+        // the speaker has the keys then
+        if state_rx.borrow().connected_device().is_none() {
+            continue;
+        }
+        // a busy manager drops the press, the next one gets through
+        if command_tx.try_send(Command::ChangeVolume(step)).is_err() {
+            debug!(step, "manager busy, drop volume key");
+        }
+    }
+}
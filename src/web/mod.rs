@@ -0,0 +1,149 @@
+use crate::cli::DeviceOutput;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use bluetooth_audio_connector_tg5040::{
+    AppState, BluetoothConnectState, BluetoothScanStatus, Command,
+};
+use serde::Serialize;
+use std::{net::SocketAddr, sync::Arc};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, watch},
+};
+use tracing::{error, info, warn};
+
+const INDEX_HTML: &str = include_str!("index.html");
+
+#[derive(Clone)]
+struct WebState {
+    /// Wanted by the actions, anyone reaching the port may take them without.
+    token: Option<Arc<str>>,
+    command_tx: mpsc::Sender<Command>,
+    state_rx: watch::Receiver<AppState>,
+}
+
+#[derive(Serialize)]
+struct StateOutput<'a> {
+    powered: bool,
+    scan: &'static str,
+    connect: &'static str,
+    devices: Vec<DeviceOutput<'a>>,
+}
+
+/// Serve a page with the device list and connect buttons, so a phone browser
+/// can manage the Bluetooth audio of a docked handheld. The actions want the
+/// token, when there's one; the device list is open to anyone reaching the
+/// port.
+pub async fn spawn(
+    addr: SocketAddr, token: Option<String>, command_tx: mpsc::Sender<Command>,
+    state_rx: watch::Receiver<AppState>,
+) -> anyhow::Result<()> {
+    if token.is_none() {
+        warn!(%addr, "web remote has no token, anyone on the network can connect devices");
+    }
+
+    let router = Router::new()
+        .route("/", get(index))
+        .route("/api/state", get(state))
+        .route("/api/scan", post(scan))
+        .route("/api/connect/:address", post(connect))
+        .route("/api/disconnect", post(disconnect))
+        .with_state(WebState {
+            token: token.map(Arc::from),
+            command_tx,
+            state_rx,
+        });
+
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "serve web remote");
+
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, router).await {
+            error!(?err, "web remote stopped");
+        }
+    });
+
+    Ok(())
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+async fn state(State(web): State<WebState>) -> Response {
+    let state = web.state_rx.borrow().clone();
+
+    let scan = match state.scan_status {
+        BluetoothScanStatus::Disable => "disable",
+        BluetoothScanStatus::Scanning => "scanning",
+        BluetoothScanStatus::Finished => "finished",
+        BluetoothScanStatus::Failed => "failed",
+    };
+    let connect = match state.connect_state {
+        BluetoothConnectState::Idle => "idle",
+        BluetoothConnectState::InProgress(_) => "connecting",
+        BluetoothConnectState::Connected => "connected",
+        BluetoothConnectState::Failed { .. } => "failed",
+    };
+
+    Json(StateOutput {
+        powered: state.powered,
+        scan,
+        connect,
+        devices: state.devices.iter().map(DeviceOutput::from).collect(),
+    })
+    .into_response()
+}
+
+async fn scan(State(web): State<WebState>, headers: HeaderMap) -> StatusCode {
+    send(&web, &headers, Command::Scan).await
+}
+
+async fn connect(
+    State(web): State<WebState>, headers: HeaderMap, Path(address): Path<String>,
+) -> StatusCode {
+    match address.parse() {
+        Ok(addr) => send(&web, &headers, Command::Connect(addr)).await,
+        Err(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+async fn disconnect(State(web): State<WebState>, headers: HeaderMap) -> StatusCode {
+    send(&web, &headers, Command::Disconnect).await
+}
+
+async fn send(web: &WebState, headers: &HeaderMap, command: Command) -> StatusCode {
+    if !authorized(web, headers) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    match web.command_tx.send(command).await {
+        Ok(()) => StatusCode::ACCEPTED,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// Whether the request has the token, compared in constant time so it can't
+/// be guessed a byte at a time.
+fn authorized(web: &WebState, headers: &HeaderMap) -> bool {
+    let Some(token) = &web.token else {
+        return true;
+    };
+    let Some(given) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}